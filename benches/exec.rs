@@ -0,0 +1,199 @@
+//! Benchmarks for executing machines and for preparing their rendering,
+//! using machines from `test_machines/`. Run with `cargo bench`.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::mem;
+use std::path::Path;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use nalgebra as na;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use ultimate_scale::exec::{view, Exec, ExecView, TickTime};
+use ultimate_scale::machine::{grid, Block, BlockIndex, Machine, PlacedBlock, SavedMachine};
+use ultimate_scale::render;
+
+/// Machines from `test_machines/` to benchmark. `stress_wind_source` consists
+/// mostly of wind, so it shows the cost of wind propagation.
+const MACHINES: &[&str] = &[
+    "stress",
+    "stress_wind_source",
+    "hilbert",
+    "falling_extravaganza",
+];
+
+/// Number of ticks to run in each iteration of the execution benchmark.
+const NUM_TICKS: usize = 100;
+
+/// Number of ticks to run before measuring rendering preparation, so that
+/// there is some wind and some blips to render.
+const NUM_WARMUP_TICKS: usize = 20;
+
+fn load_machine(name: &str) -> Machine {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("test_machines")
+        .join(format!("{}.json", name));
+    let file = File::open(&path).unwrap();
+    let saved_machine: SavedMachine = serde_json::from_reader(BufReader::new(file)).unwrap();
+
+    saved_machine.into_machine().unwrap()
+}
+
+/// A large sandbox with only one block in each layer.
+fn sparse_sandbox() -> Machine {
+    let mut machine = Machine::new_sandbox(grid::Vector3::new(60, 60, 40));
+
+    for z in 0..40 {
+        machine.set(
+            &grid::Point3::new(z, z, z),
+            Some(PlacedBlock {
+                block: Block::Solid,
+            }),
+        );
+    }
+
+    machine
+}
+
+fn new_exec(machine: &Machine) -> Exec {
+    // Use the same level inputs in every run.
+    Exec::new(machine.clone(), &mut StdRng::seed_from_u64(0))
+}
+
+fn tick_time(num_ticks_passed: usize, tick_progress: f32) -> TickTime {
+    let mut time = TickTime::zero();
+    time.num_ticks_passed = num_ticks_passed;
+    time.next_tick_timer.set_progress(tick_progress);
+    time
+}
+
+fn bench_exec_update(c: &mut Criterion) {
+    let mut group = c.benchmark_group("exec_update");
+
+    for name in MACHINES {
+        let machine = load_machine(name);
+
+        group.bench_function(*name, |b| {
+            b.iter_batched(
+                || new_exec(&machine),
+                |mut exec| {
+                    for _ in 0..NUM_TICKS {
+                        exec.update();
+                    }
+                    exec
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+/// Prints the memory used for looking up blocks by their position, compared
+/// to a dense grid of the same size, and measures looking up every position.
+fn bench_index(c: &mut Criterion) {
+    let mut group = c.benchmark_group("index");
+    let machines = vec![
+        ("stress", load_machine("stress")),
+        ("sparse_sandbox", sparse_sandbox()),
+    ];
+
+    for (name, machine) in machines.iter() {
+        let (num_chunks, num_bytes) = machine.index_memory();
+        let size = machine.size();
+        let dense_bytes =
+            (size.x * size.y * size.z) as usize * mem::size_of::<Option<BlockIndex>>();
+
+        println!(
+            "{}: {} chunks, {} bytes (dense grid: {} bytes)",
+            name, num_chunks, num_bytes, dense_bytes,
+        );
+
+        group.bench_function(*name, |b| {
+            b.iter(|| {
+                let mut num_found = 0;
+                for z in 0..size.z {
+                    for y in 0..size.y {
+                        for x in 0..size.x {
+                            if machine.get_index(&grid::Point3::new(x, y, z)).is_some() {
+                                num_found += 1;
+                            }
+                        }
+                    }
+                }
+                num_found
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_render_machine(c: &mut Criterion) {
+    let mut group = c.benchmark_group("render_machine");
+
+    for name in MACHINES {
+        let mut exec = new_exec(&load_machine(name));
+        for _ in 0..NUM_WARMUP_TICKS {
+            exec.update();
+        }
+
+        let time = tick_time(exec.cur_tick(), 0.5);
+
+        group.bench_function(*name, |b| {
+            b.iter(|| {
+                let mut stage = render::Stage::default();
+                render::machine::render_machine(
+                    exec.machine(),
+                    &time,
+                    Some(&exec),
+                    |_| true,
+                    |_| false,
+                    &mut stage,
+                );
+                stage
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_transduce(c: &mut Criterion) {
+    let mut group = c.benchmark_group("transduce");
+
+    for name in MACHINES {
+        let mut exec_view = ExecView::new(&view::Config::default(), load_machine(name), &[]);
+        for _ in 0..NUM_WARMUP_TICKS {
+            exec_view.run_tick();
+        }
+
+        // Transduce a whole tick, starting at the previous one, so that the
+        // events of the tick are computed in each iteration.
+        let prev_time = tick_time(NUM_WARMUP_TICKS - 1, 1.0);
+        let time = tick_time(NUM_WARMUP_TICKS, 1.0);
+        let eye_pos = na::Point3::new(15.0, 15.0, 30.0);
+
+        group.bench_function(*name, |b| {
+            b.iter(|| {
+                let mut stage = render::Stage::default();
+                exec_view.transduce(&prev_time, &time, &eye_pos, &mut stage);
+                stage
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_exec_update,
+    bench_index,
+    bench_render_machine,
+    bench_transduce
+);
+criterion_main!(benches);
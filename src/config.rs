@@ -27,4 +27,5 @@ pub struct Config {
     pub editor: edit::Config,
     pub exec: exec::view::Config,
     pub play: exec::play::Config,
+    pub batch: exec::batch::Config,
 }
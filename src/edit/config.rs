@@ -77,7 +77,18 @@ pub struct Config {
     pub rotate_block_cw_key: ModifiedKey,
     pub rotate_block_ccw_key: ModifiedKey,
     pub mirror_y_key: ModifiedKey,
+    pub rotate_x_cw_key: ModifiedKey,
+    pub rotate_x_ccw_key: ModifiedKey,
+    pub rotate_y_cw_key: ModifiedKey,
+    pub rotate_y_ccw_key: ModifiedKey,
+    pub mirror_x_key: ModifiedKey,
+    pub mirror_z_key: ModifiedKey,
     pub block_kind_key: ModifiedKey,
+    pub block_channel_key: ModifiedKey,
+    pub timer_period_down_key: ModifiedKey,
+    pub timer_period_up_key: ModifiedKey,
+    pub timer_phase_down_key: ModifiedKey,
+    pub timer_phase_up_key: ModifiedKey,
 
     pub undo_key: ModifiedKey,
     pub redo_key: ModifiedKey,
@@ -96,8 +107,14 @@ pub struct Config {
 
     pub select_key: ModifiedKey,
     pub select_layer_bound_key: ModifiedKey,
+    pub box_select_key: ModifiedKey,
+    pub pin_key: ModifiedKey,
     pub pipe_tool_key: ModifiedKey,
     pub block_keys: Vec<(ModifiedKey, Block)>,
+
+    /// Blocks defined by mods. These have no shortcuts.
+    pub mod_blocks: Vec<Block>,
+
     pub layer_keys: Vec<(ModifiedKey, isize)>,
 }
 
@@ -109,7 +126,18 @@ impl Default for Config {
             rotate_block_cw_key: ModifiedKey::new(VirtualKeyCode::R),
             rotate_block_ccw_key: ModifiedKey::shift(VirtualKeyCode::R),
             mirror_y_key: ModifiedKey::new(VirtualKeyCode::M),
+            rotate_x_cw_key: ModifiedKey::new(VirtualKeyCode::T),
+            rotate_x_ccw_key: ModifiedKey::shift(VirtualKeyCode::T),
+            rotate_y_cw_key: ModifiedKey::new(VirtualKeyCode::G),
+            rotate_y_ccw_key: ModifiedKey::shift(VirtualKeyCode::G),
+            mirror_x_key: ModifiedKey::shift(VirtualKeyCode::M),
+            mirror_z_key: ModifiedKey::ctrl(VirtualKeyCode::M),
             block_kind_key: ModifiedKey::new(VirtualKeyCode::C),
+            block_channel_key: ModifiedKey::new(VirtualKeyCode::N),
+            timer_period_down_key: ModifiedKey::new(VirtualKeyCode::LBracket),
+            timer_period_up_key: ModifiedKey::new(VirtualKeyCode::RBracket),
+            timer_phase_down_key: ModifiedKey::ctrl(VirtualKeyCode::LBracket),
+            timer_phase_up_key: ModifiedKey::ctrl(VirtualKeyCode::RBracket),
             undo_key: ModifiedKey::ctrl(VirtualKeyCode::Z),
             redo_key: ModifiedKey::ctrl(VirtualKeyCode::Y),
             copy_key: ModifiedKey::ctrl(VirtualKeyCode::C),
@@ -122,6 +150,8 @@ impl Default for Config {
             select_all_key: ModifiedKey::ctrl(VirtualKeyCode::A),
             select_key: ModifiedKey::new(VirtualKeyCode::Key1),
             select_layer_bound_key: ModifiedKey::ctrl(VirtualKeyCode::Key1),
+            box_select_key: ModifiedKey::new(VirtualKeyCode::B),
+            pin_key: ModifiedKey::new(VirtualKeyCode::I),
             pipe_tool_key: ModifiedKey::new(VirtualKeyCode::Key2),
             block_keys: vec![
                 (
@@ -140,6 +170,15 @@ impl Default for Config {
                     },
                 ),
                 (ModifiedKey::new(VirtualKeyCode::Key5), Block::WindSource),
+                (
+                    ModifiedKey::new(VirtualKeyCode::Key0),
+                    Block::Timer {
+                        out_dir: Dir3::X_POS,
+                        kind: BlipKind::A,
+                        period: 4,
+                        phase: 0,
+                    },
+                ),
                 (
                     ModifiedKey::new(VirtualKeyCode::Key6),
                     Block::FunnelXY {
@@ -194,6 +233,27 @@ impl Default for Config {
                         flow_dir: Dir3::X_POS,
                     },
                 ),
+                (
+                    ModifiedKey::ctrl(VirtualKeyCode::Key8),
+                    Block::Portal {
+                        open_dir: Dir3::X_NEG,
+                        channel: 0,
+                    },
+                ),
+                (
+                    ModifiedKey::ctrl(VirtualKeyCode::Key9),
+                    Block::Transmitter {
+                        in_dir: Dir3::X_NEG,
+                        channel: 0,
+                    },
+                ),
+                (
+                    ModifiedKey::ctrl(VirtualKeyCode::Key0),
+                    Block::Receiver {
+                        out_dir: Dir3::X_POS,
+                        channel: 0,
+                    },
+                ),
                 /*(
                     ModifiedKey::ctrl(VirtualKeyCode::Key1),
                     Block::Pipe(Dir3::Y_NEG, Dir3::Y_POS),
@@ -232,6 +292,7 @@ impl Default for Config {
                     },
                 ),*/
             ],
+            mod_blocks: Vec::new(),
             layer_keys: vec![
                 (ModifiedKey::new(VirtualKeyCode::F1), 0),
                 (ModifiedKey::new(VirtualKeyCode::F2), 1),
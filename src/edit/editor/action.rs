@@ -1,6 +1,20 @@
-use crate::edit::{Edit, Editor, Mode, Piece};
+use std::collections::HashMap;
+
+use crate::edit::piece::Transform;
+use log::info;
+
+use crate::edit::{route, Edit, Editor, Mode, Piece, SelectionMode};
+use crate::machine::param::ParamValue;
 use crate::machine::{grid, Block, PlacedBlock};
 
+const XY_ONLY_NOTICE: &str =
+    "Funnels, pipe buttons and blip detectors cannot be rotated out of the XY plane.";
+
+const ROUTE_ENDS_NOTICE: &str = "Select two blocks to route a pipe between them.";
+
+const NO_ROUTE_NOTICE: &str =
+    "Found no free path for a pipe between the first and the last selected block.";
+
 #[allow(unused)]
 /// Actions that can be accessed by buttons and shortcuts in the editor.
 /// This has now been turned into an enum to allow UI to run in the main
@@ -19,17 +33,37 @@ pub enum Action {
     SelectAll,
     SelectMode,
     SelectLayerBoundMode,
+    BoxSelectMode,
+    ChangeBoxHeight(isize),
+    SelectConnected,
+    SelectSameType,
+    AutoRoute,
+    SetRouteDirs(Option<grid::Dir3>, Option<grid::Dir3>),
+    TogglePin,
     PipeToolMode,
     PlaceBlockMode(Block),
     Cancel,
     RotateCW,
     RotateCCW,
     MirrorY,
+    Transform(Transform),
     NextKind,
+    NextChannel,
+    ChangeTimer {
+        period_delta: isize,
+        phase_delta: isize,
+    },
+    SetParam(&'static str, ParamValue),
+    ResizeSide(grid::Dir3, isize),
+    CropToBlocks,
+    ShiftBlocks(grid::Dir3),
+    ConfirmResize,
 }
 
 impl Editor {
     pub fn run_action(&mut self, action: Action) {
+        self.notice = None;
+
         match action {
             Action::Undo => self.action_undo(),
             Action::Redo => self.action_redo(),
@@ -43,13 +77,33 @@ impl Editor {
             Action::SelectAll => self.action_select_all(),
             Action::SelectMode => self.action_select_mode(),
             Action::SelectLayerBoundMode => self.action_select_layer_bound_mode(),
+            Action::BoxSelectMode => self.action_box_select_mode(),
+            Action::ChangeBoxHeight(delta) => self.action_change_box_height(delta),
+            Action::SelectConnected => self.action_select_connected(),
+            Action::SelectSameType => self.action_select_same_type(),
+            Action::AutoRoute => self.action_auto_route(),
+            Action::SetRouteDirs(source_dir, target_dir) => {
+                self.action_set_route_dirs(source_dir, target_dir)
+            }
+            Action::TogglePin => self.action_toggle_pin(),
             Action::PipeToolMode => self.action_pipe_tool_mode(),
             Action::PlaceBlockMode(block) => self.action_place_block_mode(block),
             Action::Cancel => self.action_cancel(),
             Action::RotateCW => self.action_rotate_cw(),
             Action::RotateCCW => self.action_rotate_ccw(),
             Action::MirrorY => self.action_mirror_y(),
+            Action::Transform(transform) => self.action_transform(transform),
             Action::NextKind => self.action_next_kind(),
+            Action::NextChannel => self.action_next_channel(),
+            Action::ChangeTimer {
+                period_delta,
+                phase_delta,
+            } => self.action_change_timer(period_delta, phase_delta),
+            Action::SetParam(name, value) => self.action_set_param(name, value),
+            Action::ResizeSide(dir, delta) => self.action_resize_side(dir, delta),
+            Action::CropToBlocks => self.action_crop_to_blocks(),
+            Action::ShiftBlocks(dir) => self.action_shift_blocks(dir),
+            Action::ConfirmResize => self.action_confirm_resize(),
         }
     }
 
@@ -215,6 +269,113 @@ impl Editor {
         self.go_into_select_mode(true);
     }
 
+    pub fn action_box_select_mode(&mut self) {
+        let mut existing_selection = self
+            .mode
+            .selection()
+            .cloned()
+            .unwrap_or_else(|| SelectionMode::new(false));
+        existing_selection.set_is_layer_bound(self.current_layer, false);
+
+        self.mode = Mode::BoxSelect {
+            existing_selection,
+            start_pos: None,
+            height: 1,
+        };
+    }
+
+    pub fn action_change_box_height(&mut self, delta: isize) {
+        let max_height = self.machine.size().z - self.current_layer;
+
+        if let Mode::BoxSelect { height, .. } = &mut self.mode {
+            *height = (*height + delta).min(max_height).max(1);
+        }
+    }
+
+    /// Selects all blocks that are connected to the selected blocks, or to
+    /// the block under the mouse.
+    pub fn action_select_connected(&mut self) {
+        let start = self.selection_or_mouse_block();
+        let connected = self.machine.connected_blocks(&start);
+
+        self.mode = self.overwrite_selection(connected.into_iter(), self.mode.clone());
+    }
+
+    /// Selects all blocks that have the same type as the most recently
+    /// selected block, or as the block under the mouse.
+    pub fn action_select_same_type(&mut self) {
+        let pos = self
+            .mode
+            .selection()
+            .and_then(|selection| selection.newest_point())
+            .or(self.mouse_block_pos);
+        let block = match pos.and_then(|pos| self.machine.get(&pos)) {
+            Some(placed_block) => placed_block.block.clone(),
+            None => return,
+        };
+
+        let same_type: Vec<_> = self
+            .machine
+            .iter_blocks()
+            .filter(|(_, (_, placed_block))| placed_block.block.has_same_type(&block))
+            .map(|(_, (pos, _))| *pos)
+            .collect();
+
+        self.mode = self.overwrite_selection(same_type.into_iter(), self.mode.clone());
+    }
+
+    /// Connects the first and the last selected block with a shortest path
+    /// of pipes, using the holes picked by `action_set_route_dirs`.
+    pub fn action_auto_route(&mut self) {
+        let (source, target) = match self.route_ends() {
+            Some(ends) => ends,
+            None => {
+                self.notice = Some(ROUTE_ENDS_NOTICE.to_string());
+                return;
+            }
+        };
+        let (source_dir, target_dir) = self
+            .route_holes()
+            .map_or((None, None), |holes| (holes.source_dir, holes.target_dir));
+
+        if let Some(path) =
+            route::find_route(&self.machine, &source, source_dir, &target, target_dir)
+        {
+            let blocks = route::route_pipes(&self.machine, &path);
+            self.run_and_track_edit(Edit::set_blocks_combine(&self.machine, blocks));
+        } else {
+            info!("Found no route from {:?} to {:?}", source, target);
+            self.notice = Some(NO_ROUTE_NOTICE.to_string());
+        }
+    }
+
+    /// Picks the holes of the source and the target block that
+    /// `action_auto_route` connects. `None` allows any hole.
+    pub fn action_set_route_dirs(
+        &mut self,
+        source_dir: Option<grid::Dir3>,
+        target_dir: Option<grid::Dir3>,
+    ) {
+        self.route_dirs = (source_dir, target_dir);
+    }
+
+    /// Pins the selected blocks, or the block under the mouse, so that their
+    /// activations are traced while the machine is running. If all of them
+    /// are pinned already, they are unpinned instead.
+    pub fn action_toggle_pin(&mut self) {
+        let positions = self.selection_or_mouse_block();
+
+        if positions.iter().all(|pos| self.pinned.contains(pos)) {
+            self.pinned.retain(|pos| !positions.contains(pos));
+        } else {
+            for pos in positions {
+                if !self.pinned.contains(&pos) {
+                    self.pinned.push(pos);
+                }
+            }
+        }
+    }
+
     pub fn action_pipe_tool_mode(&mut self) {
         self.mode = Mode::new_pipe_tool();
     }
@@ -228,6 +389,8 @@ impl Editor {
     }
 
     pub fn action_cancel(&mut self) {
+        self.pending_resize = None;
+
         self.mode = match &self.mode {
             Mode::DragAndDrop { selection, .. } => Mode::new_selection(selection.clone()),
             Mode::PipeTool { last_pos, .. } if last_pos.is_some() => Mode::new_pipe_tool(),
@@ -297,6 +460,69 @@ impl Editor {
         }
     }
 
+    /// Rotates or mirrors the blocks to be placed, or the selected blocks
+    /// around their center. Without a selection, the block under the mouse is
+    /// transformed in place.
+    pub fn action_transform(&mut self, transform: Transform) {
+        let machine = &self.machine;
+        let mut edit = None;
+
+        match &mut self.mode {
+            Mode::PlacePiece { piece, .. } | Mode::DragAndDrop { piece, .. } => {
+                if piece.can_transform(&transform) {
+                    piece.transform(&transform);
+                } else {
+                    self.notice = Some(XY_ONLY_NOTICE.to_string());
+                }
+            }
+            Mode::Select { selection, .. } => {
+                let points: Vec<_> = if !selection.is_empty() {
+                    selection.to_vec()
+                } else {
+                    self.mouse_block_pos.into_iter().collect()
+                };
+
+                let mut piece = Piece::new_from_selection(machine, points.iter().cloned());
+                if piece.blocks().is_empty() {
+                    return;
+                }
+                if !piece.can_transform(&transform) {
+                    self.notice = Some(XY_ONLY_NOTICE.to_string());
+                    return;
+                }
+
+                let center = piece.min_pos() + piece.extent() / 2;
+                piece.transform(&Transform::Seq(vec![
+                    Transform::Shift(-center.coords),
+                    transform,
+                    Transform::Shift(center.coords),
+                ]));
+
+                if piece
+                    .blocks()
+                    .iter()
+                    .all(|(pos, _)| machine.is_valid_pos(pos))
+                {
+                    let mut blocks: HashMap<_, _> = points.iter().map(|p| (*p, None)).collect();
+                    blocks.extend(piece.iter().map(|(pos, block)| (pos, Some(block))));
+
+                    edit = Some((Edit::SetBlocks(blocks), piece));
+                }
+            }
+            _ => {
+                // No op in other modes.
+            }
+        }
+
+        if let Some((edit, piece)) = edit {
+            self.run_and_track_edit(edit);
+            self.mode = self.overwrite_selection(
+                piece.blocks().iter().map(|(pos, _)| *pos),
+                self.mode.clone(),
+            );
+        }
+    }
+
     pub fn action_next_kind(&mut self) {
         let mut edit = None;
 
@@ -323,4 +549,168 @@ impl Editor {
             self.run_and_track_edit(edit);
         }
     }
+
+    pub fn action_next_channel(&mut self) {
+        let mut edit = None;
+
+        match &mut self.mode {
+            Mode::PlacePiece { piece, .. } => {
+                piece.set_next_channel();
+            }
+            Mode::Select { selection, .. } => {
+                if !selection.is_empty() {
+                    edit = Some(Edit::NextChannel(selection.to_vec()));
+                } else if let Some(mouse_block_pos) = self.mouse_block_pos {
+                    edit = Some(Edit::NextChannel(vec![mouse_block_pos]));
+                }
+            }
+            Mode::DragAndDrop { piece, .. } => {
+                piece.set_next_channel();
+            }
+            _ => {
+                // No op in other modes.
+            }
+        };
+
+        if let Some(edit) = edit {
+            self.run_and_track_edit(edit);
+        }
+    }
+
+    pub fn action_change_timer(&mut self, period_delta: isize, phase_delta: isize) {
+        self.mutate_blocks(|block| block.change_timer(period_delta, phase_delta));
+    }
+
+    pub fn action_set_param(&mut self, name: &'static str, value: ParamValue) {
+        let mut edit = None;
+
+        match &mut self.mode {
+            Mode::PlacePiece { piece, .. } => {
+                piece.mutate_blocks(|block| {
+                    block.set_param(name, value);
+                });
+            }
+            Mode::Select { selection, .. } => {
+                if !selection.is_empty() {
+                    edit = Some(Edit::SetParam(selection.to_vec(), name, value));
+                } else if let Some(mouse_block_pos) = self.mouse_block_pos {
+                    edit = Some(Edit::SetParam(vec![mouse_block_pos], name, value));
+                }
+            }
+            Mode::DragAndDrop { piece, .. } => {
+                piece.mutate_blocks(|block| {
+                    block.set_param(name, value);
+                });
+            }
+            _ => {
+                // No op in other modes.
+            }
+        };
+
+        if let Some(edit) = edit {
+            self.run_and_track_edit(edit);
+        }
+    }
+
+    /// Mutates the blocks that are to be placed, or the selected blocks. In
+    /// the latter case, this is done by an undoable edit.
+    fn mutate_blocks(&mut self, f: impl Fn(&mut Block)) {
+        let mut edit = None;
+
+        match &mut self.mode {
+            Mode::PlacePiece { piece, .. } => {
+                piece.mutate_blocks(f);
+            }
+            Mode::Select { selection, .. } => {
+                let points = if !selection.is_empty() {
+                    selection.to_vec()
+                } else {
+                    self.mouse_block_pos.into_iter().collect()
+                };
+
+                let blocks = points
+                    .into_iter()
+                    .filter_map(|p| {
+                        let mut placed_block = self.machine.get(&p)?.clone();
+                        f(&mut placed_block.block);
+
+                        Some((p, Some(placed_block)))
+                    })
+                    .collect();
+
+                edit = Some(Edit::SetBlocks(blocks));
+            }
+            Mode::DragAndDrop { piece, .. } => {
+                piece.mutate_blocks(f);
+            }
+            _ => {
+                // No op in other modes.
+            }
+        };
+
+        if let Some(edit) = edit {
+            self.run_and_track_edit(edit);
+        }
+    }
+
+    /// Grows (for positive `delta`) or shrinks (for negative `delta`) the
+    /// machine on the side given by `dir`.
+    pub fn action_resize_side(&mut self, dir: grid::Dir3, delta: isize) {
+        let axis_vector = dir.0.to_vector();
+        let size = self.machine.size() + axis_vector * delta;
+
+        // Growing on the negative side means that all blocks need to move in
+        // the positive direction.
+        let shift = if dir.1 == grid::Sign::Neg {
+            axis_vector * delta
+        } else {
+            grid::Vector3::zeros()
+        };
+
+        self.request_resize(shift, size);
+    }
+
+    pub fn action_crop_to_blocks(&mut self) {
+        let piece = Piece::new_from_selection(
+            &self.machine,
+            self.machine.iter_blocks().map(|(_, (pos, _))| *pos),
+        );
+
+        if piece.blocks().is_empty() {
+            return;
+        }
+
+        let min = piece.min_pos();
+        let size = piece.max_pos() - min + grid::Vector3::new(1, 1, 1);
+
+        self.request_resize(-min.coords, size);
+    }
+
+    pub fn action_shift_blocks(&mut self, dir: grid::Dir3) {
+        self.request_resize(dir.to_vector(), self.machine.size());
+    }
+
+    pub fn action_confirm_resize(&mut self) {
+        if let Some((edit, _)) = self.pending_resize.take() {
+            self.run_and_track_edit(edit);
+        }
+    }
+
+    /// Runs a resize, unless the level forbids it. If blocks would be removed
+    /// by the resize, it is only run after confirmation.
+    fn request_resize(&mut self, shift: grid::Vector3, size: grid::Vector3) {
+        if !self.machine.is_resizable() || size.iter().any(|&n| n < 1) {
+            return;
+        }
+
+        let edit = Edit::Resize { shift, size };
+        let num_dropped = self.machine.num_blocks_outside(&shift, &size);
+
+        if num_dropped == 0 {
+            self.pending_resize = None;
+            self.run_and_track_edit(edit);
+        } else {
+            self.pending_resize = Some((edit, num_dropped));
+        }
+    }
 }
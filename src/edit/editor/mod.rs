@@ -17,11 +17,12 @@ use rendology::Camera;
 
 use crate::edit_camera_view::EditCameraView;
 use crate::input_state::InputState;
-use crate::machine::grid;
+use crate::machine::{grid, lint};
 use crate::machine::{Block, Machine, PlacedBlock, SavedMachine};
 
 use crate::edit::config::ModifiedKey;
-use crate::edit::{pick, Config, Edit, Mode, Piece, SelectionMode};
+use crate::edit::piece::Transform;
+use crate::edit::{pick, route, Config, Edit, Mode, Piece, SelectionMode};
 
 /// Maximal length of the undo queue.
 pub const MAX_UNDOS: usize = 1000;
@@ -57,10 +58,31 @@ pub struct Editor {
 
     /// Position of the *block* the mouse is currently pointing to, if any.
     mouse_block_pos: Option<grid::Point3>,
+
+    /// A resize that would remove blocks, together with the number of blocks
+    /// it would remove. It is only run once the user confirms it.
+    pending_resize: Option<(Edit, usize)>,
+
+    /// Likely mistakes in the machine. Updated whenever the machine is
+    /// edited.
+    warnings: Vec<lint::Warning>,
+
+    /// Blocks whose activations are traced while the machine is running.
+    pinned: Vec<grid::Point3>,
+
+    /// Message explaining why the last action could not be performed.
+    /// Cleared on the next action.
+    notice: Option<String>,
+
+    /// Holes of the source and the target block that the user picked for
+    /// routing a pipe. `None` lets the router choose any suitable hole.
+    route_dirs: (Option<grid::Dir3>, Option<grid::Dir3>),
 }
 
 impl Editor {
     pub fn new(config: &Config, machine: Machine) -> Editor {
+        let warnings = lint::lint(&machine);
+
         Editor {
             config: config.clone(),
             machine,
@@ -71,6 +93,11 @@ impl Editor {
             current_layer: 0,
             mouse_grid_pos: None,
             mouse_block_pos: None,
+            pending_resize: None,
+            warnings,
+            pinned: Vec::new(),
+            notice: None,
+            route_dirs: (None, None),
         }
     }
 
@@ -78,9 +105,34 @@ impl Editor {
         &self.machine
     }
 
+    pub fn pinned(&self) -> &[grid::Point3] {
+        &self.pinned
+    }
+
+    pub fn current_layer(&self) -> isize {
+        self.current_layer
+    }
+
+    /// Returns the position of the block that the mouse is pointing to. This
+    /// is only given while selecting, so that block info does not get in the
+    /// way of placing blocks.
+    pub fn mouse_block_pos(&self) -> Option<grid::Point3> {
+        match self.mode {
+            Mode::Select { .. } => self.mouse_block_pos,
+            _ => None,
+        }
+    }
+
     pub fn run_edit(&mut self, edit: Edit) -> Edit {
         let undo_edit = edit.run(&mut self.machine);
 
+        // A pending resize was checked against the blocks from before this
+        // edit, so it needs to be requested again.
+        self.pending_resize = None;
+
+        // The machine may have been resized.
+        self.current_layer = self.current_layer.min(self.machine.size().z - 1).max(0);
+
         // Now that the machine has been mutated, we need to make sure there is
         // no spurious state left in the editing mode.
         // TODO: use take_mut or mem::replace
@@ -89,6 +141,16 @@ impl Editor {
             .clone()
             .make_consistent_with_machine(&self.machine);
 
+        // Linting visits every block a few times, so its cost grows linearly
+        // with the machine. It runs on every edit, which includes every frame
+        // in which the mouse button is held down while placing blocks. This
+        // is fine for the machine sizes we have seen so far. Should it get
+        // slow, we could lint lazily, e.g. only when the warnings are shown.
+        self.warnings = lint::lint(&self.machine);
+
+        let machine = &self.machine;
+        self.pinned.retain(|pos| machine.is_block_at(pos));
+
         undo_edit
     }
 
@@ -314,6 +376,29 @@ impl Editor {
                     Mode::new_selection(selection)
                 }
             }
+            Mode::BoxSelect {
+                existing_selection, ..
+            } if input_state.is_button_pressed(MouseButton::Right) => {
+                // Give up on box selection on right mouse click.
+                Mode::new_selection(existing_selection)
+            }
+            Mode::BoxSelect {
+                existing_selection,
+                start_pos: Some(start_pos),
+                height,
+            } if !input_state.is_button_pressed(MouseButton::Left) => {
+                // Leave box selection once the user is done dragging.
+                let end_pos = self.mouse_grid_pos.unwrap_or(start_pos);
+
+                let mut selection = existing_selection;
+                for p in self.box_selection(&start_pos, &end_pos, height) {
+                    if !selection.contains(&p) {
+                        self.push_selection(p, &mut selection);
+                    }
+                }
+
+                Mode::new_selection(selection)
+            }
             Mode::PipeTool { last_pos: None, .. }
                 if input_state.is_button_pressed(MouseButton::Right) =>
             {
@@ -448,6 +533,31 @@ impl Editor {
             config: self.config.clone(),
             current_layer: self.current_layer,
             mode: self.mode.clone(),
+            inspected_blocks: self.inspected_blocks(),
+            machine_size: self.machine.size(),
+            is_resizable: self.machine.is_resizable(),
+            pending_resize_drops: self.pending_resize.as_ref().map(|(_, num)| *num),
+            warnings: self.warnings.clone(),
+            notice: self.notice.clone(),
+            route_holes: self.route_holes(),
+        }
+    }
+
+    /// Returns the blocks whose parameters can be edited in the inspector,
+    /// i.e. the selected blocks or the blocks that are to be placed.
+    fn inspected_blocks(&self) -> Vec<Block> {
+        match &self.mode {
+            Mode::Select { selection, .. } => selection
+                .iter()
+                .filter_map(|p| self.machine.get(p))
+                .map(|placed_block| placed_block.block.clone())
+                .collect(),
+            Mode::PlacePiece { piece, .. } | Mode::DragAndDrop { piece, .. } => piece
+                .blocks()
+                .iter()
+                .map(|(_, placed_block)| placed_block.block.clone())
+                .collect(),
+            _ => Vec::new(),
         }
     }
 
@@ -472,6 +582,8 @@ impl Editor {
     }
 
     fn on_key_press(&mut self, key: ModifiedKey) {
+        self.notice = None;
+
         // Action shortcuts
         if key == self.config.undo_key {
             self.action_undo();
@@ -491,6 +603,10 @@ impl Editor {
             self.action_select_mode();
         } else if key == self.config.select_layer_bound_key {
             self.action_select_layer_bound_mode();
+        } else if key == self.config.pin_key {
+            self.action_toggle_pin();
+        } else if key == self.config.box_select_key {
+            self.action_box_select_mode();
         } else if key == self.config.pipe_tool_key {
             self.action_pipe_tool_mode();
         } else if key == self.config.cancel_key {
@@ -503,12 +619,34 @@ impl Editor {
             self.action_delete();
         } else if key == self.config.block_kind_key {
             self.action_next_kind();
+        } else if key == self.config.block_channel_key {
+            self.action_next_channel();
+        } else if key == self.config.timer_period_down_key {
+            self.action_change_timer(-1, 0);
+        } else if key == self.config.timer_period_up_key {
+            self.action_change_timer(1, 0);
+        } else if key == self.config.timer_phase_down_key {
+            self.action_change_timer(0, -1);
+        } else if key == self.config.timer_phase_up_key {
+            self.action_change_timer(0, 1);
         } else if key == self.config.rotate_block_cw_key {
             self.action_rotate_cw();
         } else if key == self.config.rotate_block_ccw_key {
             self.action_rotate_ccw();
         } else if key == self.config.mirror_y_key {
             self.action_mirror_y();
+        } else if key == self.config.rotate_x_cw_key {
+            self.action_transform(Transform::RotateCWYZ);
+        } else if key == self.config.rotate_x_ccw_key {
+            self.action_transform(Transform::RotateCCWYZ);
+        } else if key == self.config.rotate_y_cw_key {
+            self.action_transform(Transform::RotateCWXZ);
+        } else if key == self.config.rotate_y_ccw_key {
+            self.action_transform(Transform::RotateCCWXZ);
+        } else if key == self.config.mirror_x_key {
+            self.action_transform(Transform::MirrorX);
+        } else if key == self.config.mirror_z_key {
+            self.action_transform(Transform::MirrorZ);
         }
 
         // Switch to specific layer
@@ -549,6 +687,17 @@ impl Editor {
             {
                 self.on_left_mouse_click_select(input_state, modifiers, selection)
             }
+            Mode::BoxSelect {
+                existing_selection,
+                start_pos: None,
+                height,
+            } if button == glutin::MouseButton::Left && state == glutin::ElementState::Pressed => {
+                Mode::BoxSelect {
+                    existing_selection,
+                    start_pos: self.mouse_grid_pos,
+                    height,
+                }
+            }
             Mode::PipeTool { .. }
                 if button == glutin::MouseButton::Left
                     && state == glutin::ElementState::Pressed =>
@@ -798,6 +947,11 @@ impl Editor {
             } => {
                 overwrite(existing_selection);
             }
+            Mode::BoxSelect {
+                existing_selection, ..
+            } => {
+                overwrite(existing_selection);
+            }
             Mode::DragAndDrop { .. } => {
                 // Drag and drop just simply does not allow overwriting the
                 // selection, since it carries the meaning of which blocks are
@@ -814,9 +968,115 @@ impl Editor {
         return mode;
     }
 
+    /// Returns the positions of blocks in the box spanned by two positions in
+    /// the current layer, extending `height` layers upwards.
+    fn box_selection(
+        &self,
+        a: &grid::Point3,
+        b: &grid::Point3,
+        height: isize,
+    ) -> Vec<grid::Point3> {
+        let min = grid::Point3::new(a.x.min(b.x), a.y.min(b.y), self.current_layer);
+        let max = grid::Point3::new(a.x.max(b.x), a.y.max(b.y), self.current_layer + height - 1);
+
+        self.machine
+            .iter_blocks()
+            .map(|(_, (pos, _))| *pos)
+            .filter(|pos| {
+                pos.x >= min.x
+                    && pos.x <= max.x
+                    && pos.y >= min.y
+                    && pos.y <= max.y
+                    && pos.z >= min.z
+                    && pos.z <= max.z
+            })
+            .collect()
+    }
+
+    /// Returns the selected blocks, or the block under the mouse if there is
+    /// no selection.
+    fn selection_or_mouse_block(&self) -> Vec<grid::Point3> {
+        match self.mode.selection() {
+            Some(selection) if !selection.is_empty() => selection.to_vec(),
+            _ => self.mouse_block_pos.into_iter().collect(),
+        }
+    }
+
+    /// Returns the first and the last selected block, which are connected by
+    /// `action_auto_route`, if they are distinct.
+    fn route_ends(&self) -> Option<(grid::Point3, grid::Point3)> {
+        let selection = self.mode.selection()?.to_vec();
+
+        match (selection.first(), selection.last()) {
+            (Some(source), Some(target)) if source != target => Some((*source, *target)),
+            _ => None,
+        }
+    }
+
+    /// Returns the holes through which a pipe can be routed between the
+    /// blocks given by `route_ends`, together with the holes the user picked.
+    /// Picked holes that the blocks do not have are ignored.
+    fn route_holes(&self) -> Option<ui::RouteHoles> {
+        let (source, target) = self.route_ends()?;
+        let source_holes = route::source_holes(&self.machine.get(&source)?.block);
+        let target_holes = route::target_holes(&self.machine.get(&target)?.block);
+
+        Some(ui::RouteHoles {
+            source_dir: self.route_dirs.0.filter(|dir| source_holes.contains(dir)),
+            target_dir: self.route_dirs.1.filter(|dir| target_holes.contains(dir)),
+            source_holes,
+            target_holes,
+        })
+    }
+
     fn push_selection(&self, p: grid::Point3, selection: &mut SelectionMode) {
         if self.machine.is_valid_pos(&p) {
             selection.push_if_correct_layer(self.current_layer, p);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::machine::grid::{Dir3, Point3};
+
+    /// Test that a resize that waits for confirmation is dropped once the
+    /// machine is edited, since it may remove a different number of blocks now.
+    #[test]
+    fn test_pending_resize_cleared_by_edit() {
+        let blocks: Vec<_> = (0..4)
+            .map(|x| {
+                (
+                    Point3::new(x, 0, 0),
+                    PlacedBlock {
+                        block: Block::Solid,
+                    },
+                )
+            })
+            .collect();
+        let machine = Machine::new_from_block_data(&grid::Vector3::new(4, 1, 1), &blocks, &None);
+        let mut editor = Editor::new(&Config::default(), machine);
+
+        editor.action_resize_side(Dir3::X_POS, -2);
+        assert_eq!(editor.ui_input().pending_resize_drops, Some(2));
+
+        let mut set_blocks = HashMap::new();
+        set_blocks.insert(Point3::new(1, 0, 0), None);
+        editor.run_and_track_edit(Edit::SetBlocks(set_blocks));
+        assert_eq!(editor.ui_input().pending_resize_drops, None);
+
+        editor.action_confirm_resize();
+        assert_eq!(editor.machine().size(), grid::Vector3::new(4, 1, 1));
+        assert_eq!(editor.machine().num_blocks(), 3);
+
+        // Requesting it again asks for confirmation with the new count.
+        editor.action_resize_side(Dir3::X_POS, -3);
+        assert_eq!(editor.ui_input().pending_resize_drops, Some(2));
+
+        editor.action_confirm_resize();
+        assert_eq!(editor.machine().size(), grid::Vector3::new(1, 1, 1));
+        assert_eq!(editor.machine().num_blocks(), 1);
+    }
+}
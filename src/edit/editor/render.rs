@@ -4,14 +4,16 @@ use nalgebra as na;
 use rendology::{basic_obj, BasicObj};
 
 use crate::edit::{Editor, Mode, Piece};
+use crate::exec::anim::WindDeadend;
 use crate::exec::TickTime;
-use crate::machine::{grid, Block, PlacedBlock};
+use crate::machine::{grid, network, Block, PlacedBlock};
+use crate::render::layers::Layers;
 use crate::render::{self, Stage};
 
 pub const GRID_OFFSET_Z: f32 = 0.00;
 
 impl Editor {
-    pub fn render(&mut self, out: &mut Stage) {
+    pub fn render(&mut self, layers: &Layers, out: &mut Stage) {
         profile!("editor");
 
         let grid_size: na::Vector3<f32> = na::convert(self.machine.size());
@@ -25,15 +27,17 @@ impl Editor {
             &mut out.solid,
         );
 
-        let filter = |pos| {
+        let filter = |pos: &grid::Point3| {
             // Don't render blocks that are going to be overwritten by the pipe
             // tool. Otherwise it may look a bit confusing if the same grid
             // position contains two different pipes.
-            if let Mode::PipeTool { blocks, .. } = &self.mode {
-                !blocks.contains_key(pos)
+            let overwritten = if let Mode::PipeTool { blocks, .. } = &self.mode {
+                blocks.contains_key(pos)
             } else {
-                true
-            }
+                false
+            };
+
+            !overwritten && !layers.is_hidden(pos.z, self.current_layer)
         };
 
         let unfocus = |pos: &grid::Point3| {
@@ -43,7 +47,9 @@ impl Editor {
                 false
             };
 
-            tentative_die || !self.mode.impacts_layer(self.current_layer, pos.z)
+            tentative_die
+                || !self.mode.impacts_layer(self.current_layer, pos.z)
+                || layers.is_ghost(pos.z, self.current_layer)
         };
 
         render::machine::render_machine(
@@ -61,11 +67,19 @@ impl Editor {
             &mut out.lines,
         );*/
 
+        self.render_warnings(out);
+
+        for pos in self.pinned.iter() {
+            self.render_block_wireframe(pos, 0.85, 6.0, &na::Vector4::new(0.7, 0.3, 0.9, 1.0), out);
+        }
+
         match &self.mode {
             Mode::Select { selection, .. } => {
                 self.render_selection(selection.iter(), out);
 
                 if let Some(mouse_block_pos) = self.mouse_block_pos {
+                    self.render_network(&mouse_block_pos, out);
+
                     self.render_block_wireframe(
                         &mouse_block_pos,
                         1.0,
@@ -111,6 +125,53 @@ impl Editor {
                     ..Default::default()
                 });
             }
+            Mode::BoxSelect {
+                existing_selection,
+                start_pos,
+                height,
+            } => {
+                self.render_selection(existing_selection.iter(), out);
+
+                match (start_pos, self.mouse_grid_pos) {
+                    (Some(start_pos), Some(mouse_grid_pos)) => {
+                        let new_selection = self.box_selection(start_pos, &mouse_grid_pos, *height);
+                        self.render_selection(new_selection.iter(), out);
+
+                        let min = na::Point3::new(
+                            start_pos.x.min(mouse_grid_pos.x),
+                            start_pos.y.min(mouse_grid_pos.y),
+                            self.current_layer,
+                        );
+                        let size = na::Vector3::new(
+                            (start_pos.x - mouse_grid_pos.x).abs() + 1,
+                            (start_pos.y - mouse_grid_pos.y).abs() + 1,
+                            *height,
+                        );
+                        let min: na::Point3<f32> = na::convert(min);
+                        let size: na::Vector3<f32> = na::convert(size);
+
+                        render::machine::render_cuboid_wireframe(
+                            &render::machine::Cuboid {
+                                center: min + size / 2.0 + na::Vector3::z() * GRID_OFFSET_Z,
+                                size,
+                            },
+                            0.05,
+                            &na::Vector4::new(0.3, 0.3, 0.9, 1.0),
+                            &mut out.solid,
+                        );
+                    }
+                    (None, Some(mouse_grid_pos)) => {
+                        self.render_block_wireframe(
+                            &mouse_grid_pos,
+                            1.0,
+                            9.0,
+                            &na::Vector4::new(0.3, 0.3, 0.9, 1.0),
+                            out,
+                        );
+                    }
+                    _ => (),
+                }
+            }
             Mode::PlacePiece { piece, .. } => {
                 if let Some(mouse_grid_pos) = self.mouse_grid_pos {
                     self.render_piece_to_place(piece, &mouse_grid_pos, out);
@@ -220,6 +281,54 @@ impl Editor {
         }
     }
 
+    fn render_warnings(&self, out: &mut Stage) {
+        let color = na::Vector4::new(0.9, 0.1, 0.1, 1.0);
+
+        for warning in self.warnings.iter() {
+            for pos in warning.positions() {
+                self.render_block_wireframe(&pos, 1.0, 13.0, &color, out);
+            }
+        }
+    }
+
+    /// Highlights the pipe network of the block at `pos`, together with the
+    /// wind sources that feed it and the places where its wind ends.
+    fn render_network(&self, pos: &grid::Point3, out: &mut Stage) {
+        let network = match network::network_at(&self.machine, pos) {
+            Some(network) => network,
+            None => return,
+        };
+
+        let outline = |pos: &grid::Point3, color: &na::Vector4<f32>, out: &mut Stage| {
+            self.render_block_wireframe(pos, 1.0, 13.0, color, out);
+        };
+
+        for pos in network.blocks.iter() {
+            outline(pos, &na::Vector4::new(0.2, 0.7, 0.9, 1.0), out);
+        }
+
+        for pos in network.sources.iter() {
+            outline(pos, &na::Vector4::new(0.9, 0.8, 0.1, 1.0), out);
+        }
+
+        for (pos, dir, deadend) in network.deadends.iter() {
+            // Wind that is blocked by another block is more likely to be a
+            // mistake than wind that just blows into empty space.
+            let color = match deadend {
+                WindDeadend::Block => na::Vector4::new(0.9, 0.1, 0.1, 1.0),
+                WindDeadend::Space => na::Vector4::new(0.9, 0.5, 0.1, 1.0),
+            };
+
+            let pos: na::Point3<f32> = na::convert(*pos);
+            let dir: na::Vector3<f32> = na::convert(dir.to_vector());
+            let center = pos + na::Vector3::new(0.5, 0.5, 0.5 + GRID_OFFSET_Z) + dir * 0.5;
+            let transform =
+                na::Matrix4::new_translation(&center.coords) * na::Matrix4::new_scaling(0.3);
+
+            render::machine::render_line_wireframe(10.0, &color, &transform, out);
+        }
+    }
+
     fn render_selection<'a>(
         &self,
         selection: impl Iterator<Item = &'a grid::Point3>,
@@ -1,8 +1,14 @@
 use imgui::{im_str, ImString};
 
+use crate::edit::config::ModifiedKey;
 use crate::edit::editor::action::Action;
+use crate::edit::piece::Transform;
 use crate::edit::Config;
 use crate::edit::Mode;
+use crate::machine::grid::{self, Axis3, Dir3, Point3};
+use crate::machine::lint::Warning;
+use crate::machine::param::{self, ParamValue};
+use crate::machine::{BlipKind, Block, PlacedBlock};
 
 const BUTTON_H: f32 = 25.0;
 const BUTTON_W: f32 = 66.25;
@@ -13,6 +19,22 @@ pub struct Input {
     pub config: Config,
     pub current_layer: isize,
     pub mode: Mode,
+    pub inspected_blocks: Vec<Block>,
+    pub machine_size: grid::Vector3,
+    pub is_resizable: bool,
+    pub pending_resize_drops: Option<usize>,
+    pub warnings: Vec<Warning>,
+    pub notice: Option<String>,
+    pub route_holes: Option<RouteHoles>,
+}
+
+/// Holes that can be picked for routing a pipe between two selected blocks.
+#[derive(Clone, Debug)]
+pub struct RouteHoles {
+    pub source_holes: Vec<Dir3>,
+    pub target_holes: Vec<Dir3>,
+    pub source_dir: Option<Dir3>,
+    pub target_dir: Option<Dir3>,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -50,7 +72,66 @@ pub fn run(input: &Input, ui: &imgui::Ui, output: &mut Output) {
                 .build(|| {
                     ui_actions(&input, ui, output);
                 });
+            if input.is_resizable {
+                imgui::TreeNode::new(ui, im_str!("Grid"))
+                    .opened(false, imgui::Condition::FirstUseEver)
+                    .build(|| {
+                        ui_grid(&input, ui, output);
+                    });
+            }
+            if let Some(notice) = input.notice.as_ref() {
+                ui.separator();
+                ui.text_wrapped(&ImString::new(notice.as_str()));
+            }
+            if !input.warnings.is_empty() {
+                let label = ImString::new(format!("Warnings ({})", input.warnings.len()));
+                imgui::TreeNode::new(ui, &label)
+                    .opened(false, imgui::Condition::FirstUseEver)
+                    .build(|| {
+                        ui_warnings(&input.warnings, ui);
+                    });
+            }
         });
+
+    if let Some(num_dropped) = input.pending_resize_drops {
+        imgui::Window::new(im_str!("Resize"))
+            .always_auto_resize(true)
+            .position([250.0, 200.0], imgui::Condition::FirstUseEver)
+            .bg_alpha(BG_ALPHA)
+            .collapsible(false)
+            .build(&ui, || {
+                ui.text(&ImString::new(format!(
+                    "This will remove {} blocks that do not fit into the grid.",
+                    num_dropped
+                )));
+
+                if ui.button(im_str!("Remove"), [BUTTON_W, BUTTON_H]) {
+                    output.actions.push(Action::ConfirmResize);
+                }
+                ui.same_line(0.0);
+                if ui.button(im_str!("Cancel"), [BUTTON_W, BUTTON_H]) {
+                    output.actions.push(Action::Cancel);
+                }
+            });
+    }
+
+    if !input.inspected_blocks.is_empty() {
+        imgui::Window::new(im_str!("Inspector"))
+            .horizontal_scrollbar(true)
+            .always_auto_resize(true)
+            .position([250.0, 0.0], imgui::Condition::FirstUseEver)
+            .bg_alpha(BG_ALPHA)
+            .collapsible(true)
+            .build(&ui, || {
+                ui_inspector(&input.inspected_blocks, ui, output);
+            });
+    }
+}
+
+fn ui_warnings(warnings: &[Warning], ui: &imgui::Ui) {
+    for warning in warnings {
+        ui.bullet_text(&ImString::new(warning.to_string()));
+    }
 }
 
 fn ui_layers(input: &Input, ui: &imgui::Ui, output: &mut Output) {
@@ -129,6 +210,40 @@ fn ui_modes(input: &Input, ui: &imgui::Ui, output: &mut Output) {
     }
     ui.next_column();
 
+    ui.text_disabled(&ImString::new(format!("{}", input.config.box_select_key)));
+    ui.next_column();
+
+    let box_height = match &input.mode {
+        Mode::BoxSelect { height, .. } => Some(*height),
+        _ => None,
+    };
+    let selectable = imgui::Selectable::new(im_str!("Select box")).selected(box_height.is_some());
+    if selectable.build(ui) {
+        output.actions.push(Action::BoxSelectMode);
+    }
+    if ui.is_item_hovered() {
+        let text = format!(
+            "Switch to selecting blocks in a box that can span multiple layers, starting at the current one.\n\nShortcut: {}",
+            input.config.box_select_key
+        );
+        ui.tooltip(|| ui.text(&ImString::new(text)));
+    }
+    ui.next_column();
+
+    if let Some(box_height) = box_height {
+        ui.next_column();
+        ui.text(&ImString::new(format!("Height: {}", box_height)));
+        ui.same_line(0.0);
+        if ui.button(im_str!("-##box_height"), [20.0, 0.0]) {
+            output.actions.push(Action::ChangeBoxHeight(-1));
+        }
+        ui.same_line(0.0);
+        if ui.button(im_str!("+##box_height"), [20.0, 0.0]) {
+            output.actions.push(Action::ChangeBoxHeight(1));
+        }
+        ui.next_column();
+    }
+
     ui.text_disabled(&ImString::new(format!("{}", input.config.pipe_tool_key)));
     ui.next_column();
 
@@ -149,6 +264,106 @@ fn ui_modes(input: &Input, ui: &imgui::Ui, output: &mut Output) {
     }
 
     ui.columns(1, im_str!("ui_modes_end"), false);
+
+    if ui.button(im_str!("Connected"), [BUTTON_W, BUTTON_H]) {
+        output.actions.push(Action::SelectConnected);
+    }
+    if ui.is_item_hovered() {
+        ui.tooltip(|| {
+            ui.text(im_str!(
+                "Select all blocks that are connected to the selected blocks."
+            ))
+        });
+    }
+
+    ui.same_line(0.0);
+
+    if ui.button(im_str!("Same type"), [BUTTON_W, BUTTON_H]) {
+        output.actions.push(Action::SelectSameType);
+    }
+    if ui.is_item_hovered() {
+        ui.tooltip(|| {
+            ui.text(im_str!(
+                "Select all blocks of the same type as the last selected block."
+            ))
+        });
+    }
+
+    if ui.button(im_str!("Pin"), [BUTTON_W, BUTTON_H]) {
+        output.actions.push(Action::TogglePin);
+    }
+    if ui.is_item_hovered() {
+        let text = format!(
+            "Pin or unpin the selected blocks, showing their activations in a timing diagram while playing.\n\nShortcut: {}",
+            input.config.pin_key
+        );
+        ui.tooltip(|| ui.text(&ImString::new(text)));
+    }
+
+    ui.same_line(0.0);
+
+    if ui.button(im_str!("Route pipe"), [BUTTON_W, BUTTON_H]) {
+        output.actions.push(Action::AutoRoute);
+    }
+    if ui.is_item_hovered() {
+        ui.tooltip(|| {
+            ui.text(im_str!(
+                "Connect the first and the last selected block with the shortest possible pipe, using the picked holes."
+            ))
+        });
+    }
+
+    if let Some(route_holes) = input.route_holes.as_ref() {
+        ui_route_holes(route_holes, ui, output);
+    }
+}
+
+fn ui_route_holes(route_holes: &RouteHoles, ui: &imgui::Ui, output: &mut Output) {
+    let source_dir = ui_route_hole(
+        "From",
+        &route_holes.source_holes,
+        route_holes.source_dir,
+        ui,
+    );
+    let target_dir = ui_route_hole("To", &route_holes.target_holes, route_holes.target_dir, ui);
+
+    if source_dir.is_some() || target_dir.is_some() {
+        output.actions.push(Action::SetRouteDirs(
+            source_dir.unwrap_or(route_holes.source_dir),
+            target_dir.unwrap_or(route_holes.target_dir),
+        ));
+    }
+}
+
+/// Shows a choice between the given holes, returning the new choice if it
+/// was changed.
+fn ui_route_hole(
+    name: &str,
+    holes: &[Dir3],
+    dir: Option<Dir3>,
+    ui: &imgui::Ui,
+) -> Option<Option<Dir3>> {
+    let mut current = dir;
+    let mut changed = false;
+
+    ui.text(&ImString::new(format!("{}:", name)));
+    ui.same_line(0.0);
+
+    let label = ImString::new(format!("any##route{}", name));
+    changed |= ui.radio_button(&label, &mut current, None);
+
+    for &hole in holes {
+        ui.same_line(0.0);
+
+        let label = ImString::new(format!("{}##route{}", param::dir_name(hole), name));
+        changed |= ui.radio_button(&label, &mut current, Some(hole));
+    }
+
+    if changed {
+        Some(current)
+    } else {
+        None
+    }
 }
 
 fn ui_blocks(input: &Input, ui: &imgui::Ui, output: &mut Output) {
@@ -160,28 +375,47 @@ fn ui_blocks(input: &Input, ui: &imgui::Ui, output: &mut Output) {
         _ => None,
     };
 
-    for (block_key, block) in input.config.block_keys.clone().iter() {
-        ui.text_disabled(&ImString::new(format!("{}", block_key)));
-        ui.next_column();
+    for (block_key, block) in input.config.block_keys.iter() {
+        ui_block_choice(block, Some(block_key), &cur_block, ui, output);
+    }
 
-        let name = &ImString::new(block.name());
-        let selected = cur_block
-            .as_ref()
-            .map_or(false, |(_, placed_block)| placed_block.block == *block);
-        let selectable = imgui::Selectable::new(name).selected(selected);
-        if selectable.build(ui) {
-            output.actions.push(Action::PlaceBlockMode(block.clone()));
-        }
-        if ui.is_item_hovered() {
-            let text = format!("{}\n\nShortcut: {}", block.description(), block_key);
-            ui.tooltip(|| ui.text(&ImString::new(text)));
-        }
-        ui.next_column();
+    for block in input.config.mod_blocks.iter() {
+        ui_block_choice(block, None, &cur_block, ui, output);
     }
 
     ui.columns(1, im_str!("ui_blocks_end"), false);
 }
 
+fn ui_block_choice(
+    block: &Block,
+    block_key: Option<&ModifiedKey>,
+    cur_block: &Option<(Point3, PlacedBlock)>,
+    ui: &imgui::Ui,
+    output: &mut Output,
+) {
+    if let Some(block_key) = block_key {
+        ui.text_disabled(&ImString::new(format!("{}", block_key)));
+    }
+    ui.next_column();
+
+    let name = &ImString::new(block.name());
+    let selected = cur_block
+        .as_ref()
+        .map_or(false, |(_, placed_block)| placed_block.block == *block);
+    let selectable = imgui::Selectable::new(name).selected(selected);
+    if selectable.build(ui) {
+        output.actions.push(Action::PlaceBlockMode(block.clone()));
+    }
+    if ui.is_item_hovered() {
+        let text = match block_key {
+            Some(block_key) => format!("{}\n\nShortcut: {}", block.description(), block_key),
+            None => block.description(),
+        };
+        ui.tooltip(|| ui.text(&ImString::new(text)));
+    }
+    ui.next_column();
+}
+
 fn ui_actions(input: &Input, ui: &imgui::Ui, output: &mut Output) {
     if ui.button(im_str!("Undo"), [BUTTON_W, BUTTON_H]) {
         output.actions.push(Action::Undo);
@@ -299,4 +533,332 @@ fn ui_actions(input: &Input, ui: &imgui::Ui, output: &mut Output) {
         );
         ui.tooltip(|| ui.text(&ImString::new(text)));
     }
+
+    if ui.button(im_str!("Channel"), [BUTTON_W, BUTTON_H]) {
+        output.actions.push(Action::NextChannel);
+    }
+    if ui.is_item_hovered() {
+        let text = format!(
+            "Changes channel of selected blocks where applicable (i.e. portals, transmitters and receivers).\n\nShortcut: {}",
+            input.config.block_channel_key,
+        );
+        ui.tooltip(|| ui.text(&ImString::new(text)));
+    }
+
+    for (i, (label, transform, key, help)) in [
+        (
+            im_str!("↻ X"),
+            Transform::RotateCWYZ,
+            input.config.rotate_x_cw_key,
+            "Rotate blocks clockwise around the X axis.",
+        ),
+        (
+            im_str!("↺ X"),
+            Transform::RotateCCWYZ,
+            input.config.rotate_x_ccw_key,
+            "Rotate blocks counterclockwise around the X axis.",
+        ),
+        (
+            im_str!("↻ Y"),
+            Transform::RotateCWXZ,
+            input.config.rotate_y_cw_key,
+            "Rotate blocks clockwise around the Y axis.",
+        ),
+        (
+            im_str!("↺ Y"),
+            Transform::RotateCCWXZ,
+            input.config.rotate_y_ccw_key,
+            "Rotate blocks counterclockwise around the Y axis.",
+        ),
+        (
+            im_str!("Mirror X"),
+            Transform::MirrorX,
+            input.config.mirror_x_key,
+            "Mirror blocks at X axis.",
+        ),
+        (
+            im_str!("Mirror Z"),
+            Transform::MirrorZ,
+            input.config.mirror_z_key,
+            "Turn blocks upside down.",
+        ),
+    ]
+    .iter()
+    .enumerate()
+    {
+        if i % 2 == 1 {
+            ui.same_line(0.0);
+        }
+
+        if ui.button(label, [BUTTON_W, BUTTON_H]) {
+            output.actions.push(Action::Transform(transform.clone()));
+        }
+        if ui.is_item_hovered() {
+            let text = format!("{}\n\nShortcut: {}", help, key);
+            ui.tooltip(|| ui.text(&ImString::new(text)));
+        }
+    }
+
+    for &(label, period_delta, phase_delta, key, help) in &[
+        (
+            im_str!("Period -"),
+            -1,
+            0,
+            input.config.timer_period_down_key,
+            "Decreases the period of selected timers.",
+        ),
+        (
+            im_str!("Period +"),
+            1,
+            0,
+            input.config.timer_period_up_key,
+            "Increases the period of selected timers.",
+        ),
+        (
+            im_str!("Phase -"),
+            0,
+            -1,
+            input.config.timer_phase_down_key,
+            "Decreases the phase of selected timers.",
+        ),
+        (
+            im_str!("Phase +"),
+            0,
+            1,
+            input.config.timer_phase_up_key,
+            "Increases the phase of selected timers.",
+        ),
+    ] {
+        if period_delta > 0 || phase_delta > 0 {
+            ui.same_line(0.0);
+        }
+
+        if ui.button(label, [BUTTON_W, BUTTON_H]) {
+            output.actions.push(Action::ChangeTimer {
+                period_delta,
+                phase_delta,
+            });
+        }
+        if ui.is_item_hovered() {
+            let text = format!("{}\n\nShortcut: {}", help, key);
+            ui.tooltip(|| ui.text(&ImString::new(text)));
+        }
+    }
+}
+
+fn ui_grid(input: &Input, ui: &imgui::Ui, output: &mut Output) {
+    let size = input.machine_size;
+    ui.text(&ImString::new(format!(
+        "Size: {} x {} x {}",
+        size.x, size.y, size.z
+    )));
+
+    ui.columns(3, im_str!("ui_grid"), false);
+    ui.set_column_width(0, 50.0);
+
+    for &dir in &Dir3::ALL {
+        ui.text(&ImString::new(param::dir_name(dir)));
+        ui.next_column();
+
+        let label = ImString::new(format!("Grow##{:?}", dir));
+        if ui.button(&label, [BUTTON_W, 0.0]) {
+            output.actions.push(Action::ResizeSide(dir, 1));
+        }
+        ui.next_column();
+
+        let label = ImString::new(format!("Shrink##{:?}", dir));
+        if ui.button(&label, [BUTTON_W, 0.0]) {
+            output.actions.push(Action::ResizeSide(dir, -1));
+        }
+        ui.next_column();
+    }
+
+    ui.columns(1, im_str!("ui_grid_end"), false);
+
+    if ui.button(im_str!("Crop"), [BUTTON_W, BUTTON_H]) {
+        output.actions.push(Action::CropToBlocks);
+    }
+    if ui.is_item_hovered() {
+        ui.tooltip(|| {
+            ui.text(im_str!(
+                "Shrinks the grid to the blocks that have been placed."
+            ))
+        });
+    }
+
+    ui.text(im_str!("Move all blocks:"));
+    for (i, &dir) in Dir3::ALL.iter().enumerate() {
+        if i % 2 == 1 {
+            ui.same_line(0.0);
+        }
+
+        let label = ImString::new(format!("{}##shift", param::dir_name(dir)));
+        if ui.button(&label, [BUTTON_W, 0.0]) {
+            output.actions.push(Action::ShiftBlocks(dir));
+        }
+    }
+}
+
+fn ui_inspector(blocks: &[Block], ui: &imgui::Ui, output: &mut Output) {
+    if let [block] = blocks {
+        ui.text(&ImString::new(block.name()));
+    } else {
+        ui.text(&ImString::new(format!("{} blocks", blocks.len())));
+    }
+
+    // Collect the values of each parameter over all blocks, keeping the order
+    // in which the parameters are defined.
+    let mut params: Vec<(&'static str, Vec<ParamValue>)> = Vec::new();
+
+    for block in blocks {
+        for (name, value) in block.params() {
+            if let Some((_, values)) = params.iter_mut().find(|(n, _)| *n == name) {
+                values.push(value);
+            } else {
+                params.push((name, vec![value]));
+            }
+        }
+    }
+
+    if params.is_empty() {
+        ui.text_disabled(im_str!("No parameters"));
+        return;
+    }
+
+    ui.separator();
+
+    for (name, values) in params {
+        // If the selected blocks disagree on the value, we show no value.
+        let value = if values.iter().all(|value| *value == values[0]) {
+            Some(values[0])
+        } else {
+            None
+        };
+
+        ui.text(&ImString::new(name));
+        if value.is_none() {
+            ui.same_line(0.0);
+            ui.text_disabled(im_str!("(mixed)"));
+        }
+
+        if let Some(new_value) = ui_param(name, values[0], value, ui) {
+            output.actions.push(Action::SetParam(name, new_value));
+        }
+    }
+}
+
+fn ui_param(
+    name: &str,
+    first_value: ParamValue,
+    value: Option<ParamValue>,
+    ui: &imgui::Ui,
+) -> Option<ParamValue> {
+    match first_value {
+        ParamValue::Bool(first) => {
+            let mut enabled = first;
+            let label = ImString::new(format!("enabled##{}", name));
+
+            if ui.checkbox(&label, &mut enabled) {
+                Some(ParamValue::Bool(enabled))
+            } else {
+                None
+            }
+        }
+        ParamValue::Dir(_) => {
+            let choices: Vec<_> = Dir3::ALL
+                .iter()
+                .map(|dir| (param::dir_name(*dir).to_string(), ParamValue::Dir(*dir)))
+                .collect();
+            ui_param_choice(name, value, &choices, ui)
+        }
+        ParamValue::Axis(_) => {
+            let choices: Vec<_> = Axis3::ALL
+                .iter()
+                .map(|axis| (param::axis_name(*axis).to_string(), ParamValue::Axis(*axis)))
+                .collect();
+            ui_param_choice(name, value, &choices, ui)
+        }
+        ParamValue::Kind(_) => {
+            let choices: Vec<_> = [BlipKind::A, BlipKind::B]
+                .iter()
+                .map(|kind| (kind.to_string(), ParamValue::Kind(*kind)))
+                .collect();
+            ui_param_choice(name, value, &choices, ui)
+        }
+        ParamValue::OptionalKind(_) => {
+            let choices: Vec<_> = [None, Some(BlipKind::A), Some(BlipKind::B)]
+                .iter()
+                .map(|kind| {
+                    (
+                        kind.map_or("any".to_string(), |kind| kind.to_string()),
+                        ParamValue::OptionalKind(*kind),
+                    )
+                })
+                .collect();
+            ui_param_choice(name, value, &choices, ui)
+        }
+        ParamValue::Number(first) => ui_param_number(name, first, ui).map(ParamValue::Number),
+        ParamValue::OptionalNumber(first) => {
+            let mut unlimited = first.is_none();
+            let label = ImString::new(format!("unlimited##{}", name));
+
+            if ui.checkbox(&label, &mut unlimited) {
+                Some(ParamValue::OptionalNumber(if unlimited {
+                    None
+                } else {
+                    Some(1)
+                }))
+            } else if let Some(first) = first {
+                ui_param_number(name, first, ui)
+                    .map(|number| ParamValue::OptionalNumber(Some(number)))
+            } else {
+                None
+            }
+        }
+    }
+}
+
+fn ui_param_choice(
+    name: &str,
+    value: Option<ParamValue>,
+    choices: &[(String, ParamValue)],
+    ui: &imgui::Ui,
+) -> Option<ParamValue> {
+    let mut current = value;
+    let mut changed = false;
+
+    for (i, (choice_name, choice)) in choices.iter().enumerate() {
+        if i > 0 {
+            ui.same_line(0.0);
+        }
+
+        let label = ImString::new(format!("{}##{}", choice_name, name));
+        changed |= ui.radio_button(&label, &mut current, Some(*choice));
+    }
+
+    if changed {
+        current
+    } else {
+        None
+    }
+}
+
+fn ui_param_number(name: &str, first: usize, ui: &imgui::Ui) -> Option<usize> {
+    let mut new_number = None;
+
+    let label = ImString::new(format!("-##{}", name));
+    if ui.button(&label, [20.0, 0.0]) && first > 0 {
+        new_number = Some(first - 1);
+    }
+
+    ui.same_line(0.0);
+    ui.text(&ImString::new(first.to_string()));
+    ui.same_line(0.0);
+
+    let label = ImString::new(format!("+##{}", name));
+    if ui.button(&label, [20.0, 0.0]) {
+        new_number = Some(first + 1);
+    }
+
+    new_number
 }
@@ -3,10 +3,12 @@ pub mod editor;
 pub mod mode;
 pub mod pick;
 pub mod piece;
+pub mod route;
 
 use std::collections::HashMap;
 
 use crate::machine::grid;
+use crate::machine::param::ParamValue;
 use crate::machine::{Block, Machine, PlacedBlock};
 
 pub use config::Config;
@@ -30,6 +32,22 @@ pub enum Edit {
     /// Switch to the next kind.
     NextKind(Vec<grid::Point3>),
 
+    /// Switch to the next channel.
+    NextChannel(Vec<grid::Point3>),
+
+    /// Switch to the previous channel.
+    PrevChannel(Vec<grid::Point3>),
+
+    /// Set the parameter of the given name, where applicable.
+    SetParam(Vec<grid::Point3>, &'static str, ParamValue),
+
+    /// Move all blocks by the given vector and change the size of the
+    /// machine. Blocks that end up outside of the machine are removed.
+    Resize {
+        shift: grid::Vector3,
+        size: grid::Vector3,
+    },
+
     /// Run two edits in sequence.
     Pair(Box<Edit>, Box<Edit>),
 }
@@ -196,6 +214,80 @@ impl Edit {
                     Edit::NextKind(points)
                 }
             }
+            Edit::NextChannel(points) => {
+                for p in &points {
+                    if let Some(placed_block) = machine.get_mut(p) {
+                        placed_block.block.set_next_channel();
+                    }
+                }
+
+                if points.is_empty() {
+                    Edit::NoOp
+                } else {
+                    Edit::PrevChannel(points)
+                }
+            }
+            Edit::PrevChannel(points) => {
+                for p in &points {
+                    if let Some(placed_block) = machine.get_mut(p) {
+                        placed_block.block.set_prev_channel();
+                    }
+                }
+
+                if points.is_empty() {
+                    Edit::NoOp
+                } else {
+                    Edit::NextChannel(points)
+                }
+            }
+            Edit::SetParam(points, name, value) => {
+                let previous_blocks: HashMap<_, _> = points
+                    .iter()
+                    .filter_map(|p| {
+                        let placed_block = machine.get_mut(p)?;
+                        let previous_block = placed_block.clone();
+
+                        if placed_block.block.set_param(name, value) {
+                            Some((*p, Some(previous_block)))
+                        } else {
+                            None
+                        }
+                    })
+                    .collect();
+
+                if previous_blocks.is_empty() {
+                    Edit::NoOp
+                } else {
+                    Edit::SetBlocks(previous_blocks)
+                }
+            }
+            Edit::Resize { shift, size } => {
+                let previous_size = machine.size();
+
+                if shift == grid::Vector3::zeros() && size == previous_size {
+                    Edit::NoOp
+                } else {
+                    let dropped_blocks = machine.resize(&shift, &size);
+                    let undo_resize = Edit::Resize {
+                        shift: -shift,
+                        size: previous_size,
+                    };
+
+                    if dropped_blocks.is_empty() {
+                        undo_resize
+                    } else {
+                        let restore_blocks = dropped_blocks
+                            .into_iter()
+                            .map(|(p, placed_block)| (p, Some(placed_block)))
+                            .collect();
+
+                        Edit::Pair(
+                            Box::new(undo_resize),
+                            Box::new(Edit::SetBlocks(restore_blocks)),
+                        )
+                    }
+                }
+            }
             Edit::Pair(a, b) => {
                 let undo_a = a.run(machine);
                 let undo_b = b.run(machine);
@@ -242,3 +334,113 @@ pub fn count_outputs<'a>(blocks: impl Iterator<Item = &'a Option<PlacedBlock>>)
         })
         .sum()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::machine::grid::{Dir3, Point3};
+
+    /// Test that undoing `Edit::SetParam` restores the changed blocks.
+    #[test]
+    fn test_set_param_undo() {
+        let a = Point3::new(0, 0, 0);
+        let b = Point3::new(1, 0, 0);
+        let pipe = PlacedBlock {
+            block: Block::Pipe(Dir3::X_NEG, Dir3::X_POS),
+        };
+        let solid = PlacedBlock {
+            block: Block::Solid,
+        };
+
+        let mut machine = Machine::new_sandbox(grid::Vector3::new(2, 1, 1));
+        machine.set(&a, Some(pipe.clone()));
+        machine.set(&b, Some(solid.clone()));
+
+        let undo =
+            Edit::SetParam(vec![a, b], "dir a", ParamValue::Dir(Dir3::Y_POS)).run(&mut machine);
+        assert_eq!(
+            machine.get(&a).unwrap().block,
+            Block::Pipe(Dir3::Y_POS, Dir3::X_POS)
+        );
+
+        let redo = undo.run(&mut machine);
+        assert_eq!(machine.get(&a), Some(&pipe));
+        assert_eq!(machine.get(&b), Some(&solid));
+
+        redo.run(&mut machine);
+        assert_eq!(
+            machine.get(&a).unwrap().block,
+            Block::Pipe(Dir3::Y_POS, Dir3::X_POS)
+        );
+
+        // Nothing to undo if no block changed.
+        let undo = Edit::SetParam(vec![b], "dir a", ParamValue::Dir(Dir3::Y_POS)).run(&mut machine);
+        assert!(match undo {
+            Edit::NoOp => true,
+            _ => false,
+        });
+    }
+
+    /// Test that undoing a resize restores the size, the positions of the
+    /// blocks and the blocks that were removed by it.
+    #[test]
+    fn test_resize_undo() {
+        let blocks: Vec<_> = (0..4)
+            .map(|x| {
+                (
+                    Point3::new(x, 0, 0),
+                    PlacedBlock {
+                        block: Block::Input {
+                            out_dir: Dir3::X_POS,
+                            index: x as usize,
+                        },
+                    },
+                )
+            })
+            .collect();
+        let size = grid::Vector3::new(4, 1, 1);
+        let mut machine = Machine::new_from_block_data(&size, &blocks, &None);
+
+        let assert_blocks =
+            |machine: &Machine, shift: grid::Vector3, range: std::ops::Range<usize>| {
+                assert_eq!(machine.num_blocks(), range.len());
+                for (pos, placed_block) in &blocks[range] {
+                    assert_eq!(machine.get(&(pos + shift)), Some(placed_block), "{:?}", pos);
+                }
+            };
+
+        // Growing and shifting keeps all blocks.
+        let undo = Edit::Resize {
+            shift: grid::Vector3::new(1, 2, 0),
+            size: grid::Vector3::new(6, 3, 2),
+        }
+        .run(&mut machine);
+        assert_eq!(machine.size(), grid::Vector3::new(6, 3, 2));
+        assert_blocks(&machine, grid::Vector3::new(1, 2, 0), 0..4);
+
+        undo.run(&mut machine);
+        assert_eq!(machine.size(), size);
+        assert_blocks(&machine, grid::Vector3::zeros(), 0..4);
+
+        // Cropping to the right half removes the blocks on the left.
+        let undo = Edit::Resize {
+            shift: grid::Vector3::new(-2, 0, 0),
+            size: grid::Vector3::new(2, 1, 1),
+        }
+        .run(&mut machine);
+        assert_eq!(machine.size(), grid::Vector3::new(2, 1, 1));
+        assert_blocks(&machine, grid::Vector3::new(-2, 0, 0), 2..4);
+        assert!(match undo {
+            Edit::Pair(..) => true,
+            _ => false,
+        });
+
+        let redo = undo.run(&mut machine);
+        assert_eq!(machine.size(), size);
+        assert_blocks(&machine, grid::Vector3::zeros(), 0..4);
+
+        redo.run(&mut machine);
+        assert_blocks(&machine, grid::Vector3::new(-2, 0, 0), 2..4);
+    }
+}
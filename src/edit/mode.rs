@@ -52,6 +52,21 @@ pub enum Mode {
         end_pos: na::Point2<f32>,
     },
 
+    /// Select blocks in a box of grid positions, possibly spanning multiple
+    /// layers.
+    BoxSelect {
+        /// Blocks that were already selected when entering this mode.
+        existing_selection: SelectionMode,
+
+        /// Grid position where the user started dragging, if any. The box
+        /// spans from there to the current mouse grid position.
+        start_pos: Option<grid::Point3>,
+
+        /// Number of layers covered by the box, starting at the current
+        /// layer.
+        height: isize,
+    },
+
     PlacePiece {
         piece: Piece,
         is_paste: bool,
@@ -103,6 +118,9 @@ impl Mode {
             Mode::RectSelect {
                 existing_selection, ..
             } => Some(existing_selection),
+            Mode::BoxSelect {
+                existing_selection, ..
+            } => Some(existing_selection),
             _ => None,
         }
     }
@@ -161,6 +179,19 @@ impl Mode {
 
                 Mode::DragAndDrop { selection, piece }
             }
+            Mode::BoxSelect {
+                existing_selection,
+                start_pos,
+                height,
+            } => {
+                let existing_selection = existing_selection.make_consistent_with_machine(machine);
+
+                Mode::BoxSelect {
+                    existing_selection,
+                    start_pos,
+                    height,
+                }
+            }
             Mode::PlacePiece {
                 piece,
                 is_paste,
@@ -191,6 +222,9 @@ impl Mode {
             Mode::RectSelect {
                 existing_selection, ..
             } => existing_selection.impacts_layer(current_layer, target_layer),
+            Mode::BoxSelect { height, .. } => {
+                target_layer >= current_layer && target_layer < current_layer + height
+            }
             Mode::PlacePiece { piece, .. } => {
                 target_layer >= current_layer + piece.min_pos().z
                     && target_layer <= current_layer + piece.max_pos().z
@@ -1,18 +1,52 @@
 use std::ops::Mul;
 
 use crate::edit::Edit;
-use crate::machine::grid;
-use crate::machine::{Machine, PlacedBlock};
+use crate::machine::grid::{self, Axis3};
+use crate::machine::{Block, Machine, PlacedBlock};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Transform {
     Shift(grid::Vector3),
+
+    /// Rotate around the Z axis.
     RotateCWXY,
     RotateCCWXY,
+
+    /// Rotate around the Y axis.
+    RotateCWXZ,
+    RotateCCWXZ,
+
+    /// Rotate around the X axis.
+    RotateCWYZ,
+    RotateCCWYZ,
+
+    /// Mirror at the X axis, i.e. flip Y coordinates.
+    MirrorX,
+
+    /// Mirror at the Y axis, i.e. flip X coordinates.
     MirrorY,
+
+    /// Flip Z coordinates, i.e. turn upside down.
+    MirrorZ,
+
     Seq(Vec<Transform>),
 }
 
+impl Transform {
+    /// Returns true if every step of the transform maps the XY plane onto
+    /// itself.
+    pub fn keeps_xy_plane(&self) -> bool {
+        match self {
+            Transform::RotateCWXZ
+            | Transform::RotateCCWXZ
+            | Transform::RotateCWYZ
+            | Transform::RotateCCWYZ => false,
+            Transform::Seq(inner) => inner.iter().all(Transform::keeps_xy_plane),
+            _ => true,
+        }
+    }
+}
+
 impl<'a> Mul<grid::Point3> for &'a Transform {
     type Output = grid::Point3;
 
@@ -21,7 +55,13 @@ impl<'a> Mul<grid::Point3> for &'a Transform {
             Transform::Shift(delta) => p + delta,
             Transform::RotateCWXY => grid::Point3::new(p.y, -p.x, p.z),
             Transform::RotateCCWXY => grid::Point3::new(-p.y, p.x, p.z),
+            Transform::RotateCWXZ => grid::Point3::new(p.z, p.y, -p.x),
+            Transform::RotateCCWXZ => grid::Point3::new(-p.z, p.y, p.x),
+            Transform::RotateCWYZ => grid::Point3::new(p.x, p.z, -p.y),
+            Transform::RotateCCWYZ => grid::Point3::new(p.x, -p.z, p.y),
+            Transform::MirrorX => grid::Point3::new(p.x, -p.y, p.z),
             Transform::MirrorY => grid::Point3::new(-p.x, p.y, p.z),
+            Transform::MirrorZ => grid::Point3::new(p.x, p.y, -p.z),
             Transform::Seq(inner) => inner.iter().fold(p, |p, transform| transform * p),
         }
     }
@@ -43,7 +83,13 @@ impl<'a> Mul<grid::Dir3> for &'a Transform {
             Transform::Shift(_) => d,
             Transform::RotateCWXY => d.rotated_cw_xy(),
             Transform::RotateCCWXY => d.rotated_ccw_xy(),
+            Transform::RotateCWXZ => d.rotated_cw(Axis3::X, Axis3::Z),
+            Transform::RotateCCWXZ => d.rotated_ccw(Axis3::X, Axis3::Z),
+            Transform::RotateCWYZ => d.rotated_cw(Axis3::Y, Axis3::Z),
+            Transform::RotateCCWYZ => d.rotated_ccw(Axis3::Y, Axis3::Z),
+            Transform::MirrorX => d.mirrored(Axis3::Y),
             Transform::MirrorY => d.mirrored_y(),
+            Transform::MirrorZ => d.mirrored(Axis3::Z),
             Transform::Seq(inner) => inner.iter().fold(d, |d, transform| transform * d),
         }
     }
@@ -86,6 +132,16 @@ impl Piece {
         &self.blocks
     }
 
+    /// Returns false if the transform would rotate blocks that only exist in
+    /// the XY plane out of it.
+    pub fn can_transform(&self, transform: &Transform) -> bool {
+        transform.keeps_xy_plane()
+            || self
+                .blocks
+                .iter()
+                .all(|(_, placed_block)| !placed_block.block.is_xy_only())
+    }
+
     pub fn transform(&mut self, transform: &Transform) {
         for (pos, placed_block) in self.blocks.iter_mut() {
             *pos = transform * *pos;
@@ -117,6 +173,18 @@ impl Piece {
         }
     }
 
+    pub fn mutate_blocks(&mut self, f: impl Fn(&mut Block)) {
+        for (_, placed_block) in self.blocks.iter_mut() {
+            f(&mut placed_block.block);
+        }
+    }
+
+    pub fn set_next_channel(&mut self) {
+        for (_, placed_block) in self.blocks.iter_mut() {
+            placed_block.block.set_next_channel();
+        }
+    }
+
     pub fn as_place_edit(&self) -> Edit {
         let set_blocks = self.iter().map(|(pos, block)| (pos, Some(block))).collect();
 
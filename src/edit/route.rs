@@ -0,0 +1,280 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::machine::grid::{Dir3, DirMap3, Point3};
+use crate::machine::{Block, Machine, PlacedBlock};
+
+/// Can wind leave `block` through a pipe that is attached in direction `dir`?
+fn can_route_out(block: &Block, dir: Dir3) -> bool {
+    match block {
+        Block::GeneralPipe(_) => true,
+        _ => block.can_connect_by_pipe(dir) && block.has_wind_hole_out(dir, false),
+    }
+}
+
+/// Can wind enter `block` through a pipe that is attached in direction `dir`?
+fn can_route_in(block: &Block, dir: Dir3) -> bool {
+    match block {
+        Block::GeneralPipe(_) => true,
+        _ => block.can_connect_by_pipe(dir) && block.has_wind_hole_in(dir, false),
+    }
+}
+
+/// Returns the holes of `block` that a route can leave through.
+pub fn source_holes(block: &Block) -> Vec<Dir3> {
+    Dir3::ALL
+        .iter()
+        .copied()
+        .filter(|dir| can_route_out(block, *dir))
+        .collect()
+}
+
+/// Returns the holes of `block` that a route can enter through.
+pub fn target_holes(block: &Block) -> Vec<Dir3> {
+    Dir3::ALL
+        .iter()
+        .copied()
+        .filter(|dir| can_route_in(block, *dir))
+        .collect()
+}
+
+/// Finds a shortest path of free grid positions that connects a hole of the
+/// block at `source` to a hole of the block at `target`.
+///
+/// If `source_dir` or `target_dir` is given, the path has to use that hole of
+/// the respective block. Otherwise, any suitable hole can be used.
+///
+/// The path may change layers, but it never passes through existing blocks.
+/// Blocks with a restricted flow, e.g. funnels, are only left through holes
+/// that let wind out and only entered through holes that let wind in.
+///
+/// The returned path starts at `source` and ends at `target`.
+pub fn find_route(
+    machine: &Machine,
+    source: &Point3,
+    source_dir: Option<Dir3>,
+    target: &Point3,
+    target_dir: Option<Dir3>,
+) -> Option<Vec<Point3>> {
+    let source_block = &machine.get(source)?.block;
+    let target_block = &machine.get(target)?.block;
+
+    if source == target {
+        return None;
+    }
+
+    let mut prev: HashMap<Point3, Point3> = HashMap::new();
+    let mut queue = VecDeque::new();
+    queue.push_back(*source);
+
+    while let Some(pos) = queue.pop_front() {
+        for &dir in &Dir3::ALL {
+            if pos == *source
+                && (!can_route_out(source_block, dir)
+                    || source_dir.map_or(false, |source_dir| source_dir != dir))
+            {
+                continue;
+            }
+
+            let next_pos = pos + dir.to_vector();
+
+            if next_pos == *target {
+                if !can_route_in(target_block, dir.invert())
+                    || target_dir.map_or(false, |target_dir| target_dir != dir.invert())
+                {
+                    continue;
+                }
+
+                let mut path = vec![*target, pos];
+                let mut cur = pos;
+                while let Some(p) = prev.get(&cur) {
+                    path.push(*p);
+                    cur = *p;
+                }
+                path.reverse();
+
+                return Some(path);
+            }
+
+            if next_pos == *source
+                || prev.contains_key(&next_pos)
+                || !machine.is_valid_pos(&next_pos)
+                || machine.is_block_at(&next_pos)
+            {
+                continue;
+            }
+
+            prev.insert(next_pos, pos);
+            queue.push_back(next_pos);
+        }
+    }
+
+    None
+}
+
+/// Returns the pipes that need to be placed to realize a path given by
+/// `find_route`.
+///
+/// The end points are only included if they are pipes themselves, so that
+/// they can be combined with the new connection.
+pub fn route_pipes(machine: &Machine, path: &[Point3]) -> HashMap<Point3, Option<PlacedBlock>> {
+    let dir_between = |a: &Point3, b: &Point3| {
+        Dir3::ALL
+            .iter()
+            .copied()
+            .find(|dir| dir.to_vector() == b - a)
+    };
+
+    let mut blocks = HashMap::new();
+
+    for (i, pos) in path.iter().enumerate() {
+        let prev_dir = i.checked_sub(1).and_then(|j| dir_between(pos, &path[j]));
+        let next_dir = path.get(i + 1).and_then(|next| dir_between(pos, next));
+
+        let is_end = i == 0 || i + 1 == path.len();
+        let is_pipe = machine
+            .get(pos)
+            .map_or(false, |placed_block| match placed_block.block {
+                Block::GeneralPipe(_) => true,
+                _ => false,
+            });
+
+        if !is_end || is_pipe {
+            let dirs = DirMap3::from_fn(|dir| Some(dir) == prev_dir || Some(dir) == next_dir);
+            blocks.insert(
+                *pos,
+                Some(PlacedBlock {
+                    block: Block::GeneralPipe(dirs),
+                }),
+            );
+        }
+    }
+
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::machine::string_util::machine_from_string;
+
+    /// Test that routing between two blocks in a row places straight pipes
+    /// between them, but not on the blocks themselves.
+    #[test]
+    fn test_route_straight() {
+        let m = "
+◉...[
+";
+        let machine = machine_from_string(m);
+
+        let source = Point3::new(0, 0, 0);
+        let target = Point3::new(4, 0, 0);
+        let path = find_route(&machine, &source, None, &target, None).unwrap();
+
+        assert_eq!(
+            path,
+            (0..=4).map(|x| Point3::new(x, 0, 0)).collect::<Vec<_>>()
+        );
+
+        let blocks = route_pipes(&machine, &path);
+        let straight = DirMap3::from_fn(|dir| dir == Dir3::X_NEG || dir == Dir3::X_POS);
+
+        assert_eq!(blocks.len(), 3);
+        for x in 1..=3 {
+            assert_eq!(
+                blocks[&Point3::new(x, 0, 0)],
+                Some(PlacedBlock {
+                    block: Block::GeneralPipe(straight.clone()),
+                })
+            );
+        }
+    }
+
+    /// Test that routes go around obstacles, changing layers if there is no
+    /// other way, and that no route is found if there is no free path at all.
+    #[test]
+    fn test_route_obstacles() {
+        let m = "
+◉.☐.[
+";
+        let source = Point3::new(0, 0, 0);
+        let target = Point3::new(4, 0, 0);
+
+        let mut machine = machine_from_string(m);
+        machine.grow_to_include(&Point3::new(4, 1, 0));
+
+        let path = find_route(&machine, &source, None, &target, None).unwrap();
+        assert_eq!(path.len(), 7);
+        assert!(path.iter().all(|p| p.z == 0 && *p != Point3::new(2, 0, 0)));
+
+        let mut machine = machine_from_string(m);
+        machine.grow_to_include(&Point3::new(4, 0, 1));
+
+        let path = find_route(&machine, &source, None, &target, None).unwrap();
+        assert_eq!(path.len(), 7);
+        assert!(path.contains(&Point3::new(2, 0, 1)));
+
+        let blocks = route_pipes(&machine, &path);
+        let layer_change = DirMap3::from_fn(|dir| dir == Dir3::Z_POS || dir == Dir3::X_POS);
+        assert_eq!(
+            blocks[&Point3::new(3, 0, 0)],
+            Some(PlacedBlock {
+                block: Block::GeneralPipe(layer_change),
+            })
+        );
+
+        let machine = machine_from_string(m);
+        assert!(find_route(&machine, &source, None, &target, None).is_none());
+    }
+
+    /// Test that funnels are only left and entered through the holes that match
+    /// their flow direction.
+    #[test]
+    fn test_route_funnels() {
+        let m = "
+▷...▷
+";
+        let machine = machine_from_string(m);
+
+        let a = Point3::new(0, 0, 0);
+        let b = Point3::new(4, 0, 0);
+
+        assert_eq!(
+            source_holes(&machine.get(&a).unwrap().block),
+            vec![Dir3::X_NEG]
+        );
+        assert_eq!(
+            target_holes(&machine.get(&a).unwrap().block),
+            vec![Dir3::X_POS]
+        );
+
+        assert!(find_route(&machine, &a, None, &b, None).is_none());
+        assert_eq!(
+            find_route(&machine, &b, None, &a, None).map(|path| path.len()),
+            Some(5)
+        );
+    }
+
+    /// Test that routes use the holes that were picked for the source and the
+    /// target block.
+    #[test]
+    fn test_route_picked_holes() {
+        let m = "
+◉...[
+";
+        let mut machine = machine_from_string(m);
+        machine.grow_to_include(&Point3::new(4, 1, 0));
+
+        let source = Point3::new(0, 0, 0);
+        let target = Point3::new(4, 0, 0);
+
+        let path = find_route(&machine, &source, Some(Dir3::Y_POS), &target, None).unwrap();
+        assert_eq!(path.len(), 7);
+        assert_eq!(path[1], Point3::new(0, 1, 0));
+
+        let path = find_route(&machine, &source, None, &target, Some(Dir3::X_NEG)).unwrap();
+        assert_eq!(path.len(), 5);
+
+        assert!(find_route(&machine, &source, None, &target, Some(Dir3::Y_POS)).is_none());
+    }
+}
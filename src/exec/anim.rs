@@ -60,11 +60,10 @@ impl AnimState {
     /// Returns the AnimState of one block based on the previous and the
     /// current simulation WindState.
     pub fn from_exec_block(exec: &Exec, block_index: BlockIndex) -> Self {
+        let wind_out = exec.blocks().wind_out.get(block_index);
+        let next_wind_out = exec.next_blocks().wind_out.get(block_index);
         let wind_out = DirMap3::from_fn(|dir| {
-            WindLife::from_states(
-                exec.blocks().wind_out[block_index][dir],
-                exec.next_blocks().wind_out[block_index][dir],
-            )
+            WindLife::from_states(wind_out.contains(dir), next_wind_out.contains(dir))
         });
 
         let prev_activation = exec.prev_activation()[block_index];
@@ -0,0 +1,218 @@
+//! Evaluation of a machine on many level examples at once, e.g. for checking
+//! that it does not only work by chance.
+//!
+//! Each example is generated from its own seed, so that failures can be
+//! reproduced.
+
+use std::mem;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::exec::{Exec, FailureReason, LevelProgress, LevelStatus};
+use crate::machine::Machine;
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Number of examples to evaluate.
+    pub num_seeds: usize,
+
+    /// Seed of the first example. The following examples use the seeds after
+    /// this one.
+    pub first_seed: u64,
+
+    pub num_threads: usize,
+
+    /// Maximal number of ticks to run each example for.
+    pub max_ticks: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            num_seeds: 100,
+            first_seed: 0,
+            num_threads: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            max_ticks: 100_000,
+        }
+    }
+}
+
+/// The outcome of running the machine on one example.
+#[derive(Debug, Clone)]
+pub struct SeedResult {
+    pub seed: u64,
+    pub status: LevelStatus,
+
+    /// Number of ticks after which the level was completed or failed, or
+    /// after which we gave up.
+    pub num_ticks: usize,
+
+    pub failure: Option<FailureReason>,
+}
+
+/// Results of a batch, ordered by seed.
+#[derive(Debug, Clone, Default)]
+pub struct Summary {
+    pub results: Vec<SeedResult>,
+}
+
+impl Summary {
+    pub fn count(&self, status: LevelStatus) -> usize {
+        self.results
+            .iter()
+            .filter(|result| result.status == status)
+            .count()
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.count(LevelStatus::Completed) == self.results.len()
+    }
+
+    /// Examples that were not completed, either because they failed or
+    /// because they were still running after the maximal number of ticks.
+    pub fn failures(&self) -> impl Iterator<Item = &SeedResult> {
+        self.results
+            .iter()
+            .filter(|result| result.status != LevelStatus::Completed)
+    }
+
+    /// Mean and maximal number of ticks that completed examples took.
+    pub fn completed_ticks(&self) -> Option<(f32, usize)> {
+        let ticks: Vec<usize> = self
+            .results
+            .iter()
+            .filter(|result| result.status == LevelStatus::Completed)
+            .map(|result| result.num_ticks)
+            .collect();
+
+        let max = *ticks.iter().max()?;
+        let mean = ticks.iter().sum::<usize>() as f32 / ticks.len() as f32;
+
+        Some((mean, max))
+    }
+}
+
+/// Evaluates a machine on many examples using a pool of worker threads.
+/// Evaluation is cancelled when this is dropped.
+pub struct Batch {
+    num_seeds: usize,
+    results: Vec<SeedResult>,
+    result_recv: mpsc::Receiver<SeedResult>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Batch {
+    pub fn spawn(machine: &Machine, config: &Config) -> Self {
+        let next_seed_index = Arc::new(AtomicUsize::new(0));
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (result_send, result_recv) = mpsc::channel();
+
+        for _ in 0..config.num_threads.max(1) {
+            let machine = machine.clone();
+            let config = config.clone();
+            let next_seed_index = next_seed_index.clone();
+            let cancel = cancel.clone();
+            let result_send = result_send.clone();
+
+            thread::spawn(move || loop {
+                let seed_index = next_seed_index.fetch_add(1, Ordering::Relaxed);
+                if seed_index >= config.num_seeds || cancel.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                let seed = config.first_seed + seed_index as u64;
+                let result = run_seed(machine.clone(), seed, config.max_ticks, &cancel);
+
+                // The receiver is gone if the batch has been dropped.
+                if result_send.send(result).is_err() {
+                    break;
+                }
+            });
+        }
+
+        Self {
+            num_seeds: config.num_seeds,
+            results: Vec::new(),
+            result_recv,
+            cancel,
+        }
+    }
+
+    /// Returns the fraction of examples that have been evaluated.
+    pub fn progress(&self) -> f32 {
+        if self.num_seeds > 0 {
+            self.results.len() as f32 / self.num_seeds as f32
+        } else {
+            1.0
+        }
+    }
+
+    /// Collects finished examples, returning the summary once all of them
+    /// are done.
+    pub fn poll(&mut self) -> Option<Summary> {
+        while let Ok(result) = self.result_recv.try_recv() {
+            self.results.push(result);
+        }
+
+        if self.results.len() == self.num_seeds {
+            Some(self.take_summary())
+        } else {
+            None
+        }
+    }
+
+    /// Blocks until all examples have been evaluated.
+    pub fn wait(mut self) -> Summary {
+        while self.results.len() < self.num_seeds {
+            match self.result_recv.recv() {
+                Ok(result) => self.results.push(result),
+                Err(_) => break,
+            }
+        }
+
+        self.take_summary()
+    }
+
+    fn take_summary(&mut self) -> Summary {
+        let mut results = mem::take(&mut self.results);
+        results.sort_by_key(|result| result.seed);
+
+        Summary { results }
+    }
+}
+
+impl Drop for Batch {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+fn run_seed(machine: Machine, seed: u64, max_ticks: usize, cancel: &AtomicBool) -> SeedResult {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut exec = Exec::new(machine, &mut rng);
+
+    while exec.cur_tick() < max_ticks && !cancel.load(Ordering::Relaxed) {
+        exec.update();
+
+        let status = exec
+            .next_level_progress()
+            .map_or(LevelStatus::Running, LevelProgress::status);
+        if status != LevelStatus::Running {
+            break;
+        }
+    }
+
+    let progress = exec.next_level_progress();
+
+    SeedResult {
+        seed,
+        status: progress.map_or(LevelStatus::Running, LevelProgress::status),
+        num_ticks: exec.cur_tick(),
+        failure: progress.and_then(|progress| progress.failure.clone()),
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::exec::wind::WindState;
+use crate::exec::{Activation, Blip, Exec};
+use crate::machine::{Block, BlockIndex, TickNum};
+
+/// The execution state repeats periodically, starting at some tick.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct Cycle {
+    /// Tick from which on the state repeats. This can be later than the
+    /// first repetition, since states are only compared with the last
+    /// checkpoint, and a repetition is only accepted once the full state has
+    /// been seen again.
+    pub start: TickNum,
+
+    /// Number of ticks after which the state repeats.
+    pub period: usize,
+}
+
+/// The part of the execution state that determines what happens next.
+#[derive(PartialEq, Eq, Clone, Hash)]
+struct State {
+    tick_phase: usize,
+
+    /// Blips, sorted by their hash, since the order in which they are stored
+    /// does not matter.
+    blips: Vec<Blip>,
+
+    wind_out: WindState,
+    activation: Vec<Activation>,
+    next_wind_out: WindState,
+    next_activation: Vec<Activation>,
+    prev_activation: Vec<Activation>,
+
+    /// Blocks that differ from their initial state.
+    changed_blocks: Vec<(BlockIndex, Block)>,
+
+    /// Number of blips fed by each input of the level so far. The inputs that
+    /// remain to be fed determine what happens next, while the outputs
+    /// received so far do not.
+    inputs_fed: Vec<usize>,
+}
+
+/// A state whose hash has been seen before, which has yet to repeat exactly.
+#[derive(Clone)]
+struct Candidate {
+    state: State,
+    tick: TickNum,
+    period: usize,
+}
+
+/// Detects when the state of an execution repeats itself, which means that
+/// the machine will keep doing the same thing forever.
+///
+/// We follow Brent's algorithm: only the hash of the state at a checkpoint is
+/// kept, and the checkpoint moves to the current tick whenever the distance to
+/// it reaches the next power of two. This keeps memory constant, but the cycle
+/// may be found a few periods after it starts. Once the checkpoint's hash is
+/// seen again, the current state is kept, and the cycle is accepted only if
+/// that exact state is seen again after the same number of ticks.
+#[derive(Clone)]
+pub struct CycleDetector {
+    /// Blocks at the start of execution. Blocks that have changed since then
+    /// are part of the state.
+    initial_blocks: Vec<Block>,
+
+    /// Blocks may depend on the current tick modulo this number.
+    tick_modulus: usize,
+
+    /// Hash of the state at the last checkpoint, and the tick of it.
+    checkpoint: Option<(u64, TickNum)>,
+
+    /// Number of ticks after the last checkpoint at which the next one is
+    /// set.
+    checkpoint_distance: usize,
+
+    candidate: Option<Candidate>,
+
+    cycle: Option<Cycle>,
+}
+
+impl CycleDetector {
+    pub fn new(exec: &Exec) -> Self {
+        let initial_blocks = exec
+            .machine()
+            .iter_blocks()
+            .map(|(_, (_, placed_block))| placed_block.block.clone())
+            .collect();
+
+        // Timers are the only blocks that look at the current tick.
+        let tick_modulus = exec
+            .machine()
+            .iter_blocks()
+            .filter_map(|(_, (_, placed_block))| placed_block.block.timer_period())
+            .fold(1, lcm);
+
+        Self {
+            initial_blocks,
+            tick_modulus,
+            checkpoint: None,
+            checkpoint_distance: 1,
+            candidate: None,
+            cycle: None,
+        }
+    }
+
+    pub fn cycle(&self) -> Option<Cycle> {
+        self.cycle
+    }
+
+    /// Records the state in the current tick of `exec`, returning the cycle
+    /// once it has been found.
+    ///
+    /// This needs to be called in every tick.
+    pub fn record(&mut self, exec: &Exec) -> Option<Cycle> {
+        if self.cycle.is_some() {
+            return self.cycle;
+        }
+
+        let state_hash = self.state_hash(exec);
+        let tick = exec.cur_tick();
+
+        if let Some(candidate) = self.candidate.take() {
+            if tick < candidate.tick + candidate.period {
+                self.candidate = Some(candidate);
+            } else if candidate.state == self.state(exec) {
+                self.cycle = Some(Cycle {
+                    start: candidate.tick,
+                    period: candidate.period,
+                });
+
+                return self.cycle;
+            }
+
+            // Otherwise, the hashes matched by chance.
+        }
+
+        match self.checkpoint {
+            Some((checkpoint_hash, checkpoint_tick)) => {
+                if checkpoint_hash == state_hash && self.candidate.is_none() {
+                    self.candidate = Some(Candidate {
+                        state: self.state(exec),
+                        tick,
+                        period: tick - checkpoint_tick,
+                    });
+                }
+
+                if tick >= checkpoint_tick + self.checkpoint_distance {
+                    self.checkpoint = Some((state_hash, tick));
+                    self.checkpoint_distance *= 2;
+                }
+            }
+            None => {
+                self.checkpoint = Some((state_hash, tick));
+            }
+        }
+
+        None
+    }
+
+    /// Hashes the same data that makes up `State`, without copying it.
+    fn state_hash(&self, exec: &Exec) -> u64 {
+        let mut hasher = DefaultHasher::new();
+
+        (exec.cur_tick() % self.tick_modulus).hash(&mut hasher);
+
+        // The order in which blips are stored does not matter.
+        let mut blip_hashes: Vec<u64> = exec.blips().iter().map(|(_, blip)| hash(blip)).collect();
+        blip_hashes.sort();
+        blip_hashes.hash(&mut hasher);
+
+        exec.blocks().wind_out.hash(&mut hasher);
+        exec.blocks().activation.hash(&mut hasher);
+        exec.next_blocks().wind_out.hash(&mut hasher);
+        exec.next_blocks().activation.hash(&mut hasher);
+        exec.prev_activation().hash(&mut hasher);
+
+        for (block_index, (_, placed_block)) in exec.machine().iter_blocks() {
+            if placed_block.block != self.initial_blocks[block_index] {
+                (block_index, &placed_block.block).hash(&mut hasher);
+            }
+        }
+
+        if let Some(progress) = exec.level_progress() {
+            for input in progress.inputs.iter() {
+                input.num_fed.hash(&mut hasher);
+            }
+        }
+
+        hasher.finish()
+    }
+
+    fn state(&self, exec: &Exec) -> State {
+        let mut blips: Vec<Blip> = exec.blips().iter().map(|(_, blip)| *blip).collect();
+        blips.sort_by_key(hash);
+
+        // Some blocks change their state when activated. Since this is rare,
+        // we only look at the blocks that differ from their initial state.
+        let changed_blocks = exec
+            .machine()
+            .iter_blocks()
+            .filter(|(block_index, (_, placed_block))| {
+                placed_block.block != self.initial_blocks[*block_index]
+            })
+            .map(|(block_index, (_, placed_block))| (block_index, placed_block.block.clone()))
+            .collect();
+
+        let inputs_fed = exec.level_progress().map_or(Vec::new(), |progress| {
+            progress.inputs.iter().map(|input| input.num_fed).collect()
+        });
+
+        State {
+            tick_phase: exec.cur_tick() % self.tick_modulus,
+            blips,
+            wind_out: exec.blocks().wind_out.clone(),
+            activation: exec.blocks().activation.clone(),
+            next_wind_out: exec.next_blocks().wind_out.clone(),
+            next_activation: exec.next_blocks().activation.clone(),
+            prev_activation: exec.prev_activation().to_vec(),
+            changed_blocks,
+            inputs_fed,
+        }
+    }
+}
+
+fn hash<T: Hash>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+fn lcm(a: usize, b: usize) -> usize {
+    a / gcd(a, b) * b
+}
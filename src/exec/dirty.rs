@@ -0,0 +1,43 @@
+/// A set of indices, e.g. of blocks or of words of packed wind, that can be
+/// iterated and cleared in time proportional to its size, no matter how many
+/// indices there are in total.
+#[derive(Clone, Debug)]
+pub struct DirtySet {
+    indices: Vec<usize>,
+    contains: Vec<bool>,
+}
+
+impl DirtySet {
+    pub fn new(num_indices: usize) -> Self {
+        Self {
+            indices: Vec::new(),
+            contains: vec![false; num_indices],
+        }
+    }
+
+    pub fn new_full(num_indices: usize) -> Self {
+        Self {
+            indices: (0..num_indices).collect(),
+            contains: vec![true; num_indices],
+        }
+    }
+
+    pub fn insert(&mut self, index: usize) {
+        if !self.contains[index] {
+            self.contains[index] = true;
+            self.indices.push(index);
+        }
+    }
+
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+
+    pub fn clear(&mut self) {
+        for &index in self.indices.iter() {
+            self.contains[index] = false;
+        }
+
+        self.indices.clear();
+    }
+}
@@ -1,6 +1,8 @@
+use std::fmt;
+
 use crate::exec::Activation;
 use crate::machine::level::{self, InputsOutputs};
-use crate::machine::{BlipKind, Block, BlockIndex, Machine};
+use crate::machine::{BlipKind, Block, BlockIndex, Machine, TickNum};
 
 #[derive(PartialEq, Eq, Debug, Clone, Copy)]
 pub enum LevelStatus {
@@ -9,6 +11,61 @@ pub enum LevelStatus {
     Failed,
 }
 
+/// Why a level was failed.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum FailureReason {
+    /// An output received a blip of the wrong kind.
+    WrongOutput {
+        index: usize,
+        tick: TickNum,
+        expected: BlipKind,
+        actual: BlipKind,
+    },
+
+    /// An output received a blip after it had already received all the
+    /// blips that were expected.
+    TooManyOutputs {
+        index: usize,
+        tick: TickNum,
+        actual: BlipKind,
+    },
+
+    /// Nothing happens in the machine anymore, but some outputs are still
+    /// incomplete.
+    Stalled { tick: TickNum },
+}
+
+impl fmt::Display for FailureReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailureReason::WrongOutput {
+                index,
+                tick,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Output {} received {} in tick {}, but expected {}",
+                index, actual, tick, expected
+            ),
+            FailureReason::TooManyOutputs {
+                index,
+                tick,
+                actual,
+            } => write!(
+                f,
+                "Output {} received {} in tick {}, but expected no more blips",
+                index, actual, tick
+            ),
+            FailureReason::Stalled { tick } => write!(
+                f,
+                "Machine stalled in tick {} with incomplete outputs",
+                tick
+            ),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Input {
     pub block_index: Option<BlockIndex>,
@@ -38,6 +95,9 @@ pub struct LevelProgress {
     ///
     /// This vector has the same length as the level's `InputOutputs::outputs`.
     pub outputs: Vec<Output>,
+
+    /// The reason for the first failure, if the level has been failed.
+    pub failure: Option<FailureReason>,
 }
 
 impl LevelProgress {
@@ -97,6 +157,7 @@ impl LevelProgress {
             inputs_outputs,
             inputs,
             outputs,
+            failure: None,
         }
     }
 
@@ -118,7 +179,7 @@ impl LevelProgress {
         })
     }
 
-    pub fn update_outputs(&mut self, next_activation: &[Activation]) {
+    pub fn update_outputs(&mut self, tick: TickNum, next_activation: &[Activation]) {
         for (index, output) in self.outputs.iter_mut().enumerate() {
             let blip_kind = output
                 .block_index
@@ -131,11 +192,56 @@ impl LevelProgress {
                     output.num_fed += 1;
                 } else {
                     output.failed = true;
+
+                    let reason = if output.num_fed < spec.len() {
+                        FailureReason::WrongOutput {
+                            index,
+                            tick,
+                            expected: spec[output.num_fed],
+                            actual: blip_kind,
+                        }
+                    } else {
+                        FailureReason::TooManyOutputs {
+                            index,
+                            tick,
+                            actual: blip_kind,
+                        }
+                    };
+                    self.failure.get_or_insert(reason);
                 }
             }
         }
     }
 
+    /// Fails the level because nothing happens anymore, unless it has
+    /// already been completed or failed.
+    pub fn stall(&mut self, tick: TickNum) {
+        if self.status() == LevelStatus::Running {
+            self.failure = Some(FailureReason::Stalled { tick });
+        }
+    }
+
+    /// Are there inputs that still have blips to feed into the machine?
+    pub fn has_pending_inputs(&self) -> bool {
+        self.inputs.iter().enumerate().any(|(index, input)| {
+            input.block_index.is_some() && input.num_fed < self.inputs_outputs.inputs[index].len()
+        })
+    }
+
+    /// Returns the index of the output that is to blame for failing the
+    /// level. For stalled machines, this is the first incomplete output.
+    pub fn failed_output(&self) -> Option<usize> {
+        match self.failure.as_ref()? {
+            FailureReason::WrongOutput { index, .. } => Some(*index),
+            FailureReason::TooManyOutputs { index, .. } => Some(*index),
+            FailureReason::Stalled { .. } => {
+                self.outputs.iter().enumerate().position(|(index, output)| {
+                    output.num_fed < self.inputs_outputs.outputs[index].len()
+                })
+            }
+        }
+    }
+
     pub fn expected_output(&self, index: usize) -> Option<BlipKind> {
         self.outputs.get(index).and_then(|output| {
             let spec = &self.inputs_outputs.outputs[index];
@@ -156,7 +262,7 @@ impl LevelProgress {
             .enumerate()
             .all(|(index, output)| output.num_fed == self.inputs_outputs.outputs[index].len());
 
-        if any_failed {
+        if any_failed || self.failure.is_some() {
             LevelStatus::Failed
         } else if all_finished {
             LevelStatus::Completed
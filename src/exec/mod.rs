@@ -1,10 +1,17 @@
 pub mod anim;
+pub mod batch;
+pub mod cycle;
+mod dirty;
 pub mod level;
 pub mod neighbors;
 pub mod play;
+pub mod stats;
 #[cfg(test)]
 mod tests;
+pub mod trace;
+pub mod vcd;
 pub mod view;
+pub mod wind;
 
 use std::cmp;
 use std::collections::HashSet;
@@ -14,13 +21,16 @@ use coarse_prof::profile;
 use log::info;
 use rand::Rng;
 
-use crate::machine::grid::{Dir3, DirMap3, Point3, Vector3};
-use crate::machine::{BlipKind, Block, BlockIndex, Machine, PlacedBlock, TickNum};
+use crate::machine::grid::{Dir3, Point3, Vector3};
+use crate::machine::mods::CustomWind;
+use crate::machine::{BlipKind, Block, BlockIndex, Machine, PlacedBlock, TickNum, NUM_CHANNELS};
 use crate::util::vec_option::VecOption;
 
+use dirty::DirtySet;
 use neighbors::NeighborMap;
+use wind::{WindFlow, WindHoles, WindMask, WindState};
 
-pub use level::{LevelProgress, LevelStatus};
+pub use level::{FailureReason, LevelProgress, LevelStatus};
 pub use play::TickTime;
 pub use view::ExecView;
 
@@ -108,7 +118,7 @@ impl BlipStatus {
     }
 }
 
-#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Hash)]
 pub struct Blip {
     /// Blip kind.
     pub kind: BlipKind,
@@ -146,19 +156,34 @@ impl Blip {
         }
     }
 
-    fn next_pos(&self) -> Point3 {
-        self.pos
-            + self
-                .move_dir
-                .map_or(Vector3::zeros(), |dir| dir.to_vector())
+    /// The position the blip will be at after moving. Usually, this is just
+    /// the adjacent position, but portals may send blips elsewhere.
+    fn next_pos(&self, machine: &Machine, neighbor_map: &NeighborMap) -> Point3 {
+        let linked_pos = self.move_dir.and_then(|dir| {
+            let block_index = machine.get_index(&self.pos)?;
+            let neighbor_index = neighbor_map[block_index][dir]?;
+
+            Some(machine.blocks.data[neighbor_index].0)
+        });
+
+        linked_pos.unwrap_or_else(|| {
+            self.pos
+                + self
+                    .move_dir
+                    .map_or(Vector3::zeros(), |dir| dir.to_vector())
+        })
     }
 }
 
 pub type Activation = Option<BlipKind>;
 
+#[derive(Clone)]
 pub struct BlocksState {
-    pub wind_out: Vec<DirMap3<bool>>,
+    pub wind_out: WindState,
     pub activation: Vec<Activation>,
+
+    /// Indices of the blocks that have an activation, in no particular order.
+    activated: Vec<BlockIndex>,
 }
 
 impl BlocksState {
@@ -169,17 +194,40 @@ impl BlocksState {
         assert!(machine.is_contiguous());
 
         Self {
-            wind_out: vec![DirMap3::default(); machine.num_blocks()],
+            wind_out: WindState::new(machine.num_blocks()),
             activation: vec![Activation::default(); machine.num_blocks()],
+            activated: Vec::new(),
+        }
+    }
+
+    fn activate(&mut self, block_index: BlockIndex, kind: BlipKind) {
+        if self.activation[block_index].is_none() {
+            self.activated.push(block_index);
         }
+
+        self.activation[block_index] = cmp::max(self.activation[block_index], Some(kind));
+    }
+
+    fn clear_activation(&mut self) {
+        for &block_index in self.activated.iter() {
+            self.activation[block_index] = None;
+        }
+
+        self.activated.clear();
     }
 }
 
+#[derive(Clone)]
 pub struct Exec {
     cur_tick: TickNum,
 
     machine: Machine,
     neighbor_map: NeighborMap,
+    wind_holes: Vec<WindHoles>,
+    wind_flow: WindFlow,
+
+    /// For each channel, the indices of the transmitters on that channel.
+    transmitters: Vec<Vec<BlockIndex>>,
 
     level_progress: Option<LevelProgress>,
     next_level_progress: Option<LevelProgress>,
@@ -190,10 +238,40 @@ pub struct Exec {
     next_blocks: BlocksState,
 
     prev_activation: Vec<Activation>,
+    prev_activated: Vec<BlockIndex>,
 
     next_blip_count: Vec<usize>,
+
+    /// Indices of the blocks with a non-zero `next_blip_count`.
+    counted: Vec<BlockIndex>,
+
+    /// Whether to only update the blocks in which something may change.
+    /// Otherwise, all blocks are updated in every tick, which gives the
+    /// same results, but is much slower for large machines.
+    incremental: bool,
+
+    /// Blocks that may activate themselves, e.g. inputs and timers.
+    self_activating: Vec<BlockIndex>,
+
+    /// Words of `WindState` whose wind needs to be recomputed in the next
+    /// tick, since something that it depends on may have changed.
+    wind_dirty: DirtySet,
+    next_wind_dirty: DirtySet,
+
+    /// Words of `WindState` whose wind changed in the last tick.
+    wind_changed: Vec<usize>,
+
+    /// Number of consecutive ticks in which nothing happened.
+    num_idle_ticks: usize,
+
+    /// Number of idle ticks after which a level is considered to be stalled.
+    max_idle_ticks: usize,
 }
 
+/// Minimal number of idle ticks after which a level is considered to be
+/// stalled.
+const MIN_STALL_TICKS: usize = 16;
+
 impl Exec {
     pub fn new<R: Rng + ?Sized>(mut machine: Machine, rng: &mut R) -> Exec {
         // Make the machine's blocks contiguous in memory.
@@ -202,6 +280,14 @@ impl Exec {
         initialize_air_blocks(&mut machine);
 
         let neighbor_map = NeighborMap::new_from_machine(&machine);
+        let wind_holes: Vec<_> = machine
+            .iter_blocks()
+            .map(|(_, (_, placed_block))| WindHoles::new(&placed_block.block))
+            .collect();
+        let wind_flow = WindFlow::new(&neighbor_map, &wind_holes, |block_index| {
+            spawns_wind(machine.block_at_index(block_index))
+        });
+        let transmitters = transmitters_by_channel(&machine);
         let level_progress = machine.level.as_ref().map(|level| {
             let inputs_outputs = level.spec.gen_inputs_outputs(rng);
             LevelProgress::new(Some(&machine), inputs_outputs)
@@ -211,25 +297,64 @@ impl Exec {
         let next_blocks = BlocksState::new_initial(&machine);
         let prev_activation = vec![None; machine.num_blocks()];
         let next_blip_count = vec![0; machine.num_blocks()];
+        let self_activating = machine
+            .iter_blocks()
+            .filter(|(_, (_, placed_block))| can_self_activate(&placed_block.block))
+            .map(|(block_index, _)| block_index)
+            .collect();
+        let wind_dirty = DirtySet::new_full(blocks.wind_out.num_words());
+        let next_wind_dirty = DirtySet::new(blocks.wind_out.num_words());
+
+        // Timers may activate after a long pause, so we need to wait for at
+        // least one period before declaring that nothing happens anymore.
+        let max_idle_ticks = machine
+            .iter_blocks()
+            .filter_map(|(_, (_, placed_block))| placed_block.block.timer_period())
+            .map(|period| period + 1)
+            .fold(MIN_STALL_TICKS, cmp::max);
 
         Exec {
             cur_tick: 0,
             machine,
             neighbor_map,
+            wind_holes,
+            wind_flow,
+            transmitters,
             level_progress,
             next_level_progress,
             blips: VecOption::new(),
             blocks,
             next_blocks,
             prev_activation,
+            prev_activated: Vec::new(),
             next_blip_count,
+            counted: Vec::new(),
+            incremental: true,
+            self_activating,
+            wind_dirty,
+            next_wind_dirty,
+            wind_changed: Vec::new(),
+            num_idle_ticks: 0,
+            max_idle_ticks,
         }
     }
 
+    /// Switches between updating only the blocks in which something may
+    /// change, and updating all blocks. Both give the same results, so this
+    /// is only useful for checking exactly that in tests.
+    #[cfg(test)]
+    pub fn set_incremental(&mut self, incremental: bool) {
+        self.incremental = incremental;
+    }
+
     pub fn machine(&self) -> &Machine {
         &self.machine
     }
 
+    pub fn cur_tick(&self) -> TickNum {
+        self.cur_tick
+    }
+
     pub fn neighbor_map(&self) -> &NeighborMap {
         &self.neighbor_map
     }
@@ -262,30 +387,94 @@ impl Exec {
         // 1) Advance state.
         self.level_progress = self.next_level_progress.clone();
 
-        // Next wind_out will be written from scratch in step 2.
+        // Next wind_out will be written in step 2, starting from the current
+        // wind. Words whose wind did not change in the last tick are the same
+        // in both buffers, so we only need to copy the changed ones.
         mem::swap(&mut self.blocks.wind_out, &mut self.next_blocks.wind_out);
 
+        // If there are many of them, it is faster to copy everything.
+        if self.wind_changed.len() > self.blocks.wind_out.num_words() / 2 {
+            self.next_blocks.wind_out.copy_from(&self.blocks.wind_out);
+        } else {
+            for &word in self.wind_changed.iter() {
+                let wind_out = self.blocks.wind_out.word(word);
+                self.next_blocks.wind_out.set_word(word, wind_out);
+            }
+        }
+        self.wind_changed.clear();
+
         // Pass along activation triple-buffer
         mem::swap(&mut self.prev_activation, &mut self.next_blocks.activation);
         mem::swap(&mut self.prev_activation, &mut self.blocks.activation);
-        for activation in self.next_blocks.activation.iter_mut() {
-            *activation = None;
+        mem::swap(&mut self.prev_activated, &mut self.next_blocks.activated);
+        mem::swap(&mut self.prev_activated, &mut self.blocks.activated);
+
+        // The wind of blocks whose activation goes away may change as well.
+        for &block_index in self.next_blocks.activated.iter() {
+            self.wind_dirty.insert(WindState::word_index(block_index));
         }
+        self.next_blocks.clear_activation();
 
         // 2) Spawn and move wind.
+        //    The wind of a block depends only on the block itself, its
+        //    activations and the wind of its neighbors in the previous tick.
+        //    Thus, we only need to recompute it if one of those changed.
+        //    Wind is advected a whole word of blocks at a time, which is fine,
+        //    since recomputing the other blocks in a word gives the same wind
+        //    that they already have.
         {
             profile!("wind");
 
-            for block_index in 0..self.machine.num_blocks() {
-                self.next_blocks.wind_out[block_index] = spawn_or_advect_wind(
-                    block_index,
-                    &self.machine,
-                    &self.neighbor_map,
-                    &self.blocks.wind_out,
-                    &self.prev_activation,
-                    &self.blocks.activation,
-                );
+            if self.incremental {
+                for &block_index in self.prev_activated.iter().chain(&self.blocks.activated) {
+                    self.wind_dirty.insert(WindState::word_index(block_index));
+                }
+            } else {
+                for word in 0..self.blocks.wind_out.num_words() {
+                    self.wind_dirty.insert(word);
+                }
+            }
+
+            for &word in self.wind_dirty.indices() {
+                let activation = &self.blocks.activation;
+                let active = self
+                    .blocks
+                    .wind_out
+                    .lanes_where(word, |block_index| activation[block_index].is_some());
+                let wind_out = self
+                    .wind_flow
+                    .advect_word(word, &self.blocks.wind_out, active);
+                self.next_blocks.wind_out.set_word(word, wind_out);
+
+                // Blocks that spawn wind are rare, so we look at them one at a
+                // time, starting with the wind that they would conduct.
+                if self.wind_flow.any_spawns_wind(word) {
+                    for block_index in self.blocks.wind_out.word_blocks(word) {
+                        if self.wind_flow.spawns_wind(block_index) {
+                            let wind_out = spawn_wind(
+                                block_index,
+                                &self.machine,
+                                &self.wind_holes,
+                                self.next_blocks.wind_out.get(block_index),
+                                &self.prev_activation,
+                                &self.blocks.activation,
+                            );
+                            self.next_blocks.wind_out.set(block_index, wind_out);
+                        }
+                    }
+                }
+
+                if self.next_blocks.wind_out.word(word) != self.blocks.wind_out.word(word) {
+                    self.wind_changed.push(word);
+
+                    for &reader in self.wind_flow.readers(word) {
+                        self.next_wind_dirty.insert(reader);
+                    }
+                }
             }
+
+            self.wind_dirty.clear();
+            mem::swap(&mut self.wind_dirty, &mut self.next_wind_dirty);
         }
 
         // 3) Remove dead blips.
@@ -306,7 +495,7 @@ impl Exec {
                 blip.status = BlipStatus::Existing;
 
                 if let Some(move_dir) = blip.move_dir {
-                    blip.pos += move_dir.to_vector();
+                    blip.pos = blip.next_pos(&self.machine, &self.neighbor_map);
                     blip.orient = move_dir;
                 }
 
@@ -314,6 +503,7 @@ impl Exec {
                     blip,
                     &self.machine,
                     &self.neighbor_map,
+                    &self.wind_holes,
                     &self.blocks.wind_out,
                     &self.next_blocks.wind_out,
                     &self.blocks.activation,
@@ -326,15 +516,17 @@ impl Exec {
         {
             profile!("count");
 
-            for count in self.next_blip_count.iter_mut() {
-                *count = 0;
+            for &block_index in self.counted.iter() {
+                self.next_blip_count[block_index] = 0;
             }
+            self.counted.clear();
 
             for (_, blip) in self.blips.iter() {
                 debug_assert!(!blip.status.is_spawning());
 
-                if let Some((next_block_index, next_block)) =
-                    self.machine.get_with_index(&blip.next_pos())
+                if let Some((next_block_index, next_block)) = self
+                    .machine
+                    .get_with_index(&blip.next_pos(&self.machine, &self.neighbor_map))
                 {
                     // Don't count blips that will kill get killed by their
                     // new block in step 7.
@@ -344,7 +536,11 @@ impl Exec {
                         .is_some();
 
                     if !will_die_anyway {
-                        self.next_blip_count[next_block_index] += 1;
+                        count_blip(
+                            &mut self.next_blip_count,
+                            &mut self.counted,
+                            next_block_index,
+                        );
                     }
                 }
             }
@@ -354,20 +550,51 @@ impl Exec {
         {
             profile!("effects");
 
-            for block_index in self.machine.blocks.data.keys() {
+            // In the incremental case, we only look at blocks that can
+            // activate themselves or that are activated, since nothing
+            // happens in all the other blocks.
+            let all_blocks: Vec<BlockIndex>;
+            let self_activating = if self.incremental {
+                &self.self_activating
+            } else {
+                all_blocks = (0..self.machine.num_blocks()).collect();
+                &all_blocks
+            };
+
+            for &block_index in self_activating.iter() {
                 if let Some(kind) = self_activate_block(
                     block_index,
+                    self.cur_tick,
                     &self.machine.blocks.data,
                     &mut self.level_progress,
                     &self.neighbor_map,
+                    &self.transmitters,
+                    &self.prev_activation,
                     &self.next_blip_count,
                 ) {
-                    self.blocks.activation[block_index] =
-                        cmp::max(self.blocks.activation[block_index], Some(kind));
+                    self.blocks.activate(block_index, kind);
                 }
             }
 
-            for (block_index, (block_pos, placed_block)) in self.machine.blocks.data.iter_mut() {
+            // Keep the order of the blocks, so that blips are always spawned
+            // in the same order.
+            let activated: Vec<BlockIndex> = if self.incremental {
+                let mut activated: Vec<BlockIndex> = self
+                    .prev_activated
+                    .iter()
+                    .chain(&self.blocks.activated)
+                    .copied()
+                    .collect();
+                activated.sort_unstable();
+                activated.dedup();
+                activated
+            } else {
+                (0..self.machine.num_blocks()).collect()
+            };
+
+            for block_index in activated {
+                let (block_pos, placed_block) = &mut self.machine.blocks.data[block_index];
+
                 if let Some(blip_kind) = self.prev_activation[block_index] {
                     run_prev_activated_block(
                         block_pos,
@@ -387,6 +614,14 @@ impl Exec {
                         &self.neighbor_map,
                         &self.next_blip_count,
                     );
+
+                    // Activation may change the block, and with it its holes.
+                    self.wind_holes[block_index] = WindHoles::new(&placed_block.block);
+                    self.wind_flow.set_block(
+                        block_index,
+                        &self.wind_holes[block_index],
+                        spawns_wind(&placed_block.block),
+                    );
                 }
             }
 
@@ -394,8 +629,9 @@ impl Exec {
             // counted, lest we lose control over our population.
             for (_, blip) in self.blips.iter() {
                 if blip.status.is_spawning() {
-                    if let Some((next_block_index, next_block)) =
-                        self.machine.get_with_index(&blip.next_pos())
+                    if let Some((next_block_index, next_block)) = self
+                        .machine
+                        .get_with_index(&blip.next_pos(&self.machine, &self.neighbor_map))
                     {
                         // Don't count blips that will kill get killed by their
                         // new block in step 7.
@@ -405,7 +641,11 @@ impl Exec {
                             .is_some();
 
                         if !will_die_anyway {
-                            self.next_blip_count[next_block_index] += 1;
+                            count_blip(
+                                &mut self.next_blip_count,
+                                &mut self.counted,
+                                next_block_index,
+                            );
                         }
                     }
                 }
@@ -419,8 +659,9 @@ impl Exec {
             profile!("activate");
 
             for (_, blip) in self.blips.iter_mut() {
-                if let Some((next_block_index, next_block)) =
-                    self.machine.get_with_index(&blip.next_pos())
+                if let Some((next_block_index, next_block)) = self
+                    .machine
+                    .get_with_index(&blip.next_pos(&self.machine, &self.neighbor_map))
                 {
                     if self.next_blip_count[next_block_index] > 1 {
                         // We ran into another blip.
@@ -445,10 +686,7 @@ impl Exec {
 
                         if activate {
                             // This block's effect will run in the next tick.
-                            self.next_blocks.activation[next_block_index] = cmp::max(
-                                self.next_blocks.activation[next_block_index],
-                                Some(blip.kind),
-                            );
+                            self.next_blocks.activate(next_block_index, blip.kind);
                         }
 
                         if let Some(die_mode) = next_block.block.is_blip_killer(inverse_dir) {
@@ -468,12 +706,41 @@ impl Exec {
         //    to see which blips exactly caused completion or failure.
         self.next_level_progress = self.level_progress.as_ref().map(|progress| {
             let mut next_progress = progress.clone();
-            next_progress.update_outputs(&self.next_blocks.activation);
+            next_progress.update_outputs(self.cur_tick, &self.next_blocks.activation);
             next_progress
         });
 
+        // 9) Detect levels that can not make any more progress, so that we do
+        //    not keep on running forever.
+        if self.is_idle() {
+            self.num_idle_ticks += 1;
+        } else {
+            self.num_idle_ticks = 0;
+        }
+
+        if self.num_idle_ticks > self.max_idle_ticks {
+            let tick = self.cur_tick;
+            if let Some(progress) = self.next_level_progress.as_mut() {
+                progress.stall(tick);
+            }
+        }
+
         self.cur_tick += 1;
     }
+
+    /// Is the machine without any blips and activations, while there are no
+    /// more inputs to feed?
+    fn is_idle(&self) -> bool {
+        let no_blips = self.blips.iter().all(|(_, blip)| blip.status.is_dead());
+        let no_activations =
+            self.blocks.activated.is_empty() && self.next_blocks.activated.is_empty();
+        let no_inputs = self
+            .next_level_progress
+            .as_ref()
+            .map_or(true, |progress| !progress.has_pending_inputs());
+
+        no_blips && no_activations && no_inputs
+    }
 }
 
 fn initialize_air_blocks(machine: &mut Machine) {
@@ -486,9 +753,14 @@ fn initialize_air_blocks(machine: &mut Machine) {
                 Dir3::ALL.iter().flat_map(move |dir| {
                     let mut result = Vec::new();
 
-                    let build_air = (block.block.has_wind_hole_out(*dir, false)
-                        && block.block.has_move_hole(*dir, false))
-                        || block.block.has_blip_spawn(*dir);
+                    // Portals are linked to their partner, so there is no
+                    // falling out of their back.
+                    let is_portal_link = block.block.portal_link_dir() == Some(*dir);
+
+                    let build_air = !is_portal_link
+                        && ((block.block.has_wind_hole_out(*dir, false)
+                            && block.block.has_move_hole(*dir, false))
+                            || block.block.has_blip_spawn(*dir));
 
                     if build_air {
                         let mut iter_pos = pos + dir.to_vector();
@@ -513,74 +785,83 @@ fn initialize_air_blocks(machine: &mut Machine) {
     }
 }
 
-fn advect_wind(
-    block_index: BlockIndex,
-    machine: &Machine,
-    neighbor_map: &NeighborMap,
-    wind_out: &[DirMap3<bool>],
-    activation: &[Activation],
-) -> DirMap3<bool> {
-    let block = machine.block_at_index(block_index);
+fn transmitters_by_channel(machine: &Machine) -> Vec<Vec<BlockIndex>> {
+    let mut transmitters = vec![Vec::new(); NUM_CHANNELS];
 
-    // Check if we got any wind in flow from our neighbors in the
-    // old state
-    let block_wind_in = neighbor_map[block_index].map(|dir, neighbor_index| {
-        neighbor_index.map_or(false, |neighbor_index| {
-            block.has_wind_hole_in(dir, activation[block_index].is_some())
-                && wind_out[neighbor_index][dir.invert()]
-        })
-    });
+    for (block_index, (_, placed_block)) in machine.iter_blocks() {
+        if let Block::Transmitter { channel, .. } = placed_block.block {
+            if let Some(channel_transmitters) = transmitters.get_mut(channel) {
+                channel_transmitters.push(block_index);
+            }
+        }
+    }
 
-    if block_wind_in.values().any(|flow| *flow) {
-        // Forward in flow to our outgoing wind hole directions
-        neighbor_map[block_index].map(|dir, neighbor_index| {
-            let hole_out = block.has_wind_hole_out(dir, activation[block_index].is_some());
-            let no_wind_in = neighbor_index.map_or(true, |_| !block_wind_in[dir]);
+    transmitters
+}
 
-            hole_out && no_wind_in
-        })
-    } else {
-        DirMap3::from_fn(|_| false)
+/// Returns true if the wind flowing out of the block may be something else
+/// than the wind that it conducts from its neighbors.
+fn spawns_wind(block: &Block) -> bool {
+    match block {
+        Block::WindSource
+        | Block::BlipWindSource { .. }
+        | Block::Input { .. }
+        | Block::DetectorWindSource { .. }
+        | Block::Delay { .. }
+        | Block::Custom(_) => true,
+        _ => false,
     }
 }
 
-fn spawn_or_advect_wind(
+/// Returns the wind flowing out of a block that may spawn wind, given the
+/// wind `pipe` that it would conduct from its neighbors.
+fn spawn_wind(
     block_index: BlockIndex,
     machine: &Machine,
-    neighbor_map: &NeighborMap,
-    wind_out: &[DirMap3<bool>],
+    wind_holes: &[WindHoles],
+    pipe: WindMask,
     prev_activation: &[Activation],
     activation: &[Activation],
-) -> DirMap3<bool> {
+) -> WindMask {
     let block = machine.block_at_index(block_index);
+    let source = wind_holes[block_index].source();
 
     match block {
-        Block::WindSource => DirMap3::from_fn(|_| true),
+        Block::WindSource => WindMask::ALL,
         Block::BlipWindSource { .. } => {
             if activation[block_index].is_some() {
-                DirMap3::from_fn(|dir| block.has_wind_source(dir))
+                source
             } else {
-                DirMap3::from_fn(|_| false)
+                WindMask::NONE
             }
         }
-        Block::Input { out_dir, .. } => DirMap3::from_fn(|dir| dir == *out_dir),
+        Block::Input { out_dir, .. } => WindMask::from_dir(*out_dir),
         Block::DetectorWindSource { .. } => {
-            let pipe = advect_wind(block_index, machine, neighbor_map, wind_out, activation);
-
             if activation[block_index].is_some() {
-                DirMap3::from_fn(|dir| block.has_wind_source(dir) || pipe[dir])
+                source | pipe
             } else {
                 pipe
             }
         }
         Block::Delay { flow_dir } => {
             if prev_activation[block_index].is_some() {
-                DirMap3::from_fn(|dir| dir == *flow_dir)
+                WindMask::from_dir(*flow_dir)
             } else {
-                DirMap3::from_fn(|_| false)
+                WindMask::NONE
             }
         }
-        _ => advect_wind(block_index, machine, neighbor_map, wind_out, activation),
+        Block::Custom(custom) => match custom.wind() {
+            CustomWind::Conduct => pipe,
+            CustomWind::Source => source,
+            CustomWind::SourceWhenActivated => {
+                if activation[block_index].is_some() {
+                    source
+                } else {
+                    WindMask::NONE
+                }
+            }
+        },
+        _ => pipe,
     }
 }
 
@@ -588,8 +869,9 @@ fn blip_move_dir(
     blip: &Blip,
     machine: &Machine,
     neighbor_map: &NeighborMap,
-    wind_out: &[DirMap3<bool>],
-    next_wind_out: &[DirMap3<bool>],
+    wind_holes: &[WindHoles],
+    wind_out: &WindState,
+    next_wind_out: &WindState,
     activation: &[Activation],
 ) -> Option<Dir3> {
     let (block_index, placed_block) = machine.get_with_index(&blip.pos)?;
@@ -603,11 +885,13 @@ fn blip_move_dir(
 
             // Our current block needs wind and a move hole in the same direction.
             let can_move_out =
-                next_wind_out[block_index][dir] && block.has_move_hole(dir, is_active);
+                next_wind_out.get(block_index).contains(dir) && block.has_move_hole(dir, is_active);
 
             // There needs to be a neighboring block which wants to receive blips opposite.
             let can_move_in = neighbor_block.has_move_hole(dir.invert(), is_active)
-                && (neighbor_block.has_wind_hole_in(dir.invert(), is_active)
+                && (wind_holes[neighbor_index]
+                    .hole_in(is_active)
+                    .contains(dir.invert())
                     || neighbor_block.has_button(dir.invert()));
 
             can_move_out && can_move_in
@@ -617,7 +901,8 @@ fn blip_move_dir(
     //
     let block_wind_in = neighbor_map[block_index].map(|dir, neighbor_index| {
         neighbor_index.map_or(false, |neighbor_index| {
-            block.has_wind_hole_in(dir, is_active) && wind_out[neighbor_index][dir.invert()]
+            wind_holes[block_index].hole_in(is_active).contains(dir)
+                && wind_out.get(neighbor_index).contains(dir.invert())
         })
     });
 
@@ -649,11 +934,38 @@ fn blip_move_dir(
     }
 }
 
+fn count_blip(
+    next_blip_count: &mut [usize],
+    counted: &mut Vec<BlockIndex>,
+    block_index: BlockIndex,
+) {
+    if next_blip_count[block_index] == 0 {
+        counted.push(block_index);
+    }
+
+    next_blip_count[block_index] += 1;
+}
+
+/// Returns true for all blocks for which `self_activate_block` may return an
+/// activation.
+fn can_self_activate(block: &Block) -> bool {
+    match block {
+        Block::BlipSpawn { .. }
+        | Block::Input { .. }
+        | Block::Receiver { .. }
+        | Block::Timer { .. } => true,
+        _ => false,
+    }
+}
+
 fn self_activate_block(
     block_index: BlockIndex,
+    cur_tick: TickNum,
     blocks: &VecOption<(Point3, PlacedBlock)>,
     level_progress: &mut Option<LevelProgress>,
     neighbor_map: &NeighborMap,
+    transmitters: &[Vec<BlockIndex>],
+    prev_activation: &[Activation],
     next_blip_count: &[usize],
 ) -> Option<BlipKind> {
     match blocks[block_index].1.block.clone() {
@@ -683,6 +995,36 @@ fn self_activate_block(
                 }
             }
         }
+        Block::Receiver { channel, .. } => {
+            // Transmitters that were activated in the previous tick activate
+            // all of their receivers now.
+            return transmitters
+                .get(channel)
+                .into_iter()
+                .flatten()
+                .filter_map(|&transmitter_index| prev_activation[transmitter_index])
+                .max();
+        }
+        Block::Timer {
+            out_dir,
+            kind,
+            period,
+            phase,
+        } => {
+            if let Some(neighbor_index) = neighbor_map[block_index][out_dir] {
+                // Like blip spawns, timers act only if there is no blip at the
+                // output position.
+                let is_safe = next_blip_count[neighbor_index] == 0
+                    || blocks[neighbor_index]
+                        .1
+                        .block
+                        .is_blip_killer(Some(out_dir))
+                        .is_some();
+                if is_safe && period > 0 && cur_tick % period == phase % period {
+                    return Some(kind);
+                }
+            }
+        }
         _ => (),
     }
 
@@ -769,6 +1111,35 @@ fn run_activated_block(
                 BlipSpawnMode::Quick,
             ));
         }
+        Block::Timer { out_dir, kind, .. } => {
+            blips.add(Blip::new(
+                *kind,
+                *block_pos,
+                *out_dir,
+                Some(*out_dir),
+                BlipSpawnMode::Bridge,
+            ));
+        }
+        Block::Receiver { out_dir, .. } => {
+            blips.add(Blip::new(
+                blip_kind,
+                *block_pos,
+                *out_dir,
+                Some(*out_dir),
+                BlipSpawnMode::Quick,
+            ));
+        }
+        Block::Custom(custom) => {
+            for out_dir in custom.spawn_dirs() {
+                blips.add(Blip::new(
+                    blip_kind,
+                    *block_pos,
+                    out_dir,
+                    Some(out_dir),
+                    BlipSpawnMode::Bridge,
+                ));
+            }
+        }
         Block::BlipDeleter { out_dirs, .. } => {
             for &out_dir in &[out_dirs.0, out_dirs.1] {
                 let neighbor_index = neighbor_map[block_index][out_dir];
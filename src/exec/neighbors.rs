@@ -3,27 +3,22 @@ use std::ops::Index;
 use crate::machine::grid::DirMap3;
 use crate::machine::{BlockIndex, Machine};
 
+#[derive(Clone)]
 pub struct NeighborMap(Vec<DirMap3<Option<BlockIndex>>>);
 
 impl NeighborMap {
     pub fn new_from_machine(machine: &Machine) -> Self {
         assert!(machine.is_contiguous());
 
-        NeighborMap(
-            machine
-                .iter_blocks()
-                .map(|(_, (pos, _))| {
-                    DirMap3::from_fn(|dir| {
-                        machine
-                            .blocks
-                            .indices
-                            .get(&(pos + dir.to_vector()))
-                            .cloned()
-                            .flatten()
-                    })
-                })
-                .collect(),
-        )
+        let portal_partners = machine.portal_partners();
+        let map = machine
+            .iter_blocks()
+            .map(|(_, (pos, _))| {
+                DirMap3::from_fn(|dir| machine.neighbor_index(pos, dir, &portal_partners))
+            })
+            .collect();
+
+        NeighborMap(map)
     }
 }
 
@@ -6,6 +6,7 @@ use imgui::{im_str, ImString};
 use log::info;
 use nalgebra as na;
 
+use crate::exec::cycle::Cycle;
 use crate::util::timer::{self, Timer};
 
 /// Possible choices in the UI for number of ticks per second to play.
@@ -17,6 +18,10 @@ pub const TICKS_PER_SEC_CHOICES: &[&str] = &[
 
 pub const MAX_TICKS_PER_UPDATE: usize = 1024;
 
+/// Number of periods to skip at once when the execution is known to repeat
+/// itself.
+pub const SKIP_PERIODS: usize = 10;
+
 #[derive(Debug, Clone)]
 pub struct Config {
     pub play_pause_key: VirtualKeyCode,
@@ -131,6 +136,11 @@ pub struct Play {
 
     play_pause_pressed: bool,
     stop_pressed: bool,
+
+    finish_pressed: bool,
+
+    /// Number of ticks to skip in the next update.
+    skip_ticks: Option<usize>,
 }
 
 impl Play {
@@ -140,15 +150,26 @@ impl Play {
             ticks_per_sec_index: 2,
             play_pause_pressed: false,
             stop_pressed: false,
+            finish_pressed: false,
+            skip_ticks: None,
         }
     }
 
+    /// Has the user requested to finish execution in the background? The
+    /// request is reset by `update_status`.
+    pub fn is_finish_pressed(&self) -> bool {
+        self.finish_pressed
+    }
+
     pub fn update_status(&mut self, dt: Duration, status: Option<&Status>) -> Option<Status> {
         let play_pause_pressed = self.play_pause_pressed;
         let stop_pressed = self.stop_pressed;
+        let finish_pressed = self.finish_pressed;
+        let skip_ticks = self.skip_ticks.take().unwrap_or(0);
 
         self.play_pause_pressed = false;
         self.stop_pressed = false;
+        self.finish_pressed = false;
 
         // Can unwrap here since TICKS_PER_SEC_CHOICES contains
         // only valid floats.
@@ -164,6 +185,11 @@ impl Play {
                 Some(Status::Paused { time: time.clone() })
             }
             Some(Status::Playing { .. }) if stop_pressed => None,
+            Some(Status::Playing { time, .. }) if finish_pressed => {
+                // Execution is paused while it is finished in the background.
+                info!("Finishing exec from time {}", time);
+                Some(Status::Paused { time: time.clone() })
+            }
             Some(Status::Playing { time, .. }) => {
                 // Set the Timer's period first, since this may change
                 // how many ticks are run in the current update.
@@ -176,6 +202,10 @@ impl Play {
                 let num_ticks_since_last_update = new_time.next_tick_timer.trigger_n();
                 new_time.num_ticks_passed += num_ticks_since_last_update.min(MAX_TICKS_PER_UPDATE);
 
+                // Skipped ticks are run all at once, without being shown.
+                let num_ticks_since_last_update = num_ticks_since_last_update + skip_ticks;
+                new_time.num_ticks_passed += skip_ticks;
+
                 Some(Status::Playing {
                     num_ticks_since_last_update,
                     prev_time: Some(time.clone()),
@@ -264,10 +294,21 @@ impl Play {
         }
     }
 
-    pub fn ui(&mut self, window_size: na::Vector2<f32>, status: Option<&Status>, ui: &imgui::Ui) {
+    pub fn ui(
+        &mut self,
+        window_size: na::Vector2<f32>,
+        status: Option<&Status>,
+        cycle: Option<Cycle>,
+        finish_progress: Option<f32>,
+        ui: &imgui::Ui,
+    ) {
         let bg_alpha = 0.8;
 
         let is_stopped = status.is_none();
+        let is_playing = status.map_or(false, |status| match status {
+            Status::Playing { .. } => true,
+            _ => false,
+        });
         let is_paused = status.map_or(false, |status| status.is_paused());
         let is_finished = status.map_or(false, |status| status.is_finished());
 
@@ -324,6 +365,22 @@ impl Play {
                     ui.tooltip(|| ui.text(&ImString::new(text)));
                 }
 
+                ui.same_line(0.0);
+
+                let selectable = imgui::Selectable::new(im_str!("⏩"))
+                    .disabled(is_stopped || is_finished || finish_progress.is_some())
+                    .size([21.0, 0.0]);
+                if selectable.build(ui) {
+                    self.finish_pressed = true;
+                }
+                if ui.is_item_hovered() {
+                    ui.tooltip(|| {
+                        ui.text(im_str!(
+                            "Run until the level is completed or failed, as fast as possible."
+                        ))
+                    });
+                }
+
                 ui.same_line_with_spacing(0.0, 30.0);
 
                 let selectable = imgui::Selectable::new(im_str!("-"))
@@ -359,7 +416,33 @@ impl Play {
                     ui.tooltip(|| ui.text(&ImString::new(text)));
                 }
 
+                if let Some(cycle) = cycle {
+                    ui.same_line_with_spacing(0.0, 30.0);
+
+                    let selectable = imgui::Selectable::new(im_str!("⏭"))
+                        .disabled(!is_playing)
+                        .size([21.0, 0.0]);
+                    if selectable.build(ui) {
+                        self.skip_ticks = Some(SKIP_PERIODS * cycle.period);
+                    }
+                    if ui.is_item_hovered() {
+                        let text = format!(
+                            "The machine repeats itself every {} ticks, starting at tick {}.\n\n\
+                             Skip {} periods.",
+                            cycle.period, cycle.start, SKIP_PERIODS,
+                        );
+                        ui.tooltip(|| ui.text(&ImString::new(text)));
+                    }
+                }
+
                 ui.set_window_font_scale(1.0);
+
+                if let Some(finish_progress) = finish_progress {
+                    imgui::ProgressBar::new(finish_progress)
+                        .overlay_text(im_str!("Finishing..."))
+                        .size([-1.0, 0.0])
+                        .build(ui);
+                }
             });
     }
 }
@@ -0,0 +1,111 @@
+use crate::exec::{BlipDieMode, Exec};
+
+/// Activity of one block, accumulated over all ticks of an execution.
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct BlockStats {
+    /// Number of ticks in which the block was activated.
+    pub activations: usize,
+
+    /// Number of ticks in which a blip was in the block.
+    pub blips: usize,
+
+    /// Number of blips that died in the block, indexed by `die_mode_index`.
+    killed: [usize; 3],
+}
+
+impl BlockStats {
+    pub fn killed(&self, die_mode: BlipDieMode) -> usize {
+        self.killed[die_mode_index(die_mode)]
+    }
+}
+
+fn die_mode_index(die_mode: BlipDieMode) -> usize {
+    match die_mode {
+        BlipDieMode::PopEarly => 0,
+        BlipDieMode::PopMiddle => 1,
+        BlipDieMode::PressButton => 2,
+    }
+}
+
+/// Per-block activity statistics of an execution, e.g. for finding blocks that
+/// are never used or places where blips collide.
+#[derive(Clone, Debug)]
+pub struct Stats {
+    /// Statistics for each block, indexed by the block index in the executed
+    /// machine.
+    pub blocks: Vec<BlockStats>,
+}
+
+impl Stats {
+    pub fn new(exec: &Exec) -> Self {
+        Self {
+            blocks: vec![BlockStats::default(); exec.machine().num_blocks()],
+        }
+    }
+
+    /// Accumulates the activity in the current tick of `exec`.
+    pub fn record(&mut self, exec: &Exec) {
+        for (stats, activation) in self.blocks.iter_mut().zip(&exec.blocks().activation) {
+            if activation.is_some() {
+                stats.activations += 1;
+            }
+        }
+
+        for (_, blip) in exec.blips().iter() {
+            if let Some(block_index) = exec.machine().get_index(&blip.pos) {
+                self.blocks[block_index].blips += 1;
+            }
+
+            // Dead blips are removed at the start of the next tick, so each
+            // of them is counted exactly once here. Blips die while moving,
+            // so we blame the block that they were about to enter, if any.
+            if let Some(die_mode) = blip.status.die_mode() {
+                let next_pos = blip.next_pos(exec.machine(), exec.neighbor_map());
+                let block_index = exec
+                    .machine()
+                    .get_index(&next_pos)
+                    .or_else(|| exec.machine().get_index(&blip.pos));
+
+                if let Some(block_index) = block_index {
+                    self.blocks[block_index].killed[die_mode_index(die_mode)] += 1;
+                }
+            }
+        }
+    }
+}
+
+/// A statistic that can be shown as a heatmap on top of the blocks.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Heatmap {
+    Activations,
+    Blips,
+    Killed(BlipDieMode),
+}
+
+impl Heatmap {
+    pub const ALL: &'static [Heatmap] = &[
+        Heatmap::Activations,
+        Heatmap::Blips,
+        Heatmap::Killed(BlipDieMode::PopEarly),
+        Heatmap::Killed(BlipDieMode::PopMiddle),
+        Heatmap::Killed(BlipDieMode::PressButton),
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Heatmap::Activations => "Activations",
+            Heatmap::Blips => "Ticks with blips",
+            Heatmap::Killed(BlipDieMode::PopEarly) => "Blips killed early",
+            Heatmap::Killed(BlipDieMode::PopMiddle) => "Blips killed by collisions",
+            Heatmap::Killed(BlipDieMode::PressButton) => "Blips pressing buttons",
+        }
+    }
+
+    pub fn value(self, stats: &BlockStats) -> usize {
+        match self {
+            Heatmap::Activations => stats.activations,
+            Heatmap::Blips => stats.blips,
+            Heatmap::Killed(die_mode) => stats.killed(die_mode),
+        }
+    }
+}
@@ -1,10 +1,20 @@
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+
 use rand::Rng;
 
 use crate::edit::piece::{Piece, Transform};
-use crate::exec::{BlipSpawnMode, BlipStatus, Exec};
+use crate::exec::batch::{self, Batch};
+use crate::exec::cycle::CycleDetector;
+use crate::exec::stats::Stats;
+use crate::exec::wind::{WindMask, WindState};
+use crate::exec::{vcd, BlipDieMode, BlipSpawnMode, BlipStatus, Exec, FailureReason, LevelStatus};
 use crate::machine::grid::{Dir3, Point3};
-use crate::machine::string_util::blocks_from_string;
-use crate::machine::{grid, Block, Machine, PlacedBlock};
+use crate::machine::level::{Level, Spec};
+use crate::machine::mods::{self, Custom, Mods};
+use crate::machine::string_util::{blocks_from_string, machine_from_string};
+use crate::machine::{grid, BlipKind, Block, Machine, PlacedBlock, SavedMachine, TickNum};
 
 /// Test that wind flows one grid block per tick.
 #[test]
@@ -107,6 +117,52 @@ fn test_merge_xy_wind_propagation() {
     });
 }
 
+/// Test that wind flows one grid block per tick when the machine is rotated
+/// out of the XY plane.
+#[test]
+fn test_straight_wind_propagation_3d() {
+    let m = "
+◉----------
+";
+
+    test_transform_invariant_3d(&blocks_from_string(m), |t, exec| {
+        for i in 0..=20 {
+            exec.update();
+
+            for x in 1..=10 {
+                assert_eq!(next_wind_out(exec, t * (x, 0, 0), t * Dir3::X_POS), x <= i);
+                assert!(!next_wind_out(exec, t * (x, 0, 0), t * Dir3::X_NEG));
+            }
+        }
+    });
+}
+
+/// Test that intersections propagate wind in all directions when the
+/// machine is rotated out of the XY plane.
+#[test]
+fn test_merge_wind_propagation_3d() {
+    let m = "
+        |
+◉-------┼--
+        |
+";
+
+    test_transform_invariant_3d(&blocks_from_string(m), |t, exec| {
+        for i in 0..20 {
+            exec.update();
+
+            assert_eq!(next_wind_out(exec, t * (8, 1, 0), t * Dir3::Y_NEG), i >= 8);
+            assert_eq!(next_wind_out(exec, t * (8, 0, 0), t * Dir3::Y_NEG), i >= 9);
+            assert_eq!(next_wind_out(exec, t * (8, 1, 0), t * Dir3::Y_POS), i >= 8);
+            assert_eq!(next_wind_out(exec, t * (8, 2, 0), t * Dir3::Y_POS), i >= 9);
+            assert_eq!(
+                next_wind_out(exec, t * (10, 1, 0), t * Dir3::X_POS),
+                i >= 10
+            );
+        }
+    });
+}
+
 /// Test propagation of a single sliver of wind.
 #[test]
 fn test_wind_sliver_propagation() {
@@ -231,19 +287,946 @@ fn test_blip_duplicator_inversion_and_blip_movement() {
     });
 }
 
+/// Test that wind flows through linked portals.
+#[test]
+fn test_portal_wind_propagation() {
+    // A wind source, followed by a pair of portals at (3,0) and (6,0). The
+    // pipes between the portals are not connected to them.
+    let m = "
+◉--⊐--⊏--
+";
+
+    test_transform_invariant_3d(&blocks_from_string(m), |t, exec| {
+        for i in 0..20 {
+            exec.update();
+
+            // Up to the first portal, wind flows as usual.
+            for x in 1..=3 {
+                assert_eq!(next_wind_out(exec, t * (x, 0, 0), t * Dir3::X_POS), i >= x);
+            }
+
+            // The second portal gets wind one tick after the first one.
+            for x in 6..=8 {
+                assert_eq!(
+                    next_wind_out(exec, t * (x, 0, 0), t * Dir3::X_POS),
+                    i >= x - 2
+                );
+            }
+
+            // Nothing flows backwards out of the second portal.
+            assert!(!next_wind_out(exec, t * (6, 0, 0), t * Dir3::X_NEG));
+
+            // The pipes behind the portals never get any wind.
+            for x in 4..=5 {
+                for &d in &Dir3::ALL {
+                    assert!(!next_wind_out(exec, t * (x, 0, 0), d));
+                }
+            }
+        }
+    });
+}
+
+/// Test that blips are teleported by linked portals.
+#[test]
+fn test_portal_blip_movement() {
+    // A single blip is spawned and moved into the portal at (3,0). It appears
+    // at the linked portal at (6,0) in the next tick.
+    let m = "
+◉--⊐--⊏--☐
+ ┷
+";
+
+    test_transform_invariant(&blocks_from_string(m), |t, exec| {
+        for i in 0..20 {
+            exec.update();
+
+            // i=0: Blip is spawned at (1,0).
+            // i=2: Blip enters the first portal.
+            // i=3: Blip is teleported to the second portal.
+            // i=5: Blip reaches the end of the pipe, where it is stuck.
+            for x in 1..=3 {
+                assert_eq!(next_blip_index(exec, t * (x, 0, 0)).is_some(), i == x - 1);
+            }
+            for x in 6..=7 {
+                assert_eq!(next_blip_index(exec, t * (x, 0, 0)).is_some(), i == x - 3);
+            }
+            assert_eq!(next_blip_index(exec, t * (8, 0, 0)).is_some(), i >= 5);
+
+            // The blip never shows up between the portals.
+            for x in 4..=5 {
+                assert!(next_blip_index(exec, t * (x, 0, 0)).is_none());
+            }
+        }
+    });
+}
+
+/// Test that portals are linked only if there are exactly two on a channel.
+#[test]
+fn test_portal_needs_unique_partner() {
+    let m = "
+◉--⊐--⊏--⊏
+";
+
+    test_transform_invariant_3d(&blocks_from_string(m), |t, exec| {
+        for _ in 0..20 {
+            exec.update();
+
+            for x in 6..=9 {
+                for &d in &Dir3::ALL {
+                    assert!(!next_wind_out(exec, t * (x, 0, 0), d));
+                }
+            }
+        }
+    });
+}
+
+/// Test that transmitters activate the receivers on their channel.
+#[test]
+fn test_transmitter_receiver_timing() {
+    // A single blip is spawned and moved into the transmitter at (3,0). The
+    // receiver at (3,2) is on the same channel, while the receiver at (3,3)
+    // is on a different channel.
+    let m = "
+◉--T
+ ┷
+   R-
+    -
+";
+    let mut blocks = blocks_from_string(m);
+    blocks.push((
+        Point3::new(3, 3, 0),
+        Block::Receiver {
+            out_dir: Dir3::X_POS,
+            channel: 1,
+        },
+    ));
+
+    test_transform_invariant(&blocks, |t, exec| {
+        let receiver_index = exec.machine().get_index(&(t * (3, 2, 0))).unwrap();
+
+        for i in 0..20 {
+            exec.update();
+
+            // i=2: Blip enters the transmitter.
+            // i=3: Transmitter is activated.
+            // i=4: Receiver is activated and spawns a blip.
+            assert_eq!(exec.blocks().activation[receiver_index].is_some(), i == 4);
+            assert_eq!(next_blip_index(exec, t * (4, 2, 0)).is_some(), i >= 4);
+
+            // The other channel stays silent.
+            assert!(next_blip_index(exec, t * (4, 3, 0)).is_none());
+        }
+    });
+}
+
+/// Test that timers produce blips periodically.
+#[test]
+fn test_timer_period_and_phase() {
+    // A timer at (1,1) with period 3 and phase 1 produces blips into a pipe
+    // that carries them to the right.
+    let m = "
+◉-----
+";
+    let mut blocks = blocks_from_string(m);
+    blocks.push((
+        Point3::new(1, 1, 0),
+        Block::Timer {
+            out_dir: Dir3::Y_NEG,
+            kind: BlipKind::B,
+            period: 3,
+            phase: 1,
+        },
+    ));
+
+    test_transform_invariant(&blocks, |t, exec| {
+        let timer_index = exec.machine().get_index(&(t * (1, 1, 0))).unwrap();
+
+        for i in 0..20 {
+            exec.update();
+
+            let fires = i % 3 == 1;
+            assert_eq!(
+                exec.blocks().activation[timer_index],
+                Some(BlipKind::B).filter(|_| fires)
+            );
+
+            let blip_index = next_blip_index(exec, t * (1, 0, 0));
+            assert_eq!(blip_index.is_some(), fires);
+            if let Some(blip_index) = blip_index {
+                assert_eq!(exec.blips()[blip_index].kind, BlipKind::B);
+            }
+        }
+    });
+}
+
+/// Test that timers do not fire into a position that a blip is about to
+/// enter.
+#[test]
+fn test_timer_blocked_output() {
+    // A single blip is spawned at (1,0) and moves through (2,0), into which
+    // the timer at (2,1) fires in every tick.
+    let m = "
+◉-----
+ ┷
+";
+    let mut blocks = blocks_from_string(m);
+    blocks.push((
+        Point3::new(2, 1, 0),
+        Block::Timer {
+            out_dir: Dir3::Y_NEG,
+            kind: BlipKind::B,
+            period: 1,
+            phase: 0,
+        },
+    ));
+
+    test_transform_invariant(&blocks, |t, exec| {
+        let timer_index = exec.machine().get_index(&(t * (2, 1, 0))).unwrap();
+        let mut stats = Stats::new(exec);
+        let mut num_fired = 0;
+
+        for _ in 0..20 {
+            exec.update();
+            stats.record(exec);
+
+            if exec.blocks().activation[timer_index].is_some() {
+                num_fired += 1;
+            }
+        }
+
+        let num_collisions: usize = stats
+            .blocks
+            .iter()
+            .map(|block_stats| block_stats.killed(BlipDieMode::PopMiddle))
+            .sum();
+
+        // The timer holds back only while the blip passes.
+        assert_eq!(num_collisions, 0);
+        assert!(num_fired > 10 && num_fired < 20);
+    });
+}
+
+/// Test that all transforms map directions the same way as they map the
+/// positions of neighbors, so that connected blocks stay connected.
+#[test]
+fn test_transform_dirs_match_points() {
+    let transforms = [
+        Transform::RotateCWXY,
+        Transform::RotateCCWXY,
+        Transform::RotateCWXZ,
+        Transform::RotateCCWXZ,
+        Transform::RotateCWYZ,
+        Transform::RotateCCWYZ,
+        Transform::MirrorX,
+        Transform::MirrorY,
+        Transform::MirrorZ,
+    ];
+
+    for t in transforms.iter() {
+        for &d in &Dir3::ALL {
+            let p = Point3::new(3, -5, 7);
+            let neighbor = t * (p + d.to_vector());
+
+            assert_eq!(neighbor - t * p, (t * d).to_vector(), "{:?} {:?}", t, d);
+        }
+    }
+}
+
+/// Test that pieces with blocks that only exist in the XY plane can not be
+/// rotated out of it, while other pieces can.
+#[test]
+fn test_xy_only_blocks_keep_xy_plane() {
+    let funnel = Piece::new_origin_block(PlacedBlock {
+        block: Block::FunnelXY {
+            flow_dir: Dir3::X_POS,
+        },
+    });
+    let pipe = Piece::new_origin_block(PlacedBlock {
+        block: Block::Pipe(Dir3::X_NEG, Dir3::X_POS),
+    });
+
+    for t in &[
+        Transform::RotateCWXY,
+        Transform::MirrorX,
+        Transform::MirrorZ,
+        Transform::Seq(vec![Transform::RotateCCWXY, Transform::MirrorY]),
+    ] {
+        assert!(funnel.can_transform(t), "{:?}", t);
+    }
+
+    for t in &[
+        Transform::RotateCWXZ,
+        Transform::RotateCCWYZ,
+        Transform::Seq(vec![Transform::RotateCWXY, Transform::RotateCWYZ]),
+    ] {
+        assert!(!funnel.can_transform(t), "{:?}", t);
+        assert!(pipe.can_transform(t), "{:?}", t);
+    }
+}
+
+/// Test that loading machines fails if they use blocks from mods that are
+/// not available.
+#[test]
+fn test_mod_resolve_errors() {
+    let mods = Mods::load_dir(Path::new("mods")).unwrap();
+
+    match mods.get("missing", "Sink") {
+        Err(mods::Error::MissingMod(mod_name)) => assert_eq!(mod_name, "missing"),
+        result => panic!("unexpected result: {:?}", result),
+    }
+    match mods.get("example", "Missing") {
+        Err(mods::Error::MissingBlock {
+            mod_name,
+            block_name,
+        }) => {
+            assert_eq!(mod_name, "example");
+            assert_eq!(block_name, "Missing");
+        }
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    let mut sink = Custom::new("example", mods.get("example", "Sink").unwrap());
+    let blocks = vec![(
+        Point3::new(0, 0, 0),
+        PlacedBlock {
+            block: Block::Custom(sink.clone()),
+        },
+    )];
+    let machine = Machine::new_from_block_data(&grid::Vector3::new(1, 1, 1), &blocks, &None);
+    let saved_machine = SavedMachine::from_machine(&machine);
+
+    // The mod listed in the machine is missing.
+    let mut missing_mod = saved_machine.clone();
+    missing_mod.mods = vec!["missing".to_string()];
+    match mods.resolve(missing_mod) {
+        Err(mods::Error::MissingMod(mod_name)) => assert_eq!(mod_name, "missing"),
+        result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+
+    // The mod is there, but it does not define the block.
+    sink.block_name = "Missing".to_string();
+    let mut missing_block = saved_machine.clone();
+    missing_block.block_data[0].1.block = Block::Custom(sink);
+    match mods.resolve(missing_block) {
+        Err(mods::Error::MissingBlock { block_name, .. }) => assert_eq!(block_name, "Missing"),
+        result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+
+    // Loading without resolving fails as well, instead of leaving the block
+    // without a definition.
+    match saved_machine.clone().into_machine() {
+        Err(mods::Error::MissingMod(mod_name)) => assert_eq!(mod_name, "example"),
+        result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+
+    assert!(mods.resolve(saved_machine).is_ok());
+}
+
+/// Test that custom blocks from `mods/example.json` conduct wind, spawn blips
+/// and kill blips after being saved and resolved again.
+#[test]
+fn test_mod_blocks_exec() {
+    let mods = Mods::load_dir(Path::new("mods")).unwrap();
+    let custom = |block_name: &str| {
+        Block::Custom(Custom::new(
+            "example",
+            mods.get("example", block_name).unwrap(),
+        ))
+    };
+
+    // The custom block definitions are not saved, so they need to be looked
+    // up again when loading.
+    let resolve = |m: &str, customs: Vec<(Point3, Block)>| {
+        let blocks: Vec<_> = blocks_from_string(m)
+            .into_iter()
+            .chain(customs)
+            .map(|(pos, block)| (pos, PlacedBlock { block }))
+            .collect();
+        let machine = Machine::new_from_block_data(&grid::Vector3::new(4, 3, 1), &blocks, &None);
+        let json = serde_json::to_string(&SavedMachine::from_machine(&machine)).unwrap();
+
+        mods.resolve(serde_json::from_str(&json).unwrap()).unwrap()
+    };
+
+    // A single blip is spawned at (1,0) and moved into the sink at (2,0),
+    // which lets the wind pass to (3,0).
+    let m = "
+◉- -
+ ┷
+";
+    let machine = resolve(m, vec![(Point3::new(2, 0, 0), custom("Sink"))]);
+    let mut exec = Exec::new(machine, &mut rand::thread_rng());
+    let mut stats = Stats::new(&exec);
+
+    for i in 0..20 {
+        exec.update();
+        stats.record(&exec);
+
+        assert_eq!(
+            next_wind_out(&exec, Point3::new(2, 0, 0), Dir3::X_POS),
+            i >= 2
+        );
+        assert_eq!(
+            next_wind_out(&exec, Point3::new(3, 0, 0), Dir3::X_POS),
+            i >= 3
+        );
+        assert!(next_blip_index(&exec, Point3::new(3, 0, 0)).is_none());
+    }
+
+    let sink_index = exec.machine().get_index(&Point3::new(2, 0, 0)).unwrap();
+    assert_eq!(stats.blocks[sink_index].killed(BlipDieMode::PopMiddle), 1);
+
+    // A single blip is moved into the button of the splitter at (2,1), which
+    // then spawns blips up and down.
+    let m = "
+  |
+◉-
+ ┷|
+";
+    let machine = resolve(m, vec![(Point3::new(2, 1, 0), custom("Splitter"))]);
+    let mut exec = Exec::new(machine, &mut rand::thread_rng());
+    let mut spawned = (false, false);
+
+    for _ in 0..20 {
+        exec.update();
+
+        spawned.0 |= next_blip_index(&exec, Point3::new(2, 0, 0)).is_some();
+        spawned.1 |= next_blip_index(&exec, Point3::new(2, 2, 0)).is_some();
+    }
+
+    assert_eq!(spawned, (true, true));
+}
+
+/// Test that the VCD export declares signals for each block and records the
+/// wind as it starts flowing.
+#[test]
+fn test_vcd_export() {
+    let m = "
+◉--
+";
+
+    let machine = machine_from_string(m);
+
+    let mut exec = Exec::new(machine, &mut rand::thread_rng());
+    let mut recorder = vcd::Recorder::new_all_blocks(&exec);
+    recorder.record(&exec);
+
+    for _ in 0..5 {
+        exec.update();
+        recorder.record(&exec);
+    }
+
+    let mut output = Vec::new();
+    recorder.write(&mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+
+    assert_eq!(output.matches("$var wire").count(), 6);
+    assert!(output.contains("$scope module block_2_0_0 $end"));
+    assert!(output.contains("$enddefinitions $end"));
+    assert!(output.contains("#0\n$dumpvars\n"));
+
+    // Wind starts flowing through the pipes after the first tick.
+    assert!(output
+        .lines()
+        .any(|line| line.starts_with('#') && line != "#0"));
+}
+
+/// Test that the VCD export keeps only the latest changes, merging older ones
+/// into the initial values.
+#[test]
+fn test_vcd_export_max_change_ticks() {
+    // The timer changes its activation in every tick.
+    let m = "
+◉-----
+";
+    let mut machine = machine_from_string(m);
+    machine.set(
+        &Point3::new(1, 1, 0),
+        Some(PlacedBlock {
+            block: Block::Timer {
+                out_dir: Dir3::Y_NEG,
+                kind: BlipKind::A,
+                period: 2,
+                phase: 0,
+            },
+        }),
+    );
+
+    let mut exec = Exec::new(machine, &mut rand::thread_rng());
+    let mut full = vcd::Recorder::new_all_blocks(&exec);
+    let mut capped = full.clone();
+    capped.set_max_change_ticks(3);
+
+    full.record(&exec);
+    capped.record(&exec);
+    for _ in 0..20 {
+        exec.update();
+        full.record(&exec);
+        capped.record(&exec);
+    }
+
+    let write = |recorder: &vcd::Recorder| {
+        let mut output = Vec::new();
+        recorder.write(&mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    };
+    let full = write(&full);
+    let capped = write(&capped);
+
+    assert!(!full.contains("$comment Dropped"));
+    assert!(capped.contains("$comment Dropped"));
+    assert_eq!(
+        capped.lines().filter(|line| line.starts_with('#')).count(),
+        4
+    );
+
+    // The last ticks are written in the same way.
+    let last_tick = |output: &str| {
+        let lines: Vec<&str> = output.lines().collect();
+        let start = lines
+            .iter()
+            .rposition(|line| line.starts_with('#'))
+            .unwrap();
+        lines[start..].join("\n")
+    };
+    assert_eq!(last_tick(&full), last_tick(&capped));
+}
+
+/// Test that block statistics count the ticks in which blips are in pipes.
+#[test]
+fn test_stats_blip_ticks() {
+    let m = "
+◉--⊐--⊏--☐
+ ┷
+";
+
+    test_transform_invariant(&blocks_from_string(m), |t, exec| {
+        let mut stats = Stats::new(exec);
+
+        for _ in 0..20 {
+            exec.update();
+            stats.record(exec);
+        }
+
+        let block_stats = |p: Point3| &stats.blocks[exec.machine().get_index(&p).unwrap()];
+
+        // The blip passes the pipes before the portal exactly once, and
+        // never shows up between the portals.
+        assert_eq!(block_stats(t * (2, 0, 0)).blips, 1);
+        assert_eq!(block_stats(t * (4, 0, 0)).blips, 0);
+        assert_eq!(block_stats(t * (5, 0, 0)).blips, 0);
+    });
+}
+
+/// Test that block statistics blame collisions on the block that the
+/// colliding blips were about to enter.
+#[test]
+fn test_stats_killed_by_collision() {
+    // Two blips are spawned at (1,2) and (2,1). They are blown into the merge
+    // at (2,2) in the same tick, where they collide.
+    let m = "
+  ◉
+ ┠|
+◉-┼
+ ┷
+";
+
+    test_transform_invariant(&blocks_from_string(m), |t, exec| {
+        let mut stats = Stats::new(exec);
+
+        for _ in 0..20 {
+            exec.update();
+            stats.record(exec);
+        }
+
+        let merge_index = exec.machine().get_index(&(t * (2, 2, 0))).unwrap();
+        let total_killed: usize = stats
+            .blocks
+            .iter()
+            .map(|block_stats| block_stats.killed(BlipDieMode::PopMiddle))
+            .sum();
+
+        assert_eq!(stats.blocks[merge_index].killed(BlipDieMode::PopMiddle), 2);
+        assert_eq!(total_killed, 2);
+    });
+}
+
+/// Test that a level fails with a reason once nothing happens in the machine
+/// anymore.
+#[test]
+fn test_level_stalled() {
+    let level = Level {
+        size: grid::Vector3::new(9, 9, 1),
+        spec: Spec::Clock {
+            pattern: vec![BlipKind::A, BlipKind::B],
+        },
+        resizable: false,
+    };
+    let mut exec = Exec::new(Machine::new_from_level(level), &mut rand::thread_rng());
+
+    for _ in 0..5 {
+        exec.update();
+    }
+    assert_eq!(
+        exec.next_level_progress().unwrap().status(),
+        LevelStatus::Running
+    );
+
+    for _ in 0..50 {
+        exec.update();
+    }
+
+    let progress = exec.next_level_progress().unwrap();
+    assert_eq!(progress.status(), LevelStatus::Failed);
+    assert!(match progress.failure {
+        Some(FailureReason::Stalled { .. }) => true,
+        _ => false,
+    });
+    assert_eq!(progress.failed_output(), Some(0));
+}
+
+/// Test that a level fails once an output receives a blip of the wrong kind,
+/// blaming that output.
+#[test]
+fn test_level_wrong_output() {
+    // The timer feeds B into the output in every tick, but only the first blip
+    // matches the pattern.
+    let mut exec = clock_level_fed_by_timer(vec![BlipKind::B, BlipKind::A]);
+
+    let first_tick = update_until_output_fed(&mut exec);
+    exec.update();
+
+    let progress = exec.next_level_progress().unwrap();
+    assert_eq!(progress.status(), LevelStatus::Failed);
+    assert_eq!(
+        progress.failure,
+        Some(FailureReason::WrongOutput {
+            index: 0,
+            tick: first_tick + 1,
+            expected: BlipKind::A,
+            actual: BlipKind::B,
+        })
+    );
+    assert_eq!(progress.failed_output(), Some(0));
+    assert_eq!(progress.outputs[0].num_fed, 1);
+}
+
+/// Test that a level fails once an output receives a blip after the expected
+/// sequence, blaming that output.
+#[test]
+fn test_level_too_many_outputs() {
+    // The clock level expects ten times the pattern, but the timer keeps on
+    // firing.
+    let mut exec = clock_level_fed_by_timer(vec![BlipKind::B]);
+
+    let first_tick = update_until_output_fed(&mut exec);
+    for _ in 1..10 {
+        exec.update();
+    }
+    assert_eq!(
+        exec.next_level_progress().unwrap().status(),
+        LevelStatus::Completed
+    );
+
+    exec.update();
+
+    let progress = exec.next_level_progress().unwrap();
+    assert_eq!(progress.status(), LevelStatus::Failed);
+    assert_eq!(
+        progress.failure,
+        Some(FailureReason::TooManyOutputs {
+            index: 0,
+            tick: first_tick + 10,
+            actual: BlipKind::B,
+        })
+    );
+    assert_eq!(progress.failed_output(), Some(0));
+    assert_eq!(progress.outputs[0].num_fed, 10);
+}
+
+/// Test that a batch evaluates every seed and aggregates the failures.
+#[test]
+fn test_batch_all_seeds_evaluated() {
+    let level = Level {
+        size: grid::Vector3::new(9, 9, 1),
+        spec: Spec::Clock {
+            pattern: vec![BlipKind::A, BlipKind::B],
+        },
+        resizable: false,
+    };
+    let config = batch::Config {
+        num_seeds: 5,
+        first_seed: 10,
+        num_threads: 2,
+        max_ticks: 100,
+    };
+
+    let summary = Batch::spawn(&Machine::new_from_level(level), &config).wait();
+
+    let seeds: Vec<u64> = summary.results.iter().map(|result| result.seed).collect();
+    assert_eq!(seeds, vec![10, 11, 12, 13, 14]);
+
+    // The empty machine never produces any output.
+    assert_eq!(summary.count(LevelStatus::Failed), 5);
+    assert_eq!(summary.failures().count(), 5);
+    assert!(!summary.is_success());
+    assert_eq!(summary.completed_ticks(), None);
+}
+
+/// Test that the state of a machine repeats once its wind has settled.
+#[test]
+fn test_cycle_detection() {
+    let m = "
+◉---
+";
+
+    test_transform_invariant(&blocks_from_string(m), |_, exec| {
+        let mut detector = CycleDetector::new(exec);
+        detector.record(exec);
+
+        for _ in 0..3 {
+            exec.update();
+            assert_eq!(detector.record(exec), None);
+        }
+
+        for _ in 0..10 {
+            exec.update();
+            detector.record(exec);
+        }
+
+        let cycle = detector.cycle().unwrap();
+        assert_eq!(cycle.period, 1);
+        assert!(cycle.start >= 3);
+    });
+}
+
+/// Test that the cycle detector finds the period of a timer that keeps on
+/// producing blips, even though it only keeps checkpoints of the state.
+#[test]
+fn test_cycle_detection_timer() {
+    let m = "
+◉-----
+";
+    let mut blocks = blocks_from_string(m);
+    blocks.push((
+        Point3::new(1, 1, 0),
+        Block::Timer {
+            out_dir: Dir3::Y_NEG,
+            kind: BlipKind::B,
+            period: 5,
+            phase: 2,
+        },
+    ));
+
+    test_transform_invariant(&blocks, |_, exec| {
+        let mut detector = CycleDetector::new(exec);
+        detector.record(exec);
+
+        for _ in 0..100 {
+            exec.update();
+            detector.record(exec);
+        }
+
+        let cycle = detector.cycle().unwrap();
+        assert_eq!(cycle.period, 5);
+    });
+}
+
+/// Test that updating only the blocks in which something may change gives
+/// exactly the same results as the non-incremental mode of `Exec`, which
+/// updates all blocks in every tick, for all of our test machines.
+#[test]
+fn test_incremental_update_equivalence() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_machines");
+
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        let file = File::open(&path).unwrap();
+        let saved_machine: SavedMachine = serde_json::from_reader(BufReader::new(file))
+            .unwrap_or_else(|err| panic!("Failed to load {}: {}", path.display(), err));
+
+        let mut incremental = Exec::new(
+            saved_machine.into_machine().unwrap(),
+            &mut rand::thread_rng(),
+        );
+        let mut full = incremental.clone();
+        full.set_incremental(false);
+
+        for _ in 0..300 {
+            incremental.update();
+            full.update();
+
+            let tick = full.cur_tick();
+            let msg = format!("{} in tick {}", path.display(), tick);
+
+            assert_eq!(incremental.blips(), full.blips(), "{}", msg);
+            assert_eq!(
+                incremental.blocks().wind_out,
+                full.blocks().wind_out,
+                "{}",
+                msg
+            );
+            assert_eq!(
+                incremental.blocks().activation,
+                full.blocks().activation,
+                "{}",
+                msg
+            );
+            assert_eq!(
+                incremental.next_blocks().wind_out,
+                full.next_blocks().wind_out,
+                "{}",
+                msg
+            );
+            assert_eq!(
+                incremental.next_blocks().activation,
+                full.next_blocks().activation,
+                "{}",
+                msg
+            );
+            assert_eq!(
+                incremental.prev_activation(),
+                full.prev_activation(),
+                "{}",
+                msg
+            );
+            assert_eq!(
+                format!("{:?}", incremental.next_level_progress()),
+                format!("{:?}", full.next_level_progress()),
+                "{}",
+                msg
+            );
+
+            for ((_, (_, a)), (_, (_, b))) in incremental
+                .machine()
+                .iter_blocks()
+                .zip(full.machine().iter_blocks())
+            {
+                assert_eq!(a.block, b.block, "{}", msg);
+            }
+        }
+    }
+}
+
+/// Test that packed wind keeps the masks of blocks apart, also across word
+/// boundaries.
+#[test]
+fn test_wind_state_packing() {
+    let num_blocks = 25;
+    let mask = |block_index: usize| WindMask::from_dir(Dir3::ALL[block_index % Dir3::ALL.len()]);
+
+    let mut state = WindState::new(num_blocks);
+    for block_index in 0..num_blocks {
+        state.set(block_index, WindMask::ALL);
+        state.set(block_index, mask(block_index));
+    }
+
+    for block_index in 0..num_blocks {
+        assert_eq!(state.get(block_index), mask(block_index));
+    }
+
+    assert_eq!(!mask(0) & mask(0), WindMask::NONE);
+    assert_eq!(!WindMask::NONE, WindMask::ALL);
+}
+
+/// Returns an execution of a clock level, whose single output at (3,0) is fed
+/// by a timer that fires a B blip in every tick.
+fn clock_level_fed_by_timer(pattern: Vec<BlipKind>) -> Exec {
+    let level = Level {
+        size: grid::Vector3::new(4, 1, 1),
+        spec: Spec::Clock { pattern },
+        resizable: false,
+    };
+    let mut machine = Machine::new_from_level(level);
+    machine.set(
+        &Point3::new(2, 0, 0),
+        Some(PlacedBlock {
+            block: Block::Timer {
+                out_dir: Dir3::X_POS,
+                kind: BlipKind::B,
+                period: 1,
+                phase: 0,
+            },
+        }),
+    );
+
+    Exec::new(machine, &mut rand::thread_rng())
+}
+
+/// Updates until the first output has received its first blip, and returns
+/// the tick in which that happened.
+fn update_until_output_fed(exec: &mut Exec) -> TickNum {
+    for _ in 0..10 {
+        exec.update();
+
+        let progress = exec.next_level_progress().unwrap();
+        if progress.outputs[0].num_fed > 0 {
+            assert_eq!(progress.outputs[0].num_fed, 1);
+            assert_eq!(progress.status(), LevelStatus::Running);
+
+            // `Exec::update` advances the tick after updating the outputs.
+            return exec.cur_tick() - 1;
+        }
+    }
+
+    panic!("Output was never fed");
+}
+
 fn next_wind_out(exec: &Exec, p: Point3, d: Dir3) -> bool {
     let block_index = exec.machine().get_index(&p).unwrap();
-    exec.next_blocks().wind_out[block_index][d]
+    exec.next_blocks().wind_out.get(block_index).contains(d)
 }
 
 fn next_blip_index(exec: &Exec, p: Point3) -> Option<usize> {
     exec.blips()
         .iter()
-        .find(|(_, blip)| blip.next_pos() == p)
+        .find(|(_, blip)| blip.next_pos(exec.machine(), exec.neighbor_map()) == p)
         .map(|(next_blip_index, _)| next_blip_index)
 }
 
+/// Runs `test` on randomly transformed copies of `blocks`, using only
+/// transforms that keep the XY plane in place. This is needed for machines
+/// with blips, which fall down, and for blocks that only exist in the XY
+/// plane.
 fn test_transform_invariant<T>(blocks: &[(Point3, Block)], test: T)
+where
+    T: for<'a> Fn(&'a Transform, &'a mut Exec),
+{
+    test_transform_invariant_with(
+        blocks,
+        &[
+            Transform::RotateCWXY,
+            Transform::RotateCCWXY,
+            Transform::MirrorY,
+        ],
+        test,
+    );
+}
+
+/// Runs `test` on randomly transformed copies of `blocks`, using all
+/// transforms, including those that rotate the machine out of the XY plane.
+fn test_transform_invariant_3d<T>(blocks: &[(Point3, Block)], test: T)
+where
+    T: for<'a> Fn(&'a Transform, &'a mut Exec),
+{
+    test_transform_invariant_with(
+        blocks,
+        &[
+            Transform::RotateCWXY,
+            Transform::RotateCCWXY,
+            Transform::RotateCWXZ,
+            Transform::RotateCCWXZ,
+            Transform::RotateCWYZ,
+            Transform::RotateCCWYZ,
+            Transform::MirrorX,
+            Transform::MirrorY,
+            Transform::MirrorZ,
+        ],
+        test,
+    );
+}
+
+fn test_transform_invariant_with<T>(blocks: &[(Point3, Block)], choices: &[Transform], test: T)
 where
     T: for<'a> Fn(&'a Transform, &'a mut Exec),
 {
@@ -261,7 +1244,7 @@ where
     let piece = Piece::new(blocks);
 
     for _ in 0..1000 {
-        let mut transform = random_transform();
+        let mut transform = random_transform(choices);
         let mut transformed_piece = piece.clone();
         transformed_piece.transform(&transform);
 
@@ -280,7 +1263,7 @@ where
     }
 }
 
-fn random_transform() -> Transform {
+fn random_transform(choices: &[Transform]) -> Transform {
     const MAX_TRANSFORMS: usize = 5;
     const MAX_SHIFT_XY: isize = 100;
     const MAX_SHIFT_Z: isize = 3;
@@ -288,18 +1271,19 @@ fn random_transform() -> Transform {
     let mut rng = rand::thread_rng();
 
     let transforms = (0..rng.gen_range(0, MAX_TRANSFORMS))
-        .map(|_| match rng.gen_range(0, 4) {
-            0 => {
+        .map(|_| {
+            // Shifts are as likely as any other single transform.
+            let i = rng.gen_range(0, choices.len() + 1);
+
+            if i == choices.len() {
                 let x = rng.gen_range(-MAX_SHIFT_XY, MAX_SHIFT_XY);
                 let y = rng.gen_range(-MAX_SHIFT_XY, MAX_SHIFT_XY);
                 let z = rng.gen_range(-MAX_SHIFT_Z, MAX_SHIFT_Z);
 
                 Transform::Shift(grid::Vector3::new(x, y, z))
+            } else {
+                choices[i].clone()
             }
-            1 => Transform::RotateCWXY,
-            2 => Transform::RotateCCWXY,
-            3 => Transform::MirrorY,
-            _ => unreachable!(),
         })
         .collect();
 
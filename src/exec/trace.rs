@@ -0,0 +1,71 @@
+use std::collections::VecDeque;
+
+use crate::exec::{Activation, Exec};
+use crate::machine::grid::Point3;
+use crate::machine::{BlipKind, TickNum};
+
+/// State of a traced block in one tick.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default)]
+pub struct Sample {
+    pub activation: Activation,
+
+    /// Kind of a blip that is in the block, if any.
+    pub blip: Option<BlipKind>,
+}
+
+/// Records the state of a set of blocks over the most recent ticks of an
+/// execution, like the probes of a logic analyzer.
+#[derive(Clone, Debug)]
+pub struct Trace {
+    /// Positions of the traced blocks.
+    pub positions: Vec<Point3>,
+
+    /// For each recorded tick, one sample per traced block. Older ticks are
+    /// dropped once there are more than `max_ticks`.
+    pub ticks: VecDeque<(TickNum, Vec<Sample>)>,
+
+    max_ticks: usize,
+}
+
+impl Trace {
+    pub fn new(positions: Vec<Point3>, max_ticks: usize) -> Self {
+        Self {
+            positions,
+            ticks: VecDeque::new(),
+            max_ticks,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Records the current state of the traced blocks.
+    pub fn record(&mut self, exec: &Exec) {
+        if self.is_empty() {
+            return;
+        }
+
+        let samples = self.positions.iter().map(|pos| sample(exec, pos)).collect();
+
+        self.ticks.push_back((exec.cur_tick(), samples));
+
+        while self.ticks.len() > self.max_ticks {
+            self.ticks.pop_front();
+        }
+    }
+}
+
+fn sample(exec: &Exec, pos: &Point3) -> Sample {
+    let activation = exec
+        .machine()
+        .get_index(pos)
+        .and_then(|block_index| exec.blocks().activation[block_index]);
+    let blip = exec
+        .blips()
+        .iter()
+        .find(|(_, blip)| blip.pos == *pos)
+        .map(|(_, blip)| blip.kind);
+
+    Sample { activation, blip }
+}
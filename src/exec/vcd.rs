@@ -0,0 +1,271 @@
+//! Export of execution signals in the Value Change Dump (VCD) format, so that
+//! runs can be inspected in waveform viewers like GTKWave.
+//!
+//! Each tick corresponds to one time unit of the dump.
+
+use std::collections::VecDeque;
+use std::io::{self, Write};
+
+use crate::exec::{Activation, Exec};
+use crate::machine::grid::{Dir3, Point3};
+use crate::machine::{BlipKind, Block, BlockIndex, TickNum};
+
+/// Maximal number of ticks with changes that are kept. Older changes are
+/// merged into the initial values, so that long runs do not use up all the
+/// memory.
+const MAX_CHANGE_TICKS: usize = 100_000;
+
+#[derive(Debug, Clone)]
+enum Source {
+    /// Activation of a block, encoded as 0 (none), 1 (A) or 2 (B).
+    Activation(BlockIndex),
+
+    /// Wind flowing out of a block, one bit per direction in the order of
+    /// `Dir3::ALL`, just like in `WindMask`.
+    Wind(BlockIndex),
+}
+
+impl Source {
+    fn width(&self) -> usize {
+        match self {
+            Source::Activation(_) => 2,
+            Source::Wind(_) => Dir3::ALL.len(),
+        }
+    }
+
+    fn value(&self, exec: &Exec) -> u32 {
+        match self {
+            Source::Activation(block_index) => {
+                activation_value(exec.blocks().activation[*block_index])
+            }
+            Source::Wind(block_index) => exec.blocks().wind_out.get(*block_index).bits().into(),
+        }
+    }
+}
+
+fn activation_value(activation: Activation) -> u32 {
+    match activation {
+        None => 0,
+        Some(BlipKind::A) => 1,
+        Some(BlipKind::B) => 2,
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Signal {
+    scope: String,
+    name: String,
+    source: Source,
+}
+
+/// Records the changes of block signals while executing, and writes them as
+/// a VCD file.
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    signals: Vec<Signal>,
+
+    /// Values of the signals in the last recorded tick.
+    values: Option<Vec<u32>>,
+
+    /// Changed signals with their new value, for each tick in which
+    /// something changed. The first entry contains the values of all
+    /// signals.
+    changes: VecDeque<(TickNum, Vec<(usize, u32)>)>,
+
+    /// Number of ticks with changes that have been merged into the first
+    /// entry of `changes`.
+    num_dropped: usize,
+
+    max_change_ticks: usize,
+}
+
+impl Recorder {
+    /// Creates a recorder for the activation and wind of the blocks at the
+    /// given positions. Inputs and outputs of the machine are always
+    /// included, so that their events can be seen.
+    pub fn new(exec: &Exec, positions: &[Point3]) -> Self {
+        let machine = exec.machine();
+        let mut signals = Vec::new();
+
+        for (block_index, (_, placed_block)) in machine.iter_blocks() {
+            let name = match placed_block.block {
+                Block::Input { index, .. } => format!("input_{}", index),
+                Block::Output { index, .. } => format!("output_{}", index),
+                _ => continue,
+            };
+
+            signals.push(Signal {
+                scope: "io".to_string(),
+                name,
+                source: Source::Activation(block_index),
+            });
+        }
+
+        for pos in positions {
+            if let Some((block_index, _)) = machine.get_with_index(pos) {
+                let scope = format!("block_{}_{}_{}", pos.x, pos.y, pos.z);
+
+                signals.push(Signal {
+                    scope: scope.clone(),
+                    name: "activation".to_string(),
+                    source: Source::Activation(block_index),
+                });
+                signals.push(Signal {
+                    scope,
+                    name: "wind".to_string(),
+                    source: Source::Wind(block_index),
+                });
+            }
+        }
+
+        Self {
+            signals,
+            values: None,
+            changes: VecDeque::new(),
+            num_dropped: 0,
+            max_change_ticks: MAX_CHANGE_TICKS,
+        }
+    }
+
+    /// Creates a recorder for all blocks of the machine, except for air.
+    pub fn new_all_blocks(exec: &Exec) -> Self {
+        let positions: Vec<Point3> = exec
+            .machine()
+            .iter_blocks()
+            .filter(|(_, (_, placed_block))| placed_block.block != Block::Air)
+            .map(|(_, (pos, _))| *pos)
+            .collect();
+
+        Self::new(exec, &positions)
+    }
+
+    /// Records the signals in the current tick of `exec`.
+    pub fn record(&mut self, exec: &Exec) {
+        let values: Vec<u32> = self
+            .signals
+            .iter()
+            .map(|signal| signal.source.value(exec))
+            .collect();
+
+        let changed: Vec<(usize, u32)> = match self.values.as_ref() {
+            Some(prev_values) => values
+                .iter()
+                .zip(prev_values.iter())
+                .enumerate()
+                .filter(|(_, (value, prev_value))| value != prev_value)
+                .map(|(index, (value, _))| (index, *value))
+                .collect(),
+            None => values.iter().copied().enumerate().collect(),
+        };
+
+        if !changed.is_empty() {
+            self.changes.push_back((exec.cur_tick(), changed));
+        }
+
+        if self.changes.len() > self.max_change_ticks + 1 {
+            let (tick, changed) = self.changes.remove(1).unwrap();
+            let (first_tick, first_values) = &mut self.changes[0];
+
+            *first_tick = tick;
+            for (index, value) in changed {
+                first_values[index].1 = value;
+            }
+
+            self.num_dropped += 1;
+        }
+
+        self.values = Some(values);
+    }
+
+    /// Sets how many ticks with changes are kept, so that tests can check
+    /// the merging of older changes without running for long.
+    #[cfg(test)]
+    pub fn set_max_change_ticks(&mut self, max_change_ticks: usize) {
+        self.max_change_ticks = max_change_ticks;
+    }
+
+    pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "$version Ultimate Scale $end")?;
+        writeln!(w, "$comment One time unit per tick $end")?;
+        writeln!(w, "$timescale 1 s $end")?;
+        if self.num_dropped > 0 {
+            writeln!(
+                w,
+                "$comment Dropped the first {} ticks with changes $end",
+                self.num_dropped
+            )?;
+        }
+        writeln!(w, "$scope module machine $end")?;
+
+        let mut cur_scope: Option<&str> = None;
+        for (index, signal) in self.signals.iter().enumerate() {
+            if cur_scope != Some(signal.scope.as_str()) {
+                if cur_scope.is_some() {
+                    writeln!(w, "$upscope $end")?;
+                }
+                writeln!(w, "$scope module {} $end", signal.scope)?;
+                cur_scope = Some(signal.scope.as_str());
+            }
+
+            writeln!(
+                w,
+                "$var wire {} {} {} $end",
+                signal.source.width(),
+                identifier(index),
+                signal.name
+            )?;
+        }
+        if cur_scope.is_some() {
+            writeln!(w, "$upscope $end")?;
+        }
+
+        writeln!(w, "$upscope $end")?;
+        writeln!(w, "$enddefinitions $end")?;
+
+        for (i, (tick, changed)) in self.changes.iter().enumerate() {
+            writeln!(w, "#{}", tick)?;
+
+            // The first entry contains the values of all signals, possibly
+            // with older changes merged into it.
+            if i == 0 {
+                writeln!(w, "$dumpvars")?;
+            }
+
+            for &(index, value) in changed {
+                let width = self.signals[index].source.width();
+                writeln!(
+                    w,
+                    "b{:0width$b} {}",
+                    value,
+                    identifier(index),
+                    width = width
+                )?;
+            }
+
+            if i == 0 {
+                writeln!(w, "$end")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the short identifier code of the signal with the given index,
+/// using the printable ASCII characters from `!` to `~`.
+fn identifier(mut index: usize) -> String {
+    let first = b'!';
+    let num_chars = (b'~' - first + 1) as usize;
+
+    let mut id = String::new();
+    loop {
+        id.push((first + (index % num_chars) as u8) as char);
+        index /= num_chars;
+
+        if index == 0 {
+            return id;
+        }
+
+        index -= 1;
+    }
+}
@@ -0,0 +1,102 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+use crate::exec::{Exec, LevelProgress, LevelStatus};
+
+use super::Recording;
+
+/// Runs an execution on a worker thread without showing it, until the level
+/// is completed or failed. Only cycle detection is recorded meanwhile.
+pub struct Finish {
+    max_ticks: usize,
+
+    /// Total number of outputs that the level expects.
+    num_outputs: usize,
+
+    num_ticks: Arc<AtomicUsize>,
+    num_outputs_fed: Arc<AtomicUsize>,
+    cancel: Arc<AtomicBool>,
+
+    result_recv: mpsc::Receiver<(Exec, Recording)>,
+}
+
+fn count_outputs_fed(progress: &LevelProgress) -> usize {
+    progress.outputs.iter().map(|output| output.num_fed).sum()
+}
+
+impl Finish {
+    pub(super) fn spawn(mut exec: Exec, mut recording: Recording, max_ticks: usize) -> Self {
+        let num_outputs = exec.next_level_progress().map_or(0, |progress| {
+            progress.inputs_outputs.outputs.iter().map(Vec::len).sum()
+        });
+
+        let num_ticks = Arc::new(AtomicUsize::new(0));
+        let num_outputs_fed = Arc::new(AtomicUsize::new(0));
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (result_send, result_recv) = mpsc::channel();
+
+        {
+            let num_ticks = num_ticks.clone();
+            let num_outputs_fed = num_outputs_fed.clone();
+            let cancel = cancel.clone();
+
+            thread::spawn(move || {
+                let end_tick = exec.cur_tick() + max_ticks;
+
+                while exec.cur_tick() < end_tick && !cancel.load(Ordering::Relaxed) {
+                    exec.update();
+
+                    // The other recordings look at every block in every
+                    // tick, which would slow us down a lot. They continue
+                    // once the execution is shown again.
+                    recording.cycle_detector.record(&exec);
+
+                    num_ticks.fetch_add(1, Ordering::Relaxed);
+
+                    if let Some(progress) = exec.next_level_progress() {
+                        num_outputs_fed.store(count_outputs_fed(progress), Ordering::Relaxed);
+
+                        if progress.status() != LevelStatus::Running {
+                            break;
+                        }
+                    }
+                }
+
+                // The receiver is gone if execution was stopped in the
+                // meantime, so we ignore errors here.
+                let _ = result_send.send((exec, recording));
+            });
+        }
+
+        Self {
+            max_ticks,
+            num_outputs,
+            num_ticks,
+            num_outputs_fed,
+            cancel,
+            result_recv,
+        }
+    }
+
+    /// Returns how far along we are, between 0 and 1. For levels, this is
+    /// measured by the outputs that have been produced.
+    pub fn progress(&self) -> f32 {
+        if self.num_outputs > 0 {
+            self.num_outputs_fed.load(Ordering::Relaxed) as f32 / self.num_outputs as f32
+        } else {
+            self.num_ticks.load(Ordering::Relaxed) as f32 / self.max_ticks as f32
+        }
+    }
+
+    /// Returns the execution and its recording once finishing is done.
+    pub(super) fn try_take(&mut self) -> Option<(Exec, Recording)> {
+        self.result_recv.try_recv().ok()
+    }
+}
+
+impl Drop for Finish {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
@@ -1,9 +1,14 @@
 mod blip_anim;
 mod event;
+mod finish;
 
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
 use std::time::Duration;
 
 use coarse_prof::profile;
+use log::{info, warn};
 use nalgebra as na;
 
 use glium::glutin::{self, WindowEvent};
@@ -14,18 +19,34 @@ use rendology::{basic_obj, BasicObj, Camera, Light, RenderList};
 use crate::edit::pick;
 use crate::edit_camera_view::EditCameraView;
 use crate::exec::anim::{AnimState, WindDeadend, WindLife};
+use crate::exec::cycle::{Cycle, CycleDetector};
+use crate::exec::stats::{Heatmap, Stats};
+use crate::exec::trace::Trace;
+use crate::exec::vcd;
 use crate::exec::{Blip, BlipStatus, Exec, LevelProgress, LevelStatus, TickTime};
 use crate::input_state::InputState;
 use crate::machine::grid::{Dir3, Point3};
-use crate::machine::{grid, BlipKind, Machine};
+use crate::machine::{grid, BlipKind, Block, Machine, TickNum};
 use crate::render;
+use crate::render::layers::Layers;
 
 use event::TransduceEvent;
+use finish::Finish;
+
+/// Number of ticks for which the activations of pinned blocks are kept.
+const TRACE_TICKS: usize = 64;
 
 #[derive(Debug, Clone)]
 pub struct Config {
     particle_budget_per_tick: usize,
     close_particle_budget_fraction: f32,
+
+    /// File to which signals are written when exporting a VCD dump.
+    vcd_path: PathBuf,
+
+    /// Maximal number of ticks to run for when finishing execution in the
+    /// background.
+    max_finish_ticks: usize,
 }
 
 impl Default for Config {
@@ -33,6 +54,8 @@ impl Default for Config {
         Self {
             particle_budget_per_tick: 500_000,
             close_particle_budget_fraction: 0.3,
+            vcd_path: PathBuf::from("trace.vcd"),
+            max_finish_ticks: 1_000_000,
         }
     }
 }
@@ -43,6 +66,48 @@ impl Config {
     }
 }
 
+/// Everything that is recorded about the execution in each tick that is
+/// shown. Ticks that are finished in the background only go into the cycle
+/// detector.
+#[derive(Clone)]
+struct Recording {
+    /// Recent activations of the blocks that have been pinned in the editor.
+    trace: Trace,
+
+    /// Signals of the pinned blocks and of the inputs and outputs since the
+    /// start of execution, for exporting.
+    vcd: vcd::Recorder,
+
+    /// Activity of each block since the start of execution.
+    stats: Stats,
+
+    /// Finds out when the execution starts repeating itself.
+    cycle_detector: CycleDetector,
+}
+
+impl Recording {
+    fn new(exec: &Exec, pinned: &[grid::Point3]) -> Self {
+        let mut recording = Self {
+            trace: Trace::new(pinned.to_vec(), TRACE_TICKS),
+            vcd: vcd::Recorder::new(exec, pinned),
+            stats: Stats::new(exec),
+            cycle_detector: CycleDetector::new(exec),
+        };
+
+        recording.vcd.record(exec);
+        recording.cycle_detector.record(exec);
+
+        recording
+    }
+
+    fn record(&mut self, exec: &Exec) {
+        self.trace.record(exec);
+        self.vcd.record(exec);
+        self.stats.record(exec);
+        self.cycle_detector.record(exec);
+    }
+}
+
 pub struct ExecView {
     config: Config,
 
@@ -54,17 +119,32 @@ pub struct ExecView {
 
     transduce_events: Vec<(f32, TransduceEvent)>,
     particle_budget: Vec<f32>,
+
+    recording: Recording,
+
+    /// Execution that is running in the background, without being shown.
+    finish: Option<Finish>,
+
+    /// Has the camera already been pointed at the output that failed the
+    /// level?
+    failure_focused: bool,
 }
 
 impl ExecView {
-    pub fn new(config: &Config, machine: Machine) -> ExecView {
+    pub fn new(config: &Config, machine: Machine, pinned: &[grid::Point3]) -> ExecView {
+        let exec = Exec::new(machine, &mut rand::thread_rng());
+        let recording = Recording::new(&exec, pinned);
+
         ExecView {
             config: config.clone(),
-            exec: Exec::new(machine, &mut rand::thread_rng()),
+            exec,
             mouse_block_pos: None,
             blip_anim_cache: blip_anim::Cache::default(),
             transduce_events: Vec::new(),
             particle_budget: Vec::new(),
+            recording,
+            finish: None,
+            failure_focused: false,
         }
     }
 
@@ -86,10 +166,33 @@ impl ExecView {
         );
     }
 
+    pub fn exec(&self) -> &Exec {
+        &self.exec
+    }
+
+    pub fn mouse_block_pos(&self) -> Option<grid::Point3> {
+        self.mouse_block_pos
+    }
+
+    pub fn trace(&self) -> &Trace {
+        &self.recording.trace
+    }
+
+    pub fn cycle(&self) -> Option<Cycle> {
+        self.recording.cycle_detector.cycle()
+    }
+
     pub fn run_tick(&mut self) {
         profile!("tick");
 
+        // While finishing in the background, the shown execution stands
+        // still.
+        if self.finish.is_some() {
+            return;
+        }
+
         self.exec.update();
+        self.recording.record(&self.exec);
 
         // The blip animation cache is indexed by the tick progress, among other
         // things. The tick progress offsets depend entirely on frame times, so
@@ -98,6 +201,55 @@ impl ExecView {
         self.blip_anim_cache.clear();
     }
 
+    pub fn export_vcd(&self) {
+        let path = &self.config.vcd_path;
+        info!("Exporting signals to VCD file {:?}", path);
+
+        let result =
+            File::create(path).and_then(|file| self.recording.vcd.write(&mut BufWriter::new(file)));
+
+        if let Err(err) = result {
+            warn!("Error while writing VCD file {:?}: {}", path.to_str(), err);
+        }
+    }
+
+    /// Starts running the execution until the level is completed or failed
+    /// in the background, as fast as possible.
+    pub fn start_finish(&mut self) {
+        if self.finish.is_some() || self.next_level_status() != LevelStatus::Running {
+            return;
+        }
+
+        info!("Finishing execution in the background");
+
+        self.finish = Some(Finish::spawn(
+            self.exec.clone(),
+            self.recording.clone(),
+            self.config.max_finish_ticks,
+        ));
+    }
+
+    /// Returns the progress of finishing in the background, between 0 and 1.
+    pub fn finish_progress(&self) -> Option<f32> {
+        self.finish.as_ref().map(Finish::progress)
+    }
+
+    /// Takes over the result of finishing in the background, once it is
+    /// done. Returns the tick at which execution stopped.
+    pub fn poll_finish(&mut self) -> Option<TickNum> {
+        let (exec, recording) = self.finish.as_mut()?.try_take()?;
+
+        info!("Finished execution at tick {}", exec.cur_tick());
+
+        self.exec = exec;
+        self.recording = recording;
+        self.finish = None;
+        self.blip_anim_cache.clear();
+        self.transduce_events.clear();
+
+        Some(self.exec.cur_tick())
+    }
+
     pub fn next_level_status(&self) -> LevelStatus {
         self.exec
             .next_level_progress()
@@ -108,6 +260,20 @@ impl ExecView {
         self.exec.level_progress()
     }
 
+    /// Returns the position of the output block that failed the level, but
+    /// only the first time this is called after the failure.
+    pub fn take_failure_focus(&mut self) -> Option<grid::Point3> {
+        if self.failure_focused {
+            return None;
+        }
+
+        let progress = self.exec.next_level_progress()?;
+        let block_index = progress.outputs[progress.failed_output()?].block_index?;
+
+        self.failure_focused = true;
+        Some(self.exec.machine().blocks.data[block_index].0)
+    }
+
     pub fn on_event(&mut self, event: &WindowEvent) {
         match event {
             WindowEvent::KeyboardInput { input, .. } => self.on_keyboard_input(*input),
@@ -117,20 +283,29 @@ impl ExecView {
 
     fn on_keyboard_input(&mut self, _input: glutin::KeyboardInput) {}
 
-    pub fn render(&mut self, time: &TickTime, out: &mut render::Stage) {
+    pub fn render(
+        &mut self,
+        time: &TickTime,
+        layers: &Layers,
+        current_layer: isize,
+        heatmap: Option<Heatmap>,
+        out: &mut render::Stage,
+    ) {
         profile!("exec_view");
 
+        let is_hidden = |pos: &grid::Point3| layers.is_hidden(pos.z, current_layer);
+
         render::machine::render_machine(
             &self.exec.machine(),
             time,
             Some(&self.exec),
-            |_| true,
-            |_| false,
+            |pos| !is_hidden(pos),
+            |pos| layers.is_ghost(pos.z, current_layer),
             out,
         );
 
-        self.render_blocks(time, out);
-        self.render_blips(time, out);
+        self.render_blocks(time, &is_hidden, heatmap, out);
+        self.render_blips(time, &is_hidden, out);
     }
 
     pub fn transduce(
@@ -333,10 +508,37 @@ impl ExecView {
         }
     }
 
-    fn render_blocks(&self, time: &TickTime, out: &mut render::Stage) {
+    fn render_blocks(
+        &self,
+        time: &TickTime,
+        is_hidden: impl Fn(&grid::Point3) -> bool,
+        heatmap: Option<Heatmap>,
+        out: &mut render::Stage,
+    ) {
         let blocks = &self.exec.machine().blocks;
 
+        let max_heat = heatmap.map_or(0, |heatmap| {
+            self.recording
+                .stats
+                .blocks
+                .iter()
+                .map(|stats| heatmap.value(stats))
+                .max()
+                .unwrap_or(0)
+        });
+
         for (block_index, (block_pos, placed_block)) in blocks.data.iter() {
+            if is_hidden(block_pos) {
+                continue;
+            }
+
+            if let Some(heatmap) = heatmap {
+                if placed_block.block != Block::Air {
+                    let heat = heatmap.value(&self.recording.stats.blocks[block_index]);
+                    render_heat(block_pos, heat, max_heat, out);
+                }
+            }
+
             let anim_state = AnimState::from_exec_block(&self.exec, block_index);
 
             for &dir in &Dir3::ALL {
@@ -378,10 +580,19 @@ impl ExecView {
         }
     }
 
-    fn render_blips(&mut self, time: &TickTime, out: &mut render::Stage) {
+    fn render_blips(
+        &mut self,
+        time: &TickTime,
+        is_hidden: impl Fn(&grid::Point3) -> bool,
+        out: &mut render::Stage,
+    ) {
         profile!("blips");
 
         for (_index, blip) in self.exec.blips().iter() {
+            if is_hidden(&blip.pos) {
+                continue;
+            }
+
             let anim_input = self.blip_anim_input(blip);
             let anim_value = self
                 .blip_anim_cache
@@ -427,10 +638,39 @@ impl ExecView {
                 .machine()
                 .get_index(&blip.pos)
                 .map_or(false, |block_index| {
-                    self.exec.next_blocks().wind_out[block_index][dir]
+                    self.exec
+                        .next_blocks()
+                        .wind_out
+                        .get(block_index)
+                        .contains(dir)
                 })
         });
 
         blip_anim::Input::from_blip(blip, is_on_wind)
     }
 }
+
+/// Outlines a block in a color that shows how much activity there was in it,
+/// relative to the block with the most activity. Blocks without any activity
+/// are outlined in gray.
+fn render_heat(pos: &grid::Point3, heat: usize, max_heat: usize, out: &mut render::Stage) {
+    let color = if heat == 0 {
+        na::Vector4::new(0.3, 0.3, 0.3, 1.0)
+    } else {
+        let cold = na::Vector4::new(0.1, 0.3, 1.0, 1.0);
+        let warm = na::Vector4::new(1.0, 0.9, 0.1, 1.0);
+        let hot = na::Vector4::new(1.0, 0.1, 0.1, 1.0);
+
+        let t = heat as f32 / max_heat as f32;
+        if t < 0.5 {
+            cold + (warm - cold) * (t * 2.0)
+        } else {
+            warm + (hot - warm) * (t * 2.0 - 1.0)
+        }
+    };
+
+    let center = render::machine::block_center(pos);
+    let transform = na::Matrix4::new_translation(&center.coords);
+
+    render::machine::render_line_wireframe(13.0, &color, &transform, out);
+}
@@ -0,0 +1,403 @@
+//! Packed representation of wind, with one bit per direction and block.
+//!
+//! Packing makes copying and comparing the wind of all blocks cheap. It also
+//! allows advecting the wind of a whole word of blocks at once, see
+//! `WindFlow`.
+
+use std::collections::BTreeMap;
+use std::ops::{BitAnd, BitOr, Not, Range};
+
+use crate::exec::neighbors::NeighborMap;
+use crate::machine::grid::{Dir3, DirMap3};
+use crate::machine::{Block, BlockIndex};
+
+/// A set of directions, e.g. the directions in which wind flows out of a
+/// block. Bit `i` corresponds to the direction with index `i`, as given by
+/// `Dir3::to_index`, which is also the order of `Dir3::ALL`.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default, Hash)]
+pub struct WindMask(u8);
+
+impl WindMask {
+    pub const BITS: usize = Dir3::NUM_INDICES;
+    pub const NONE: WindMask = WindMask(0);
+    pub const ALL: WindMask = WindMask((1 << Self::BITS) - 1);
+
+    pub fn from_dir(dir: Dir3) -> Self {
+        WindMask(1 << dir.to_index())
+    }
+
+    pub fn from_fn(f: impl Fn(Dir3) -> bool) -> Self {
+        Dir3::ALL
+            .iter()
+            .filter(|dir| f(**dir))
+            .fold(Self::NONE, |mask, dir| mask | Self::from_dir(*dir))
+    }
+
+    pub fn to_dir_map(self) -> DirMap3<bool> {
+        DirMap3::from_fn(|dir| self.contains(dir))
+    }
+
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub fn contains(self, dir: Dir3) -> bool {
+        self.0 & (1 << dir.to_index()) != 0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl BitAnd for WindMask {
+    type Output = WindMask;
+
+    fn bitand(self, other: WindMask) -> WindMask {
+        WindMask(self.0 & other.0)
+    }
+}
+
+impl BitOr for WindMask {
+    type Output = WindMask;
+
+    fn bitor(self, other: WindMask) -> WindMask {
+        WindMask(self.0 | other.0)
+    }
+}
+
+impl Not for WindMask {
+    type Output = WindMask;
+
+    fn not(self) -> WindMask {
+        WindMask(!self.0) & Self::ALL
+    }
+}
+
+/// Number of blocks whose wind is packed into one word.
+const BLOCKS_PER_WORD: usize = 64 / WindMask::BITS;
+
+/// The lowest bit of each block's lane in a word.
+const LANE_LOW_BITS: u64 = 0x0041_0410_4104_1041;
+
+/// The wind flowing out of each block of a machine. The masks of
+/// `BLOCKS_PER_WORD` consecutive blocks are packed into one word, so that
+/// the whole state can be copied and compared a word at a time.
+#[derive(PartialEq, Eq, Clone, Debug, Hash)]
+pub struct WindState {
+    num_blocks: usize,
+    words: Vec<u64>,
+}
+
+impl WindState {
+    pub fn new(num_blocks: usize) -> Self {
+        let num_words = (num_blocks + BLOCKS_PER_WORD - 1) / BLOCKS_PER_WORD;
+
+        Self {
+            num_blocks,
+            words: vec![0; num_words],
+        }
+    }
+
+    pub fn num_words(&self) -> usize {
+        self.words.len()
+    }
+
+    /// Returns the index of the word that holds the wind of the given block.
+    pub fn word_index(block_index: BlockIndex) -> usize {
+        Self::location(block_index).0
+    }
+
+    /// Returns the blocks whose wind is packed into the given word.
+    pub fn word_blocks(&self, word: usize) -> Range<BlockIndex> {
+        word * BLOCKS_PER_WORD..((word + 1) * BLOCKS_PER_WORD).min(self.num_blocks)
+    }
+
+    pub fn word(&self, word: usize) -> u64 {
+        self.words[word]
+    }
+
+    pub fn set_word(&mut self, word: usize, value: u64) {
+        self.words[word] = value;
+    }
+
+    /// Returns a word with all the bits set in the lanes of those blocks of
+    /// the given word for which `f` is true.
+    pub fn lanes_where(&self, word: usize, f: impl Fn(BlockIndex) -> bool) -> u64 {
+        self.word_blocks(word)
+            .filter(|&block_index| f(block_index))
+            .fold(0, |lanes, block_index| {
+                lanes | ((WindMask::ALL.0 as u64) << Self::location(block_index).1)
+            })
+    }
+
+    pub fn get(&self, block_index: BlockIndex) -> WindMask {
+        let (word, shift) = Self::location(block_index);
+
+        WindMask(((self.words[word] >> shift) & WindMask::ALL.0 as u64) as u8)
+    }
+
+    pub fn set(&mut self, block_index: BlockIndex, mask: WindMask) {
+        let (word, shift) = Self::location(block_index);
+
+        self.words[word] &= !((WindMask::ALL.0 as u64) << shift);
+        self.words[word] |= (mask.0 as u64) << shift;
+    }
+
+    /// Overwrites our wind with that of `other`, which needs to have the
+    /// same number of blocks.
+    pub fn copy_from(&mut self, other: &WindState) {
+        self.words.copy_from_slice(&other.words);
+    }
+
+    fn location(block_index: BlockIndex) -> (usize, usize) {
+        (
+            block_index / BLOCKS_PER_WORD,
+            (block_index % BLOCKS_PER_WORD) * WindMask::BITS,
+        )
+    }
+}
+
+/// The directions in which a block lets wind in and out, for both of its
+/// activation states. Checking the holes of a block is comparatively slow,
+/// so we do it once before executing, instead of in every tick.
+#[derive(Clone, Debug)]
+pub struct WindHoles {
+    hole_in: [WindMask; 2],
+    hole_out: [WindMask; 2],
+    source: WindMask,
+}
+
+impl WindHoles {
+    pub fn new(block: &Block) -> Self {
+        Self {
+            hole_in: [
+                WindMask::from_fn(|dir| block.has_wind_hole_in(dir, false)),
+                WindMask::from_fn(|dir| block.has_wind_hole_in(dir, true)),
+            ],
+            hole_out: [
+                WindMask::from_fn(|dir| block.has_wind_hole_out(dir, false)),
+                WindMask::from_fn(|dir| block.has_wind_hole_out(dir, true)),
+            ],
+            source: WindMask::from_fn(|dir| block.has_wind_source(dir)),
+        }
+    }
+
+    pub fn hole_in(&self, activated: bool) -> WindMask {
+        self.hole_in[activated as usize]
+    }
+
+    pub fn hole_out(&self, activated: bool) -> WindMask {
+        self.hole_out[activated as usize]
+    }
+
+    pub fn source(&self) -> WindMask {
+        self.source
+    }
+}
+
+/// Moves the bits of `src_word` by `shift` into the bits of `mask`.
+#[derive(Clone, Debug)]
+struct GatherOp {
+    src_word: usize,
+    shift: isize,
+    mask: u64,
+}
+
+/// Advects the wind of a whole word of blocks at once.
+///
+/// For each word, we precompute how to gather the wind that flows in from
+/// the neighbors of its blocks. Bits that come from the same word and need to
+/// be moved by the same amount are gathered with a single shift. Pipes are
+/// usually stored in the order in which they are connected, so this often
+/// takes only a few operations per word.
+#[derive(Clone, Debug)]
+pub struct WindFlow {
+    /// Start of the gather operations of each word in `ops`, followed by the
+    /// total number of operations.
+    word_ops: Vec<usize>,
+    ops: Vec<GatherOp>,
+
+    /// For each word, the words that gather wind from it.
+    readers: Vec<Vec<usize>>,
+
+    hole_in: [WindState; 2],
+    hole_out: [WindState; 2],
+
+    /// All bits are set in the lanes of blocks that may spawn wind, instead
+    /// of only conducting it. These need to be looked at one at a time.
+    spawns: WindState,
+}
+
+impl WindFlow {
+    pub fn new(
+        neighbor_map: &NeighborMap,
+        wind_holes: &[WindHoles],
+        spawns_wind: impl Fn(BlockIndex) -> bool,
+    ) -> Self {
+        let num_blocks = wind_holes.len();
+        let num_words = WindState::new(num_blocks).num_words();
+
+        // Merge the bits that are moved from the same word by the same shift.
+        // The map is ordered, so that the operations end up sorted by word.
+        let mut masks: BTreeMap<(usize, usize, isize), u64> = BTreeMap::new();
+
+        for block_index in 0..num_blocks {
+            let (word, shift) = WindState::location(block_index);
+
+            for (dir, neighbor_index) in neighbor_map[block_index].iter() {
+                if let Some(neighbor_index) = neighbor_index {
+                    let (src_word, src_shift) = WindState::location(*neighbor_index);
+                    let bit = shift + dir.to_index();
+                    let src_bit = src_shift + dir.invert().to_index();
+
+                    *masks
+                        .entry((word, src_word, src_bit as isize - bit as isize))
+                        .or_insert(0) |= 1 << bit;
+                }
+            }
+        }
+
+        let mut word_ops = Vec::with_capacity(num_words + 1);
+        let mut ops = Vec::with_capacity(masks.len());
+        let mut readers = vec![Vec::new(); num_words];
+
+        for ((word, src_word, shift), mask) in masks {
+            while word_ops.len() <= word {
+                word_ops.push(ops.len());
+            }
+
+            ops.push(GatherOp {
+                src_word,
+                shift,
+                mask,
+            });
+
+            if readers[src_word].last() != Some(&word) {
+                readers[src_word].push(word);
+            }
+        }
+
+        while word_ops.len() <= num_words {
+            word_ops.push(ops.len());
+        }
+
+        let mut flow = Self {
+            word_ops,
+            ops,
+            readers,
+            hole_in: [WindState::new(num_blocks), WindState::new(num_blocks)],
+            hole_out: [WindState::new(num_blocks), WindState::new(num_blocks)],
+            spawns: WindState::new(num_blocks),
+        };
+
+        for (block_index, holes) in wind_holes.iter().enumerate() {
+            flow.set_block(block_index, holes, spawns_wind(block_index));
+        }
+
+        flow
+    }
+
+    /// Updates the holes of a block, e.g. after it has been changed by an
+    /// activation.
+    pub fn set_block(&mut self, block_index: BlockIndex, holes: &WindHoles, spawns_wind: bool) {
+        for &activated in &[false, true] {
+            self.hole_in[activated as usize].set(block_index, holes.hole_in(activated));
+            self.hole_out[activated as usize].set(block_index, holes.hole_out(activated));
+        }
+
+        let spawns = if spawns_wind {
+            WindMask::ALL
+        } else {
+            WindMask::NONE
+        };
+        self.spawns.set(block_index, spawns);
+    }
+
+    /// Returns the words that need to be advected again if the wind in the
+    /// given word changes.
+    pub fn readers(&self, word: usize) -> &[usize] {
+        &self.readers[word]
+    }
+
+    pub fn spawns_wind(&self, block_index: BlockIndex) -> bool {
+        !self.spawns.get(block_index).is_empty()
+    }
+
+    pub fn any_spawns_wind(&self, word: usize) -> bool {
+        self.spawns.word(word) != 0
+    }
+
+    /// Returns the wind flowing out of the blocks in the given word, if they
+    /// all conduct the wind of their neighbors. `active` needs to have all
+    /// bits set in the lanes of activated blocks.
+    ///
+    /// A block forwards its incoming wind to its outgoing wind holes, except
+    /// for the directions that the wind came in from.
+    pub fn advect_word(&self, word: usize, wind_out: &WindState, active: u64) -> u64 {
+        let ops = &self.ops[self.word_ops[word]..self.word_ops[word + 1]];
+        let neighbor_wind = ops.iter().fold(0, |gathered, op| {
+            let src = wind_out.words[op.src_word];
+            let moved = if op.shift >= 0 {
+                src >> op.shift
+            } else {
+                src << -op.shift
+            };
+
+            gathered | (moved & op.mask)
+        });
+
+        let hole_in =
+            (self.hole_in[0].words[word] & !active) | (self.hole_in[1].words[word] & active);
+        let hole_out =
+            (self.hole_out[0].words[word] & !active) | (self.hole_out[1].words[word] & active);
+        let wind_in = neighbor_wind & hole_in;
+
+        hole_out & !wind_in & non_empty_lanes(wind_in)
+    }
+}
+
+/// Returns a word with all bits set in the lanes that have any bit set in
+/// `word`.
+fn non_empty_lanes(word: u64) -> u64 {
+    // Collect the bits of each lane in its lowest bit. Bits from the lane
+    // above end up in the other bits, which we mask out.
+    let any = (1..WindMask::BITS).fold(word, |any, i| any | (word >> i));
+
+    (any & LANE_LOW_BITS) * WindMask::ALL.0 as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that the lanes of blocks in a word are found no matter which of
+    /// their bits are set, without leaking into neighboring lanes.
+    #[test]
+    fn test_non_empty_lanes() {
+        let lanes = |masks: &[WindMask]| {
+            let mut state = WindState::new(BLOCKS_PER_WORD);
+            for (block_index, mask) in masks.iter().enumerate() {
+                state.set(block_index, *mask);
+            }
+            state.word(0)
+        };
+
+        let full = lanes(&[WindMask::ALL; BLOCKS_PER_WORD]);
+        assert_eq!(LANE_LOW_BITS * WindMask::ALL.0 as u64, full);
+        assert_eq!(non_empty_lanes(full), full);
+        assert_eq!(non_empty_lanes(0), 0);
+
+        let mut masks = [WindMask::NONE; BLOCKS_PER_WORD];
+        masks[0] = WindMask::from_dir(Dir3::ALL[Dir3::ALL.len() - 1]);
+        masks[4] = WindMask::from_dir(Dir3::ALL[0]);
+        masks[BLOCKS_PER_WORD - 1] = WindMask::from_dir(Dir3::ALL[2]);
+
+        let mut expected = [WindMask::NONE; BLOCKS_PER_WORD];
+        for &i in &[0, 4, BLOCKS_PER_WORD - 1] {
+            expected[i] = WindMask::ALL;
+        }
+
+        assert_eq!(non_empty_lanes(lanes(&masks)), lanes(&expected));
+    }
+}
@@ -12,9 +12,11 @@ use glium::glutin;
 use crate::config::Config;
 use crate::edit::Editor;
 use crate::exec::play::{self, Play};
+use crate::exec::stats::Heatmap;
 use crate::exec::LevelStatus;
 use crate::input_state::InputState;
 use crate::machine::Machine;
+use crate::render::layers::Layers;
 use crate::util::stats;
 
 use draw::Draw;
@@ -34,6 +36,13 @@ pub struct Game {
     play: Play,
     play_status: Option<play::Status>,
 
+    /// How the layers of the machine are shown. This is kept here, so that
+    /// it persists when switching between editing and execution.
+    layers: Layers,
+
+    /// Block activity that is shown on top of the blocks while executing.
+    heatmap: Option<Heatmap>,
+
     debug_frame_time: stats::Variable,
     show_config_ui: bool,
     show_debug_ui: bool,
@@ -75,6 +84,8 @@ impl Game {
             next_input_stage: update::InputStage::default(),
             play,
             play_status: None,
+            layers: Layers::default(),
+            heatmap: None,
             debug_frame_time: stats::Variable::new(Duration::from_secs(1)),
             show_config_ui: false,
             show_debug_ui: false,
@@ -103,12 +114,27 @@ impl Game {
                 }
             }
 
+            // If execution has been finished in the background, continue
+            // showing it paused at the tick where it stopped.
+            if let Some(tick) = output.finished_tick {
+                self.play_status = self.play_status.as_ref().map(|status| {
+                    let mut time = status.time().clone();
+                    time.num_ticks_passed = tick;
+                    time.next_tick_timer.set_progress(0.0);
+
+                    play::Status::Paused { time }
+                });
+            }
+
             self.last_output = Some(output);
         }
 
         // Note that play status may be set to `Finished` above in this
         // function, so order is important here.
         let old_play_status = self.play_status.clone();
+        if self.play.is_finish_pressed() && self.play_status.is_some() {
+            self.next_input_stage.finish = true;
+        }
         self.play_status = self.play.update_status(dt, self.play_status.as_ref());
 
         // Did we just stop execution?
@@ -121,8 +147,10 @@ impl Game {
 
             // Submit the next input for the update thread. Updating can then run
             // at the same time as drawing the previous output.
-            let next_input_stage =
+            let mut next_input_stage =
                 std::mem::replace(&mut self.next_input_stage, Default::default());
+            next_input_stage.layers = self.layers.clone();
+            next_input_stage.heatmap = self.heatmap;
             let next_input = next_input_stage.into_input(
                 dt,
                 self.target_size,
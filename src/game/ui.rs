@@ -6,10 +6,16 @@ use rendology::basic_obj::BasicObj;
 use rendology::fxaa;
 
 use crate::edit::editor;
+use crate::exec::batch::Summary;
+use crate::exec::stats::Heatmap;
+use crate::exec::trace::{Sample, Trace};
 use crate::exec::{LevelProgress, LevelStatus};
+use crate::game::update::BlockInfo;
 use crate::game::Game;
-use crate::machine::{level, Level};
+use crate::machine::grid::Dir3;
+use crate::machine::{level, param, BlipKind, Level};
 use crate::render;
+use crate::render::layers::{LayerFocus, LayerVisibility};
 
 impl Game {
     pub fn ui(&mut self, ui: &imgui::Ui) {
@@ -28,9 +34,36 @@ impl Game {
         self.play.ui(
             na::Vector2::new(self.target_size.0 as f32, self.target_size.1 as f32),
             self.play_status.as_ref(),
+            self.last_output.as_ref().and_then(|o| o.cycle),
+            self.last_output.as_ref().and_then(|o| o.finish_progress),
             ui,
         );
 
+        let mouse_block_info = self
+            .last_output
+            .as_ref()
+            .and_then(|o| o.mouse_block_info.as_ref());
+        if let Some(mouse_block_info) = mouse_block_info {
+            // Don't cover imgui windows with the tooltip.
+            if !ui.io().want_capture_mouse {
+                ui.tooltip(|| ui_block_info(mouse_block_info, ui));
+            }
+        }
+
+        if self.play_status.is_some() {
+            let trace = self.last_output.as_ref().and_then(|o| o.trace.as_ref());
+            if self.ui_trace(trace, ui) {
+                self.next_input_stage.export_vcd = true;
+            }
+
+            self.ui_heatmap(ui);
+        }
+
+        if let Some(output) = self.last_output.as_ref() {
+            let (current_layer, num_layers) = (output.current_layer, output.num_layers);
+            self.ui_layers(current_layer, num_layers, ui);
+        }
+
         if self.show_config_ui {
             self.ui_config(ui);
         }
@@ -48,6 +81,204 @@ impl Game {
         }
     }
 
+    /// Shows the timing diagram of the pinned blocks. Returns true if the
+    /// signals are to be exported.
+    fn ui_trace(&self, trace: Option<&Trace>, ui: &imgui::Ui) -> bool {
+        let mut export_vcd = false;
+
+        imgui::Window::new(im_str!("Timing"))
+            .horizontal_scrollbar(true)
+            .position(
+                [
+                    self.target_size.0 as f32 / 2.0,
+                    self.target_size.1 as f32 - 80.0,
+                ],
+                imgui::Condition::FirstUseEver,
+            )
+            .position_pivot([0.5, 1.0])
+            .always_auto_resize(true)
+            .bg_alpha(0.8)
+            .build(&ui, || {
+                if ui.button(im_str!("Export VCD"), [100.0, 20.0]) {
+                    export_vcd = true;
+                }
+                if ui.is_item_hovered() {
+                    let text = "Write the signals of the pinned blocks and of the inputs and \
+                                outputs since the start of execution to a VCD file.";
+                    ui.tooltip(|| ui.text(&ImString::new(text)));
+                }
+
+                let trace = match trace {
+                    Some(trace) => trace,
+                    None => {
+                        ui.text(im_str!(
+                            "Pin blocks in the editor to show their activations here."
+                        ));
+                        return;
+                    }
+                };
+
+                if let Some((tick, _)) = trace.ticks.back() {
+                    ui.text(&ImString::new(format!("Tick {}", tick)));
+                }
+
+                for (index, pos) in trace.positions.iter().enumerate() {
+                    let label = format!("({}, {}, {})", pos.x, pos.y, pos.z);
+                    let samples = trace.ticks.iter().map(|(_, samples)| samples[index]);
+
+                    self.ui_show_trace_row(&label, samples, ui);
+                }
+            });
+
+        export_vcd
+    }
+
+    fn ui_show_trace_row(
+        &self,
+        label: &str,
+        samples: impl Iterator<Item = Sample>,
+        ui: &imgui::Ui,
+    ) {
+        let tick_width = 6.0;
+        let row_height = 16.0;
+        let low_height = 2.0;
+        let blip_height = 3.0;
+        let low_color = [0.5, 0.5, 0.5];
+
+        let draw_list = ui.get_window_draw_list();
+
+        ui.text(&ImString::new(label));
+        ui.same_line(100.0);
+
+        let cursor_pos = ui.cursor_screen_pos();
+        let mut num_ticks = 0;
+
+        for (column, sample) in samples.enumerate() {
+            let x = cursor_pos[0] + column as f32 * tick_width;
+            let bottom = cursor_pos[1] + row_height;
+
+            // Activations are shown as high signals, like in a logic
+            // analyzer.
+            let (top, color) = match sample.activation {
+                Some(kind) => (cursor_pos[1] + blip_height + 1.0, blip_color(kind)),
+                None => (bottom - low_height, low_color),
+            };
+            draw_list.add_rect_filled_multicolor(
+                [x, top],
+                [x + tick_width, bottom],
+                color,
+                color,
+                color,
+                color,
+            );
+
+            // Blips that are in the block are shown as marks above the
+            // signal.
+            if let Some(kind) = sample.blip {
+                let color = blip_color(kind);
+                draw_list.add_rect_filled_multicolor(
+                    [x + 1.0, cursor_pos[1]],
+                    [x + tick_width - 1.0, cursor_pos[1] + blip_height],
+                    color,
+                    color,
+                    color,
+                    color,
+                );
+            }
+
+            num_ticks += 1;
+        }
+
+        ui.dummy([num_ticks.max(1) as f32 * tick_width, row_height]);
+    }
+
+    fn ui_heatmap(&mut self, ui: &imgui::Ui) {
+        let heatmap = &mut self.heatmap;
+
+        imgui::Window::new(im_str!("Heatmap"))
+            .horizontal_scrollbar(true)
+            .position(
+                [self.target_size.0 as f32, self.target_size.1 as f32],
+                imgui::Condition::FirstUseEver,
+            )
+            .position_pivot([1.0, 1.0])
+            .always_auto_resize(true)
+            .collapsed(true, imgui::Condition::FirstUseEver)
+            .bg_alpha(0.8)
+            .build(&ui, || {
+                ui.radio_button(im_str!("Off"), heatmap, None);
+
+                for &choice in Heatmap::ALL {
+                    ui.radio_button(&ImString::new(choice.name()), heatmap, Some(choice));
+                }
+
+                ui.separator();
+                ui.text_disabled(im_str!(
+                    "Counted since the start of execution.\n\
+                     Blue: little, red: most, gray: none."
+                ));
+            });
+    }
+
+    fn ui_layers(&mut self, current_layer: isize, num_layers: isize, ui: &imgui::Ui) {
+        let layers = &mut self.layers;
+
+        imgui::Window::new(im_str!("Layers"))
+            .horizontal_scrollbar(true)
+            .position(
+                [0.0, self.target_size.1 as f32],
+                imgui::Condition::FirstUseEver,
+            )
+            .position_pivot([0.0, 1.0])
+            .always_auto_resize(true)
+            .collapsed(true, imgui::Condition::FirstUseEver)
+            .bg_alpha(0.8)
+            .build(&ui, || {
+                ui.radio_button(im_str!("All layers"), &mut layers.focus, LayerFocus::All);
+                ui.radio_button(
+                    im_str!("Cut away above current layer"),
+                    &mut layers.focus,
+                    LayerFocus::Cutaway,
+                );
+                ui.radio_button(
+                    im_str!("Isolate current layer"),
+                    &mut layers.focus,
+                    LayerFocus::Isolate,
+                );
+
+                ui.separator();
+
+                ui.columns(4, im_str!("ui_layers"), false);
+
+                for layer in (0..num_layers).rev() {
+                    let text = ImString::new(layer.to_string());
+                    if layer == current_layer {
+                        ui.text(&text);
+                    } else {
+                        ui.text_disabled(&text);
+                    }
+                    ui.next_column();
+
+                    let mut visibility = layers.get(layer);
+                    let choices = [
+                        ("show", LayerVisibility::Show),
+                        ("ghost", LayerVisibility::Ghost),
+                        ("hide", LayerVisibility::Hide),
+                    ];
+
+                    for (name, choice) in choices.iter() {
+                        let label = ImString::new(format!("{}##{}", name, layer));
+                        ui.radio_button(&label, &mut visibility, *choice);
+                        ui.next_column();
+                    }
+
+                    layers.set(layer, visibility);
+                }
+
+                ui.columns(1, im_str!("ui_layers_end"), false);
+            });
+    }
+
     fn ui_config(&mut self, ui: &imgui::Ui) {
         imgui::Window::new(im_str!("Config"))
             .horizontal_scrollbar(true)
@@ -176,6 +407,16 @@ impl Game {
 
     fn ui_level_progress(&mut self, level: &Level, example: &LevelProgress, ui: &imgui::Ui) {
         let next_level_status = self.last_output.as_ref().and_then(|o| o.next_level_status);
+        let failure_reason = self
+            .last_output
+            .as_ref()
+            .and_then(|o| o.failure_reason.as_ref())
+            .map(|reason| reason.to_string());
+        let batch_progress = self.last_output.as_ref().and_then(|o| o.batch_progress);
+        let batch_summary = self
+            .last_output
+            .as_ref()
+            .and_then(|o| o.batch_summary.clone());
 
         imgui::Window::new(im_str!("Level"))
             .horizontal_scrollbar(true)
@@ -202,6 +443,10 @@ impl Game {
 
                 ui.bullet_text(&ImString::new(&("Status: ".to_string() + status)));
 
+                if let Some(failure_reason) = failure_reason.as_ref() {
+                    ui.bullet_text(&ImString::new(&("Reason: ".to_string() + failure_reason)));
+                }
+
                 imgui::TreeNode::new(ui, im_str!("Show example"))
                     .opened(false, imgui::Condition::FirstUseEver)
                     .build(|| {
@@ -215,6 +460,26 @@ impl Game {
                             }
                         }
                     });
+
+                if let Some(batch_progress) = batch_progress {
+                    imgui::ProgressBar::new(batch_progress)
+                        .overlay_text(im_str!("Testing examples..."))
+                        .size([-1.0, 0.0])
+                        .build(ui);
+                } else if next_level_status.is_none() {
+                    if ui.button(im_str!("Test examples"), [120.0, 20.0]) {
+                        self.next_input_stage.batch = true;
+                    }
+                    if ui.is_item_hovered() {
+                        let text = "Run the machine on many generated examples in the \
+                                    background, and show how many of them it completes.";
+                        ui.tooltip(|| ui.text(&ImString::new(text)));
+                    }
+                }
+
+                if let Some(summary) = batch_summary.as_ref() {
+                    ui_batch_summary(summary, ui);
+                }
             });
     }
 
@@ -318,3 +583,93 @@ impl Game {
         }
     }
 }
+
+fn blip_color(kind: BlipKind) -> [f32; 3] {
+    render::machine::blip_color(kind).into()
+}
+
+/// Maximal number of failed examples to list after testing many examples.
+const MAX_SHOWN_FAILURES: usize = 5;
+
+fn ui_batch_summary(summary: &Summary, ui: &imgui::Ui) {
+    ui.bullet_text(&ImString::new(format!(
+        "Completed: {} of {} examples",
+        summary.count(LevelStatus::Completed),
+        summary.results.len(),
+    )));
+
+    if let Some((mean, max)) = summary.completed_ticks() {
+        ui.bullet_text(&ImString::new(format!(
+            "Ticks: {:.1} on average, {} at most",
+            mean, max
+        )));
+    }
+
+    for result in summary.failures().take(MAX_SHOWN_FAILURES) {
+        let reason = result
+            .failure
+            .as_ref()
+            .map_or_else(|| "Still running".to_string(), ToString::to_string);
+        ui.bullet_text(&ImString::new(format!("Seed {}: {}", result.seed, reason)));
+    }
+
+    let num_failures = summary.failures().count();
+    if num_failures > MAX_SHOWN_FAILURES {
+        ui.text(&ImString::new(format!(
+            "... and {} more",
+            num_failures - MAX_SHOWN_FAILURES
+        )));
+    }
+}
+
+fn ui_block_info(info: &BlockInfo, ui: &imgui::Ui) {
+    ui.text(&ImString::new(info.block.name()));
+    ui.same_line(0.0);
+    ui.text_disabled(&ImString::new(format!(
+        "({}, {}, {})",
+        info.pos.x, info.pos.y, info.pos.z
+    )));
+
+    ui.text_wrapped(&ImString::new(info.block.description()));
+
+    let params = info.block.params();
+    if !params.is_empty() {
+        ui.separator();
+
+        for (name, value) in params {
+            ui.text(&ImString::new(format!("{}: {}", name, value)));
+        }
+    }
+
+    if let Some(exec) = info.exec.as_ref() {
+        ui.separator();
+
+        let activation = exec
+            .activation
+            .map_or("no".to_string(), |kind| format!("by {}", kind));
+        ui.text(&ImString::new(format!("Activated: {}", activation)));
+
+        let wind_out: Vec<_> = Dir3::ALL
+            .iter()
+            .filter(|dir| exec.wind_out[**dir])
+            .map(|dir| param::dir_name(*dir))
+            .collect();
+        let wind_out = if wind_out.is_empty() {
+            "none".to_string()
+        } else {
+            wind_out.join(" ")
+        };
+        ui.text(&ImString::new(format!("Wind out: {}", wind_out)));
+
+        let blips = if exec.blips.is_empty() {
+            "none".to_string()
+        } else {
+            exec.blips
+                .iter()
+                .map(|kind| kind.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        ui.text(&ImString::new(format!("Blips: {}", blips)));
+    }
+}
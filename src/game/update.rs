@@ -11,16 +11,29 @@ use rendology::Camera;
 use crate::config::Config;
 use crate::edit::{editor, Editor};
 use crate::edit_camera_view::{EditCameraView, EditCameraViewInput};
-use crate::exec::{play, ExecView, LevelProgress, LevelStatus, TickTime};
+use crate::exec::batch::{Batch, Summary};
+use crate::exec::cycle::Cycle;
+use crate::exec::stats::Heatmap;
+use crate::exec::trace::Trace;
+use crate::exec::{
+    play, Activation, Exec, ExecView, FailureReason, LevelProgress, LevelStatus, TickTime,
+};
 use crate::input_state::InputState;
-use crate::machine::Level;
+use crate::machine::grid::{self, DirMap3};
+use crate::machine::{BlipKind, Block, Level, Machine, TickNum};
 use crate::render;
+use crate::render::layers::Layers;
 
 #[derive(Debug, Clone, Default)]
 pub struct InputStage {
     pub window_events: Vec<(InputState, glutin::WindowEvent)>,
     pub editor_ui_output: editor::ui::Output,
     pub generate_level_example: bool,
+    pub export_vcd: bool,
+    pub finish: bool,
+    pub batch: bool,
+    pub layers: Layers,
+    pub heatmap: Option<Heatmap>,
 }
 
 impl InputStage {
@@ -49,12 +62,82 @@ pub struct Input {
     pub stage: InputStage,
 }
 
+/// Information about the block that the mouse is pointing to.
+#[derive(Debug, Clone)]
+pub struct BlockInfo {
+    pub pos: grid::Point3,
+    pub block: Block,
+
+    /// State of the block in the current tick, if the machine is running.
+    pub exec: Option<BlockExecInfo>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BlockExecInfo {
+    pub activation: Activation,
+    pub wind_out: DirMap3<bool>,
+
+    /// Kinds of the blips that are currently in the block.
+    pub blips: Vec<BlipKind>,
+}
+
+impl BlockInfo {
+    fn from_machine(machine: &Machine, pos: &grid::Point3) -> Option<Self> {
+        machine.get(pos).map(|placed_block| BlockInfo {
+            pos: *pos,
+            block: placed_block.block.clone(),
+            exec: None,
+        })
+    }
+
+    fn from_exec(exec: &Exec, pos: &grid::Point3) -> Option<Self> {
+        let (block_index, placed_block) = exec.machine().get_with_index(pos)?;
+
+        let blips = exec
+            .blips()
+            .iter()
+            .filter(|(_, blip)| blip.pos == *pos)
+            .map(|(_, blip)| blip.kind)
+            .collect();
+
+        Some(BlockInfo {
+            pos: *pos,
+            block: placed_block.block.clone(),
+            exec: Some(BlockExecInfo {
+                activation: exec.blocks().activation[block_index],
+                wind_out: exec.blocks().wind_out.get(block_index).to_dir_map(),
+                blips,
+            }),
+        })
+    }
+}
+
 pub struct Output {
     pub render_stage: render::Stage,
     pub render_context: render::Context,
     pub editor_ui_input: Option<editor::ui::Input>,
+    pub mouse_block_info: Option<BlockInfo>,
+    pub trace: Option<Trace>,
+    pub current_layer: isize,
+    pub num_layers: isize,
     pub level_progress: Option<(Level, LevelProgress)>,
     pub next_level_status: Option<LevelStatus>,
+    pub failure_reason: Option<FailureReason>,
+    pub cycle: Option<Cycle>,
+
+    /// Progress of finishing execution in the background, between 0 and 1.
+    pub finish_progress: Option<f32>,
+
+    /// The tick at which finishing in the background has stopped, if it has
+    /// been completed in this update.
+    pub finished_tick: Option<TickNum>,
+
+    /// Progress of evaluating the machine on many level examples, between 0
+    /// and 1.
+    pub batch_progress: Option<f32>,
+
+    /// Result of the last evaluation on many level examples.
+    pub batch_summary: Option<Summary>,
 }
 
 enum Command {
@@ -168,6 +251,10 @@ impl Drop for UpdateRunner {
 pub struct Update {
     config: Config,
 
+    /// Set when finishing in the background has been completed, until it is
+    /// reported in the next output.
+    finished_tick: Option<TickNum>,
+
     fov: f32,
     camera: Camera,
     edit_camera_view: EditCameraView,
@@ -178,6 +265,10 @@ pub struct Update {
 
     /// Current input/output example to show for the level.
     level_progress: Option<LevelProgress>,
+
+    /// Evaluation of the machine on many level examples, while it is running.
+    batch: Option<Batch>,
+    batch_summary: Option<Summary>,
 }
 
 impl Update {
@@ -200,6 +291,7 @@ impl Update {
 
         Self {
             config: config.clone(),
+            finished_tick: None,
             fov,
             camera,
             edit_camera_view,
@@ -207,6 +299,8 @@ impl Update {
             editor,
             exec_view: None,
             level_progress,
+            batch: None,
+            batch_summary: None,
         }
     }
 
@@ -250,6 +344,22 @@ impl Update {
                 &self.edit_camera_view,
             );
 
+            if input.stage.export_vcd {
+                exec_view.export_vcd();
+            }
+
+            if input.stage.finish {
+                exec_view.start_finish();
+            }
+            if let Some(tick) = exec_view.poll_finish() {
+                self.finished_tick = Some(tick);
+            }
+
+            if let Some(pos) = exec_view.take_failure_focus() {
+                let target = render::machine::block_center(&pos);
+                self.edit_camera_view.set_target(target);
+            }
+
             self.level_progress = exec_view.level_progress().cloned();
         } else {
             // Editor mode
@@ -272,6 +382,16 @@ impl Update {
                     LevelProgress::new(None, inputs_outputs)
                 });
             }
+
+            if input.stage.batch {
+                self.batch = Some(Batch::spawn(self.editor.machine(), &self.config.batch));
+                self.batch_summary = None;
+            }
+        }
+
+        if let Some(summary) = self.batch.as_mut().and_then(Batch::poll) {
+            self.batch = None;
+            self.batch_summary = Some(summary);
         }
 
         self.edit_camera_view_input.update(
@@ -296,6 +416,7 @@ impl Update {
                 self.exec_view = Some(ExecView::new(
                     &self.config.exec,
                     self.editor.machine().clone(),
+                    self.editor.pinned(),
                 ));
             } else {
                 // Stop execution
@@ -365,9 +486,15 @@ impl Update {
             // above.
             let tick_time = input.play_status.as_ref().unwrap().time();
 
-            exec_view.render(tick_time, &mut render_stage);
+            exec_view.render(
+                tick_time,
+                &input.stage.layers,
+                self.editor.current_layer(),
+                input.stage.heatmap,
+                &mut render_stage,
+            );
         } else {
-            self.editor.render(&mut render_stage);
+            self.editor.render(&input.stage.layers, &mut render_stage);
         }
 
         let main_light_pos = na::Point3::new(
@@ -402,6 +529,23 @@ impl Update {
             None
         };
 
+        let mouse_block_info = if let Some(exec_view) = self.exec_view.as_ref() {
+            exec_view
+                .mouse_block_pos()
+                .and_then(|pos| BlockInfo::from_exec(exec_view.exec(), &pos))
+        } else {
+            self.editor
+                .mouse_block_pos()
+                .and_then(|pos| BlockInfo::from_machine(self.editor.machine(), &pos))
+        };
+
+        let trace = self
+            .exec_view
+            .as_ref()
+            .map(|exec_view| exec_view.trace())
+            .filter(|trace| !trace.is_empty())
+            .cloned();
+
         let level_progress = self
             .editor
             .machine()
@@ -414,12 +558,31 @@ impl Update {
             .as_ref()
             .map(|exec_view| exec_view.next_level_status());
 
+        let failure_reason = self
+            .exec_view
+            .as_ref()
+            .and_then(|exec_view| exec_view.exec().next_level_progress())
+            .and_then(|progress| progress.failure.clone());
+
+        let cycle = self.exec_view.as_ref().and_then(ExecView::cycle);
+        let finish_progress = self.exec_view.as_ref().and_then(ExecView::finish_progress);
+
         Output {
             render_stage,
             render_context,
             editor_ui_input,
+            mouse_block_info,
+            trace,
+            current_layer: self.editor.current_layer(),
+            num_layers: self.editor.machine().size().z,
             level_progress,
             next_level_status,
+            failure_reason,
+            cycle,
+            finish_progress,
+            finished_tick: self.finished_tick.take(),
+            batch_progress: self.batch.as_ref().map(Batch::progress),
+            batch_summary: self.batch_summary.clone(),
         }
     }
 }
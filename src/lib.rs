@@ -0,0 +1,15 @@
+// Needed for pareen stuff
+#![type_length_limit = "600000000"]
+
+//#![feature(type_alias_impl_trait)]
+
+#[macro_use]
+pub mod util;
+pub mod config;
+pub mod edit;
+pub mod edit_camera_view;
+pub mod exec;
+pub mod game;
+pub mod input_state;
+pub mod machine;
+pub mod render;
@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::mem;
+
+use crate::machine::grid::Point3;
+
+/// Side length of a chunk in the X and Y direction. Chunks are only one
+/// layer tall, since machines tend to be wide but have few layers.
+pub const CHUNK_SIZE_XY: isize = 16;
+
+const CHUNK_VOLUME: usize = (CHUNK_SIZE_XY * CHUNK_SIZE_XY) as usize;
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+struct Chunk<T> {
+    cells: Box<[Option<T>]>,
+    num_occupied: usize,
+}
+
+impl<T: Copy> Chunk<T> {
+    fn new() -> Self {
+        Self {
+            cells: vec![None; CHUNK_VOLUME].into_boxed_slice(),
+            num_occupied: 0,
+        }
+    }
+}
+
+/// Sparse storage of values in an unbounded 3D grid.
+///
+/// The grid is split into flat chunks, which are only allocated once a value
+/// is stored in them, and freed again when they become empty. This way, the
+/// memory that is needed depends only on the occupied part of the grid.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Chunks<T> {
+    chunks: HashMap<Point3, Chunk<T>>,
+}
+
+impl<T> Default for Chunks<T> {
+    fn default() -> Self {
+        Self {
+            chunks: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Copy> Chunks<T> {
+    pub fn get(&self, p: &Point3) -> Option<T> {
+        let (chunk_pos, cell_index) = Self::locate(p);
+
+        self.chunks
+            .get(&chunk_pos)
+            .and_then(|chunk| chunk.cells[cell_index])
+    }
+
+    /// Stores a value at the given position, returning the previous value.
+    pub fn set(&mut self, p: &Point3, value: Option<T>) -> Option<T> {
+        let (chunk_pos, cell_index) = Self::locate(p);
+
+        if value.is_none() && !self.chunks.contains_key(&chunk_pos) {
+            return None;
+        }
+
+        let chunk = self.chunks.entry(chunk_pos).or_insert_with(Chunk::new);
+        let previous = mem::replace(&mut chunk.cells[cell_index], value);
+
+        match (previous.is_some(), value.is_some()) {
+            (false, true) => chunk.num_occupied += 1,
+            (true, false) => chunk.num_occupied -= 1,
+            _ => (),
+        }
+
+        if chunk.num_occupied == 0 {
+            self.chunks.remove(&chunk_pos);
+        }
+
+        previous
+    }
+
+    /// Number of chunks that are currently allocated.
+    pub fn num_chunks(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Approximate number of bytes used by the allocated chunks, not counting
+    /// the overhead of the hash map.
+    pub fn num_bytes(&self) -> usize {
+        let chunk_bytes =
+            CHUNK_VOLUME * mem::size_of::<Option<T>>() + mem::size_of::<(Point3, Chunk<T>)>();
+
+        self.chunks.len() * chunk_bytes
+    }
+
+    fn locate(p: &Point3) -> (Point3, usize) {
+        let chunk_pos = Point3::new(
+            p.x.div_euclid(CHUNK_SIZE_XY),
+            p.y.div_euclid(CHUNK_SIZE_XY),
+            p.z,
+        );
+        let cell_index =
+            p.x.rem_euclid(CHUNK_SIZE_XY) + p.y.rem_euclid(CHUNK_SIZE_XY) * CHUNK_SIZE_XY;
+
+        (chunk_pos, cell_index as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that chunks store values at negative positions and on both sides of
+    /// chunk boundaries, without mixing them up.
+    #[test]
+    fn test_chunks_get_set() {
+        let mut chunks = Chunks::default();
+        let n = CHUNK_SIZE_XY;
+        let points = [
+            Point3::new(0, 0, 0),
+            Point3::new(-1, 0, 0),
+            Point3::new(0, -1, -1),
+            Point3::new(n - 1, n - 1, 0),
+            Point3::new(n, n - 1, 0),
+            Point3::new(n - 1, n, 1),
+            Point3::new(-n, -n - 1, 5),
+        ];
+
+        for (i, p) in points.iter().enumerate() {
+            assert_eq!(chunks.set(p, Some(i)), None);
+        }
+        for (i, p) in points.iter().enumerate() {
+            assert_eq!(chunks.get(p), Some(i), "{:?}", p);
+        }
+
+        assert_eq!(chunks.get(&Point3::new(1, 0, 0)), None);
+        assert_eq!(chunks.get(&Point3::new(0, 0, 1)), None);
+        assert_eq!(chunks.set(&points[1], Some(42)), Some(1));
+        assert_eq!(chunks.get(&points[1]), Some(42));
+    }
+
+    /// Test that chunks are freed once they contain no values anymore.
+    #[test]
+    fn test_chunks_free_empty() {
+        let mut chunks = Chunks::default();
+        let a = Point3::new(3, 4, 0);
+        let b = Point3::new(5, 6, 0);
+        let c = Point3::new(CHUNK_SIZE_XY, 0, 0);
+
+        // Removing values from a missing chunk does not allocate it.
+        assert_eq!(chunks.set(&a, None), None);
+        assert_eq!(chunks.num_chunks(), 0);
+
+        chunks.set(&a, Some(1));
+        chunks.set(&b, Some(2));
+        chunks.set(&c, Some(3));
+        assert_eq!(chunks.num_chunks(), 2);
+
+        // Overwriting a value keeps the chunk.
+        chunks.set(&a, Some(4));
+        chunks.set(&a, None);
+        assert_eq!(chunks.num_chunks(), 2);
+
+        chunks.set(&b, None);
+        assert_eq!(chunks.num_chunks(), 1);
+        assert_eq!(chunks.get(&c), Some(3));
+
+        chunks.set(&c, None);
+        assert_eq!(chunks.num_chunks(), 0);
+        assert_eq!(chunks, Chunks::default());
+    }
+
+    /// Test that memory is only used for chunks that contain values.
+    #[test]
+    fn test_chunks_num_bytes() {
+        let mut chunks = Chunks::default();
+        assert_eq!(chunks.num_bytes(), 0);
+
+        // One value in each layer of a 60x60x40 grid.
+        for z in 0..40 {
+            chunks.set(&Point3::new(z, z, z), Some(z as usize));
+        }
+
+        let chunk_bytes = CHUNK_VOLUME * mem::size_of::<Option<usize>>();
+        assert_eq!(chunks.num_chunks(), 40);
+        assert!(chunks.num_bytes() >= 40 * chunk_bytes);
+        assert!(chunks.num_bytes() < 60 * 60 * 40 * mem::size_of::<Option<usize>>());
+    }
+}
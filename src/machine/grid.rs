@@ -104,33 +104,44 @@ impl Dir3 {
     }
 
     pub fn rotated_cw_xy(self) -> Dir3 {
-        let axis = match self.0 {
-            Axis3::X => Axis3::Y,
-            Axis3::Y => Axis3::X,
-            Axis3::Z => Axis3::Z,
-        };
-        let sign = match self.0 {
-            Axis3::X => self.1.invert(),
-            Axis3::Y | Axis3::Z => self.1,
-        };
-        Dir3(axis, sign)
+        self.rotated_cw(Axis3::X, Axis3::Y)
     }
 
     pub fn rotated_ccw_xy(self) -> Dir3 {
-        let axis = match self.0 {
-            Axis3::X => Axis3::Y,
-            Axis3::Y => Axis3::X,
-            Axis3::Z => Axis3::Z,
-        };
-        let sign = match self.0 {
-            Axis3::Y => self.1.invert(),
-            Axis3::X | Axis3::Z => self.1,
-        };
-        Dir3(axis, sign)
+        self.rotated_ccw(Axis3::X, Axis3::Y)
     }
 
     pub fn mirrored_y(self) -> Dir3 {
-        if self.0 == Axis3::X {
+        self.mirrored(Axis3::X)
+    }
+
+    /// Rotates clockwise in the plane spanned by the axes `a` and `b`. For
+    /// `a = X` and `b = Y`, this is a clockwise rotation when looking down
+    /// onto the XY plane.
+    pub fn rotated_cw(self, a: Axis3, b: Axis3) -> Dir3 {
+        if self.0 == a {
+            Dir3(b, self.1.invert())
+        } else if self.0 == b {
+            Dir3(a, self.1)
+        } else {
+            self
+        }
+    }
+
+    /// Inverse of `rotated_cw`.
+    pub fn rotated_ccw(self, a: Axis3, b: Axis3) -> Dir3 {
+        if self.0 == a {
+            Dir3(b, self.1)
+        } else if self.0 == b {
+            Dir3(a, self.1.invert())
+        } else {
+            self
+        }
+    }
+
+    /// Flips directions along the given axis.
+    pub fn mirrored(self, axis: Axis3) -> Dir3 {
+        if self.0 == axis {
             self.invert()
         } else {
             self
@@ -182,7 +193,7 @@ impl Dir3 {
     }
 }
 
-#[derive(PartialEq, Eq, Clone, Debug, Default, Serialize, Deserialize)]
+#[derive(PartialEq, Eq, Clone, Debug, Default, Hash, Serialize, Deserialize)]
 pub struct DirMap3<T>(pub [T; Dir3::NUM_INDICES]);
 
 #[allow(dead_code)]
@@ -206,6 +217,10 @@ impl<T> DirMap3<T> {
         self.0.iter()
     }
 
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.0.iter_mut()
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = (Dir3, &T)> {
         self.keys().zip(self.values())
     }
@@ -229,79 +244,6 @@ impl<T> IndexMut<Dir3> for DirMap3<T> {
     }
 }
 
-#[derive(PartialEq, Eq, Clone, Debug)]
-pub struct Grid3<T> {
-    size: Vector3,
-    data: Vec<T>,
-}
-
-impl<T> Default for Grid3<T> {
-    fn default() -> Self {
-        Grid3 {
-            size: Vector3::zeros(),
-            data: Vec::new(),
-        }
-    }
-}
-
-impl<T: Default + Copy> Grid3<T> {
-    pub fn new(size: Vector3) -> Grid3<T> {
-        assert!(size.x >= 0 && size.y >= 0 && size.z >= 0);
-        let n = (size.x * size.y * size.z) as usize;
-
-        Grid3 {
-            size,
-            data: vec![Default::default(); n],
-        }
-    }
-}
-
-impl<T> Grid3<T> {
-    pub fn node_index(&self, p: &Point3) -> usize {
-        debug_assert!(self.is_valid_pos(p));
-
-        let index = p.x + p.y * self.size.x + p.z * self.size.x * self.size.y;
-
-        index as usize
-    }
-
-    pub fn is_valid_pos(&self, p: &Point3) -> bool {
-        p.x >= 0
-            && p.x < self.size.x
-            && p.y >= 0
-            && p.y < self.size.y
-            && p.z >= 0
-            && p.z < self.size.z
-    }
-
-    pub fn get(&self, p: &Point3) -> Option<&T> {
-        if self.is_valid_pos(p) {
-            Some(&self[*p])
-        } else {
-            None
-        }
-    }
-
-    pub fn size(&self) -> Vector3 {
-        self.size
-    }
-}
-
-impl<T> Index<Point3> for Grid3<T> {
-    type Output = T;
-
-    fn index(&self, p: Point3) -> &T {
-        &self.data[self.node_index(&p)]
-    }
-}
-
-impl<T> IndexMut<Point3> for Grid3<T> {
-    fn index_mut(&mut self, p: Point3) -> &mut T {
-        let index = self.node_index(&p);
-        &mut self.data[index]
-    }
-}
-
 pub fn is_straight(dirs: &DirMap3<bool>) -> bool {
     let count: usize = dirs.values().map(|&enabled| enabled as usize).sum();
     let has_straight = Dir3::ALL.iter().any(|&dir| dirs[dir] && dirs[dir.invert()]);
@@ -9,6 +9,10 @@ use crate::machine::{grid, BlipKind};
 pub struct Level {
     pub size: grid::Vector3,
     pub spec: Spec,
+
+    /// Whether the player may change the size of the machine.
+    #[serde(default)]
+    pub resizable: bool,
 }
 
 impl Level {}
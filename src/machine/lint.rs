@@ -0,0 +1,500 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fmt;
+
+use crate::machine::grid::{Dir3, Point3};
+use crate::machine::{param, BlipKind, Block, BlockIndex, Machine};
+
+/// A likely mistake in a machine, found without running it.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Warning {
+    /// A pipe has an opening that faces an empty cell.
+    OpenPipe { pos: Point3, dir: Dir3 },
+
+    /// No wind can ever reach the output.
+    UnreachableOutput { pos: Point3, index: usize },
+
+    /// The level expects an input with this index, but there is none.
+    MissingInput { index: usize },
+
+    /// There is more than one input with the same index.
+    DuplicateInput {
+        positions: Vec<Point3>,
+        index: usize,
+    },
+
+    /// The input's index is not used by the level.
+    UnusedInput { pos: Point3, index: usize },
+
+    /// The level expects an output with this index, but there is none.
+    MissingOutput { index: usize },
+
+    /// There is more than one output with the same index.
+    DuplicateOutput {
+        positions: Vec<Point3>,
+        index: usize,
+    },
+
+    /// The output's index is not used by the level.
+    UnusedOutput { pos: Point3, index: usize },
+
+    /// A block spawns blips directly into a solid block.
+    SpawnIntoSolid { pos: Point3, dir: Dir3 },
+
+    /// A block can only be activated by blips, but none of its buttons can
+    /// be reached by a blip.
+    NeverActivated { pos: Point3 },
+}
+
+impl Warning {
+    /// Grid positions of the blocks that the warning is about.
+    pub fn positions(&self) -> Vec<Point3> {
+        match self {
+            Warning::OpenPipe { pos, .. } => vec![*pos],
+            Warning::UnreachableOutput { pos, .. } => vec![*pos],
+            Warning::MissingInput { .. } => Vec::new(),
+            Warning::DuplicateInput { positions, .. } => positions.clone(),
+            Warning::UnusedInput { pos, .. } => vec![*pos],
+            Warning::MissingOutput { .. } => Vec::new(),
+            Warning::DuplicateOutput { positions, .. } => positions.clone(),
+            Warning::UnusedOutput { pos, .. } => vec![*pos],
+            Warning::SpawnIntoSolid { pos, .. } => vec![*pos],
+            Warning::NeverActivated { pos } => vec![*pos],
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::OpenPipe { pos, dir } => write!(
+                f,
+                "Pipe at {} has an opening towards {} that leads nowhere",
+                format_pos(pos),
+                param::dir_name(*dir)
+            ),
+            Warning::UnreachableOutput { pos, index } => write!(
+                f,
+                "Output {} at {} can not be reached by wind",
+                index,
+                format_pos(pos)
+            ),
+            Warning::MissingInput { index } => write!(f, "Input {} is missing", index),
+            Warning::DuplicateInput { positions, index } => {
+                write!(f, "Input {} exists {} times", index, positions.len())
+            }
+            Warning::UnusedInput { pos, index } => write!(
+                f,
+                "Input {} at {} is not used by the level",
+                index,
+                format_pos(pos)
+            ),
+            Warning::MissingOutput { index } => write!(f, "Output {} is missing", index),
+            Warning::DuplicateOutput { positions, index } => {
+                write!(f, "Output {} exists {} times", index, positions.len())
+            }
+            Warning::UnusedOutput { pos, index } => write!(
+                f,
+                "Output {} at {} is not used by the level",
+                index,
+                format_pos(pos)
+            ),
+            Warning::SpawnIntoSolid { pos, dir } => write!(
+                f,
+                "Block at {} spawns blips towards {} into a solid block",
+                format_pos(pos),
+                param::dir_name(*dir)
+            ),
+            Warning::NeverActivated { pos } => write!(
+                f,
+                "Block at {} can never be activated by a blip",
+                format_pos(pos)
+            ),
+        }
+    }
+}
+
+fn format_pos(pos: &Point3) -> String {
+    format!("({}, {}, {})", pos.x, pos.y, pos.z)
+}
+
+/// Checks the machine for common mistakes.
+pub fn lint(machine: &Machine) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    let portal_partners = machine.portal_partners();
+
+    lint_open_pipes(machine, &mut warnings);
+    lint_unreachable_outputs(machine, &portal_partners, &mut warnings);
+    lint_inputs_outputs(machine, &mut warnings);
+    lint_spawns_into_solid(machine, &portal_partners, &mut warnings);
+    lint_never_activated(machine, &portal_partners, &mut warnings);
+
+    warnings
+}
+
+fn is_pipe(block: &Block) -> bool {
+    match block {
+        Block::Pipe(_, _)
+        | Block::PipeMergeXY
+        | Block::GeneralPipe(_)
+        | Block::FunnelXY { .. }
+        | Block::PipeButton { .. }
+        | Block::Delay { .. } => true,
+        _ => false,
+    }
+}
+
+fn lint_open_pipes(machine: &Machine, warnings: &mut Vec<Warning>) {
+    for (_, (pos, placed_block)) in machine.iter_blocks() {
+        if !is_pipe(&placed_block.block) {
+            continue;
+        }
+
+        for &dir in &Dir3::ALL {
+            if placed_block.block.has_wind_hole(dir, false)
+                && placed_block.block.can_connect_by_pipe(dir)
+                && !machine.is_block_at(&(pos + dir.to_vector()))
+            {
+                warnings.push(Warning::OpenPipe { pos: *pos, dir });
+            }
+        }
+    }
+}
+
+/// Returns the positions of all blocks that wind can flow into, in any
+/// activation state of the blocks.
+fn wind_reachable(
+    machine: &Machine,
+    portal_partners: &BTreeMap<BlockIndex, BlockIndex>,
+) -> HashSet<Point3> {
+    let mut reached = HashSet::new();
+    let mut queue = VecDeque::new();
+
+    for (_, (pos, placed_block)) in machine.iter_blocks() {
+        for &dir in &Dir3::ALL {
+            if placed_block.block.has_wind_source(dir) {
+                queue.push_back((*pos, dir));
+            }
+        }
+    }
+
+    // Each entry is a block that wind flows out of, and the direction of
+    // the flow. Linked portals face in opposite directions, so the flow
+    // direction stays the same when passing through them.
+    while let Some((from_pos, flow_dir)) = queue.pop_front() {
+        let (pos, block) = match machine.neighbor(&from_pos, flow_dir, portal_partners) {
+            Some((pos, placed_block)) => (*pos, &placed_block.block),
+            None => continue,
+        };
+
+        let can_enter = [false, true]
+            .iter()
+            .any(|&activated| block.has_wind_hole_in(flow_dir.invert(), activated));
+        if !can_enter || !reached.insert(pos) {
+            continue;
+        }
+
+        for &dir in &Dir3::ALL {
+            let can_leave = [false, true]
+                .iter()
+                .any(|&activated| block.has_wind_hole_out(dir, activated));
+            if can_leave {
+                queue.push_back((pos, dir));
+            }
+        }
+    }
+
+    reached
+}
+
+fn lint_unreachable_outputs(
+    machine: &Machine,
+    portal_partners: &BTreeMap<BlockIndex, BlockIndex>,
+    warnings: &mut Vec<Warning>,
+) {
+    let reached = wind_reachable(machine, portal_partners);
+
+    for (_, (pos, placed_block)) in machine.iter_blocks() {
+        if let Block::Output { index, .. } = placed_block.block {
+            if !reached.contains(pos) {
+                warnings.push(Warning::UnreachableOutput { pos: *pos, index });
+            }
+        }
+    }
+}
+
+fn lint_inputs_outputs(machine: &Machine, warnings: &mut Vec<Warning>) {
+    let spec = match &machine.level {
+        Some(level) => &level.spec,
+        None => return,
+    };
+
+    let mut inputs = vec![Vec::new(); spec.input_dim()];
+    let mut outputs = vec![Vec::new(); spec.output_dim()];
+
+    for (_, (pos, placed_block)) in machine.iter_blocks() {
+        match placed_block.block {
+            Block::Input { index, .. } => match inputs.get_mut(index) {
+                Some(positions) => positions.push(*pos),
+                None => warnings.push(Warning::UnusedInput { pos: *pos, index }),
+            },
+            Block::Output { index, .. } => match outputs.get_mut(index) {
+                Some(positions) => positions.push(*pos),
+                None => warnings.push(Warning::UnusedOutput { pos: *pos, index }),
+            },
+            _ => (),
+        }
+    }
+
+    for (index, positions) in inputs.into_iter().enumerate() {
+        if positions.is_empty() {
+            warnings.push(Warning::MissingInput { index });
+        } else if positions.len() > 1 {
+            warnings.push(Warning::DuplicateInput { positions, index });
+        }
+    }
+
+    for (index, positions) in outputs.into_iter().enumerate() {
+        if positions.is_empty() {
+            warnings.push(Warning::MissingOutput { index });
+        } else if positions.len() > 1 {
+            warnings.push(Warning::DuplicateOutput { positions, index });
+        }
+    }
+}
+
+fn lint_spawns_into_solid(
+    machine: &Machine,
+    portal_partners: &BTreeMap<BlockIndex, BlockIndex>,
+    warnings: &mut Vec<Warning>,
+) {
+    for (_, (pos, placed_block)) in machine.iter_blocks() {
+        for &dir in &Dir3::ALL {
+            if !placed_block.block.has_blip_spawn(dir) {
+                continue;
+            }
+
+            let is_solid = machine
+                .neighbor(pos, dir, portal_partners)
+                .map_or(false, |(_, neighbor)| neighbor.block == Block::Solid);
+            if is_solid {
+                warnings.push(Warning::SpawnIntoSolid { pos: *pos, dir });
+            }
+        }
+    }
+}
+
+fn lint_never_activated(
+    machine: &Machine,
+    portal_partners: &BTreeMap<BlockIndex, BlockIndex>,
+    warnings: &mut Vec<Warning>,
+) {
+    let kinds = [BlipKind::A, BlipKind::B];
+
+    for (_, (pos, placed_block)) in machine.iter_blocks() {
+        let block = &placed_block.block;
+
+        // Blocks that are activated by blips inside of them, or that can not
+        // be activated at all, are out of scope here.
+        if kinds.iter().any(|&kind| block.is_activatable(kind, None))
+            || !Dir3::ALL.iter().any(|&dir| block.has_button(dir))
+        {
+            continue;
+        }
+
+        let can_be_pressed = Dir3::ALL.iter().any(|&dir| {
+            block.has_button(dir)
+                && machine
+                    .neighbor(pos, dir, portal_partners)
+                    .map_or(false, |(_, neighbor)| neighbor.block.connects(dir.invert()))
+        });
+
+        if !can_be_pressed {
+            warnings.push(Warning::NeverActivated { pos: *pos });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::machine::level::{Level, Spec};
+    use crate::machine::string_util::machine_from_string;
+    use crate::machine::{grid, PlacedBlock};
+
+    /// Test that the linter finds pipes that lead nowhere, but not pipes that
+    /// are connected on both sides.
+    #[test]
+    fn test_lint_open_pipes() {
+        let m = "
+◉---
+";
+
+        let machine = machine_from_string(m);
+
+        let warnings = lint(&machine);
+        assert_eq!(
+            warnings,
+            vec![Warning::OpenPipe {
+                pos: Point3::new(3, 0, 0),
+                dir: Dir3::X_POS,
+            }]
+        );
+    }
+
+    /// Test that the linter follows wind through linked portals when looking
+    /// for outputs that wind can not reach.
+    #[test]
+    fn test_lint_unreachable_outputs() {
+        // The portal at (2,0) is linked to the one at (1,1), so the output at
+        // (3,1) gets wind. The output at (0,2) is not connected to anything.
+        let m = "
+◉-⊐
+ ⊏-
+";
+        let mut machine = machine_from_string(m);
+        for &(pos, index) in &[(Point3::new(3, 1, 0), 0), (Point3::new(0, 2, 0), 1)] {
+            machine.grow_to_include(&pos);
+            machine.set(
+                &pos,
+                Some(PlacedBlock {
+                    block: Block::Output {
+                        in_dir: Dir3::X_NEG,
+                        index,
+                    },
+                }),
+            );
+        }
+
+        let unreachable: Vec<_> = lint(&machine)
+            .into_iter()
+            .filter(|warning| match warning {
+                Warning::UnreachableOutput { .. } => true,
+                _ => false,
+            })
+            .collect();
+        assert_eq!(
+            unreachable,
+            vec![Warning::UnreachableOutput {
+                pos: Point3::new(0, 2, 0),
+                index: 1,
+            }]
+        );
+    }
+
+    /// Test that the linter compares inputs and outputs with the level.
+    #[test]
+    fn test_lint_inputs_outputs() {
+        let level = Level {
+            size: grid::Vector3::new(5, 3, 1),
+            spec: Spec::Id { dim: 2 },
+            resizable: false,
+        };
+        let mut machine = Machine::new_sandbox(level.size);
+        machine.level = Some(level);
+
+        let inputs = [(0, 0), (1, 0), (2, 3)];
+        let outputs = [(0, 0), (1, 0), (2, 1), (0, 5)];
+        for &(y, index) in &inputs {
+            let block = Block::Input {
+                out_dir: Dir3::X_POS,
+                index,
+            };
+            machine.set(&Point3::new(0, y, 0), Some(PlacedBlock { block }));
+        }
+        for &(y, index) in &outputs {
+            let block = Block::Output {
+                in_dir: Dir3::X_NEG,
+                index,
+            };
+            let x = if index == 5 { 3 } else { 4 };
+            machine.set(&Point3::new(x, y, 0), Some(PlacedBlock { block }));
+        }
+
+        let warnings: Vec<_> = lint(&machine)
+            .into_iter()
+            .filter(|warning| match warning {
+                Warning::UnreachableOutput { .. } | Warning::NeverActivated { .. } => false,
+                _ => true,
+            })
+            .collect();
+        let expected = vec![
+            Warning::UnusedInput {
+                pos: Point3::new(0, 2, 0),
+                index: 3,
+            },
+            Warning::UnusedOutput {
+                pos: Point3::new(3, 0, 0),
+                index: 5,
+            },
+            Warning::DuplicateInput {
+                positions: vec![Point3::new(0, 0, 0), Point3::new(0, 1, 0)],
+                index: 0,
+            },
+            Warning::MissingInput { index: 1 },
+            Warning::DuplicateOutput {
+                positions: vec![Point3::new(4, 0, 0), Point3::new(4, 1, 0)],
+                index: 0,
+            },
+        ];
+
+        assert_eq!(warnings.len(), expected.len(), "{:?}", warnings);
+        for warning in expected.iter() {
+            assert!(warnings.contains(warning), "{:?}", warning);
+        }
+    }
+
+    /// Test that the linter finds blips that are spawned into solid blocks.
+    #[test]
+    fn test_lint_spawns_into_solid() {
+        let m = "
+┣☐
+┣-
+";
+        let machine = machine_from_string(m);
+
+        let warnings: Vec<_> = lint(&machine)
+            .into_iter()
+            .filter(|warning| match warning {
+                Warning::SpawnIntoSolid { .. } => true,
+                _ => false,
+            })
+            .collect();
+        assert_eq!(
+            warnings,
+            vec![Warning::SpawnIntoSolid {
+                pos: Point3::new(0, 0, 0),
+                dir: Dir3::X_POS,
+            }]
+        );
+    }
+
+    /// Test that the linter finds buttons that no blip can reach, also when
+    /// they are behind a portal.
+    #[test]
+    fn test_lint_never_activated() {
+        // The blip wind source at (1,0) is pressed from the pipe to its left.
+        // The one at (1,1) is next to a pipe that does not lead to it, and the
+        // one at (2,2) is behind a portal, which only leads to its partner.
+        let m = "
+-[
+|[
+-⊐[
+";
+        let machine = machine_from_string(m);
+
+        let mut never_activated: Vec<_> = lint(&machine)
+            .into_iter()
+            .filter_map(|warning| match warning {
+                Warning::NeverActivated { pos } => Some(pos),
+                _ => None,
+            })
+            .collect();
+        never_activated.sort_by_key(|pos| pos.y);
+
+        assert_eq!(
+            never_activated,
+            vec![Point3::new(1, 1, 0), Point3::new(2, 2, 0)]
+        );
+    }
+}
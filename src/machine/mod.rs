@@ -1,20 +1,28 @@
+pub mod chunks;
 pub mod grid;
 pub mod level;
+pub mod lint;
+pub mod mods;
+pub mod network;
+pub mod param;
 #[cfg(test)]
 pub mod string_util;
 
+use std::collections::{BTreeMap, HashSet};
 use std::fmt;
+use std::mem;
 
 use serde::{Deserialize, Serialize};
 
 use crate::exec::BlipDieMode;
 use crate::util::vec_option::VecOption;
 
-use grid::{Axis3, Dir3, DirMap3, Grid3, Point3, Sign, Vector3};
+use chunks::Chunks;
+use grid::{Axis3, Dir3, DirMap3, Point3, Sign, Vector3};
 
 pub use level::Level;
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug, Serialize, Deserialize)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug, Hash, Serialize, Deserialize)]
 pub enum BlipKind {
     A,
     B,
@@ -48,8 +56,11 @@ impl fmt::Display for BlipKind {
 
 pub type TickNum = usize;
 
+/// Number of distinct channels that can be used to link blocks.
+pub const NUM_CHANNELS: usize = 8;
+
 /// Definition of a block in the machine.
-#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+#[derive(PartialEq, Eq, Clone, Debug, Hash, Serialize, Deserialize)]
 pub enum Block {
     Pipe(Dir3, Dir3),
     PipeMergeXY,
@@ -87,6 +98,9 @@ pub enum Block {
         flow_axis: Axis3,
         kind: Option<BlipKind>,
     },
+    /// Has buttons on both sides of the two axes orthogonal to `axis`,
+    /// except for the Z axis. Since `axis` is kept in the XY plane, a pipe
+    /// button has two buttons.
     PipeButton {
         axis: Axis3,
     },
@@ -99,6 +113,25 @@ pub enum Block {
     Delay {
         flow_dir: Dir3,
     },
+    Portal {
+        open_dir: Dir3,
+        channel: usize,
+    },
+    Transmitter {
+        in_dir: Dir3,
+        channel: usize,
+    },
+    Receiver {
+        out_dir: Dir3,
+        channel: usize,
+    },
+    Timer {
+        out_dir: Dir3,
+        kind: BlipKind,
+        period: usize,
+        phase: usize,
+    },
+    Custom(mods::Custom),
 }
 
 impl Block {
@@ -162,11 +195,16 @@ impl Block {
             Block::DetectorWindSource { .. } => "Blip detector".to_string(),
             Block::BlipDeleter { .. } => "Blip deleter".to_string(),
             Block::Delay { .. } => "Delay".to_string(),
+            Block::Portal { .. } => "Portal".to_string(),
+            Block::Transmitter { .. } => "Transmitter".to_string(),
+            Block::Receiver { .. } => "Receiver".to_string(),
+            Block::Timer { .. } => "Timer".to_string(),
+            Block::Custom(custom) => custom.block_name.clone(),
         }
     }
 
-    pub fn description(&self) -> &'static str {
-        match self {
+    pub fn description(&self) -> String {
+        let description = match self {
             Block::Pipe(_, _) => "Conducts both wind and blips.",
             Block::PipeMergeXY => "Four-way pipe.",
             Block::GeneralPipe(_) => "Conducts both wind and blips.",
@@ -199,7 +237,20 @@ impl Block {
             Block::DetectorWindSource { .. } => "Spawns one thrust of wind if it detects a blip in itself.",
             Block::BlipDeleter { .. } => "Destroys blips that are in its way, if activated.",
             Block::Delay { .. } => "Delays blip movement by one tick.",
-        }
+            Block::Portal { .. } => {
+                "Sends wind and blips to the other portal on the same channel.\n\nThe two portals need to face in opposite directions."
+            }
+            Block::Transmitter { .. } => {
+                "Activates all receivers on the same channel in the next tick, when activated by a blip."
+            }
+            Block::Receiver { .. } => {
+                "Produces a blip when a transmitter on the same channel was activated in the previous tick."
+            }
+            Block::Timer { .. } => "Produces a blip periodically, every few ticks.",
+            Block::Custom(custom) => return custom.description(),
+        };
+
+        description.to_string()
     }
 
     pub fn is_pipe(&self) -> bool {
@@ -224,6 +275,7 @@ impl Block {
             Block::BlipSpawn { kind, .. } => Some(*kind),
             Block::BlipDuplicator { kind, .. } => *kind,
             Block::DetectorBlipDuplicator { kind, .. } => *kind,
+            Block::Timer { kind, .. } => Some(*kind),
             _ => None,
         }
     }
@@ -233,11 +285,93 @@ impl Block {
             Block::BlipSpawn { ref mut kind, .. } => *kind = new_kind,
             Block::BlipDuplicator { ref mut kind, .. } => *kind = Some(new_kind),
             Block::DetectorBlipDuplicator { ref mut kind, .. } => *kind = Some(new_kind),
+            Block::Timer { ref mut kind, .. } => *kind = new_kind,
+            _ => (),
+        }
+    }
+
+    /// The number of ticks after which a timer repeats.
+    pub fn timer_period(&self) -> Option<usize> {
+        match self {
+            Block::Timer { period, .. } => Some((*period).max(1)),
+            _ => None,
+        }
+    }
+
+    /// Changes the period and phase of timers. The phase is kept within the
+    /// period.
+    pub fn change_timer(&mut self, period_delta: isize, phase_delta: isize) {
+        if let Block::Timer { period, phase, .. } = self {
+            let new_period = (*period as isize + period_delta).max(1);
+            let new_phase = (*phase as isize + phase_delta).rem_euclid(new_period);
+
+            *period = new_period as usize;
+            *phase = new_phase as usize;
+        }
+    }
+
+    pub fn channel(&self) -> Option<usize> {
+        match self {
+            Block::Portal { channel, .. } => Some(*channel),
+            Block::Transmitter { channel, .. } => Some(*channel),
+            Block::Receiver { channel, .. } => Some(*channel),
+            _ => None,
+        }
+    }
+
+    pub fn set_channel(&mut self, new_channel: usize) {
+        match self {
+            Block::Portal {
+                ref mut channel, ..
+            } => *channel = new_channel,
+            Block::Transmitter {
+                ref mut channel, ..
+            } => *channel = new_channel,
+            Block::Receiver {
+                ref mut channel, ..
+            } => *channel = new_channel,
             _ => (),
         }
     }
 
+    pub fn set_next_channel(&mut self) {
+        if let Some(channel) = self.channel() {
+            self.set_channel((channel + 1) % NUM_CHANNELS);
+        }
+    }
+
+    pub fn set_prev_channel(&mut self) {
+        if let Some(channel) = self.channel() {
+            self.set_channel((channel + NUM_CHANNELS - 1) % NUM_CHANNELS);
+        }
+    }
+
+    /// The direction in which a portal is connected to its partner.
+    pub fn portal_link_dir(&self) -> Option<Dir3> {
+        match self {
+            Block::Portal { open_dir, .. } => Some(open_dir.invert()),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the block is only defined for directions in the XY
+    /// plane, so that it cannot be rotated out of it.
+    pub fn is_xy_only(&self) -> bool {
+        match self {
+            Block::FunnelXY { .. } => true,
+            Block::PipeButton { .. } => true,
+            Block::DetectorWindSource { .. } => true,
+            _ => false,
+        }
+    }
+
     pub fn mutate_dirs(&mut self, f: impl Fn(Dir3) -> Dir3) {
+        // Pipe crossings can only be represented in the XY plane, so we
+        // convert them before they are rotated out of it.
+        if let Block::PipeMergeXY = self {
+            *self = self.clone().replace_deprecated();
+        }
+
         match self {
             Block::Pipe(dir_a, dir_b) => {
                 *dir_a = f(*dir_a);
@@ -289,6 +423,13 @@ impl Block {
             Block::Delay { flow_dir } => {
                 *flow_dir = f(*flow_dir);
             }
+            Block::Portal { open_dir, .. } => {
+                *open_dir = f(*open_dir);
+            }
+            Block::Transmitter { in_dir, .. } => *in_dir = f(*in_dir),
+            Block::Receiver { out_dir, .. } => *out_dir = f(*out_dir),
+            Block::Timer { out_dir, .. } => *out_dir = f(*out_dir),
+            Block::Custom(custom) => custom.mutate_dirs(f),
         }
     }
 
@@ -316,6 +457,11 @@ impl Block {
             Block::DetectorWindSource { axis } => dir.0 == *axis,
             Block::BlipDeleter { out_dirs } => dir != out_dirs.0 && dir != out_dirs.1,
             Block::Delay { flow_dir } => dir == *flow_dir || dir == flow_dir.invert(),
+            Block::Portal { open_dir, .. } => dir == *open_dir || dir == open_dir.invert(),
+            Block::Transmitter { in_dir, .. } => dir == *in_dir,
+            Block::Receiver { .. } => false,
+            Block::Timer { .. } => false,
+            Block::Custom(custom) => custom.has_hole(dir),
         }
     }
 
@@ -327,6 +473,7 @@ impl Block {
             Block::DetectorBlipDuplicator { flow_axis, .. } => dir.0 == *flow_axis,
             Block::Air => true,
             Block::Delay { flow_dir } => dir == flow_dir.invert(),
+            Block::Custom(custom) => custom.has_wind_hole_in(dir),
             _ => self.has_wind_hole(dir, activated),
         }
     }
@@ -340,6 +487,7 @@ impl Block {
                 dir != *button_dir
             }
             Block::Output { .. } => false,
+            Block::Transmitter { .. } => false,
             Block::Solid => false,
             Block::Air => false,
             Block::BlipDeleter { .. } => false,
@@ -356,6 +504,7 @@ impl Block {
             Block::PipeButton { .. } => true,
             Block::DetectorWindSource { axis } => dir.0 == *axis || dir == Dir3::Z_POS,
             Block::BlipDeleter { out_dirs, .. } => dir != out_dirs.0 && dir != out_dirs.1,
+            Block::Custom(custom) => custom.has_move_hole(dir),
             _ => self.has_wind_hole(dir, activated),
         }
     }
@@ -366,6 +515,9 @@ impl Block {
             Block::BlipDuplicator { out_dirs, .. } => dir == out_dirs.0 || dir == out_dirs.1,
             Block::DetectorBlipDuplicator { out_dir, .. } => dir == *out_dir,
             Block::PipeButton { .. } => true,
+            Block::Receiver { out_dir, .. } => dir == *out_dir,
+            Block::Timer { out_dir, .. } => dir == *out_dir,
+            Block::Custom(custom) => custom.has_blip_spawn(dir),
             _ => false,
         }
     }
@@ -375,6 +527,7 @@ impl Block {
             Block::WindSource => true,
             Block::BlipWindSource { button_dir, .. } => dir != *button_dir,
             Block::DetectorWindSource { axis } => dir.0 != *axis && dir != Dir3::Z_POS,
+            Block::Custom(custom) => custom.has_wind_source(dir),
             _ => false,
         }
     }
@@ -391,6 +544,7 @@ impl Block {
             Block::BlipDuplicator { .. } => Some(BlipDieMode::PressButton),
             Block::BlipWindSource { .. } => Some(BlipDieMode::PressButton),
             Block::Output { .. } => Some(BlipDieMode::PressButton),
+            Block::Transmitter { .. } => Some(BlipDieMode::PressButton),
             Block::PipeButton { axis } => {
                 if dir.map_or(false, |dir| dir.0 != *axis && dir.0 != Axis3::Z) {
                     Some(BlipDieMode::PressButton)
@@ -400,6 +554,7 @@ impl Block {
             }
             Block::BlipDeleter { .. } => Some(BlipDieMode::PressButton),
             Block::Delay { .. } => Some(BlipDieMode::PressButton),
+            Block::Custom(custom) => custom.is_blip_killer(dir),
             _ => None,
         }
     }
@@ -415,6 +570,7 @@ impl Block {
             }
             Block::BlipWindSource { .. } => dir.is_some(),
             Block::Output { .. } => dir.is_some(),
+            Block::Transmitter { .. } => dir.is_some(),
             Block::DetectorBlipDuplicator { kind, .. } => {
                 dir.is_some() && (*kind == None || *kind == Some(blip_kind))
             }
@@ -424,10 +580,31 @@ impl Block {
             Block::DetectorWindSource { .. } => true,
             Block::BlipDeleter { .. } => dir.is_some(),
             Block::Delay { flow_dir } => dir == Some(flow_dir.invert()),
+            Block::Custom(custom) => custom.is_activatable(blip_kind, dir),
             _ => false,
         }
     }
 
+    /// Returns true if wind or blips can pass between this block and its
+    /// neighbor in the given direction, in any activation state.
+    pub fn connects(&self, dir: Dir3) -> bool {
+        [false, true].iter().any(|&activated| {
+            self.has_wind_hole(dir, activated) || self.has_move_hole(dir, activated)
+        }) || self.has_blip_spawn(dir)
+            || self.has_button(dir)
+    }
+
+    /// Returns true if both blocks are of the same type, ignoring their
+    /// parameters and orientation.
+    pub fn has_same_type(&self, other: &Block) -> bool {
+        match (self, other) {
+            (Block::Custom(a), Block::Custom(b)) => {
+                a.mod_name == b.mod_name && a.block_name == b.block_name
+            }
+            (a, b) => mem::discriminant(a) == mem::discriminant(b),
+        }
+    }
+
     pub fn combine(&self, other: &Block) -> Option<Block> {
         match (self, other) {
             (Block::GeneralPipe(dirs_a), Block::GeneralPipe(dirs_b)) => {
@@ -440,13 +617,11 @@ impl Block {
     }
 
     pub fn can_connect_by_pipe(&self, dir_out: Dir3) -> bool {
-        let is_pipe = if let Block::GeneralPipe(_) = self {
-            true
-        } else {
-            false
-        };
-
-        is_pipe || self.has_wind_hole(dir_out, false) || self.has_wind_source(dir_out)
+        match self {
+            Block::GeneralPipe(_) => true,
+            Block::Portal { open_dir, .. } => dir_out == *open_dir,
+            _ => self.has_wind_hole(dir_out, false) || self.has_wind_source(dir_out),
+        }
     }
 }
 
@@ -459,11 +634,27 @@ pub type BlockIndex = usize;
 
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct Blocks {
-    // TODO: Make private -- this should not leak for when we extend to chunks
-    pub indices: Grid3<Option<BlockIndex>>,
+    size: Vector3,
+    indices: Chunks<BlockIndex>,
     pub data: VecOption<(Point3, PlacedBlock)>,
 }
 
+impl Blocks {
+    fn new(size: Vector3) -> Self {
+        assert!(size.x >= 0 && size.y >= 0 && size.z >= 0);
+
+        Self {
+            size,
+            indices: Chunks::default(),
+            data: VecOption::new(),
+        }
+    }
+}
+
+fn is_in_bounds(size: &Vector3, p: &Point3) -> bool {
+    p.x >= 0 && p.x < size.x && p.y >= 0 && p.y < size.y && p.z >= 0 && p.z < size.z
+}
+
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct Machine {
     pub blocks: Blocks,
@@ -476,40 +667,32 @@ impl Machine {
         slice: &[(Point3, PlacedBlock)],
         level: &Option<Level>,
     ) -> Self {
-        let mut indices = Grid3::new(*size);
-        let mut data = VecOption::new();
+        let mut machine = Machine {
+            blocks: Blocks::new(*size),
+            level: level.clone(),
+        };
 
         for (pos, placed_block) in slice {
             let mut placed_block = placed_block.clone();
             placed_block.block = placed_block.block.replace_deprecated();
 
-            indices[*pos] = Some(data.add((*pos, placed_block)));
+            machine.grow_to_include(pos);
+            machine.set(pos, Some(placed_block));
         }
 
-        let blocks = Blocks { indices, data };
-
-        Machine {
-            blocks,
-            level: level.clone(),
-        }
+        machine
     }
 
     pub fn new_sandbox(size: Vector3) -> Self {
         Self {
-            blocks: Blocks {
-                indices: Grid3::new(size),
-                data: VecOption::new(),
-            },
+            blocks: Blocks::new(size),
             level: None,
         }
     }
 
     pub fn new_from_level(level: Level) -> Self {
         let mut machine = Self {
-            blocks: Blocks {
-                indices: Grid3::new(level.size),
-                data: VecOption::new(),
-            },
+            blocks: Blocks::new(level.size),
             level: Some(level.clone()),
         };
 
@@ -545,11 +728,56 @@ impl Machine {
     }
 
     pub fn size(&self) -> Vector3 {
-        self.blocks.indices.size()
+        self.blocks.size
     }
 
     pub fn is_valid_pos(&self, p: &Point3) -> bool {
-        self.blocks.indices.is_valid_pos(p)
+        is_in_bounds(&self.size(), p)
+    }
+
+    /// Grows the machine so that the given non-negative position becomes
+    /// valid. Since blocks are stored sparsely, this is cheap.
+    pub fn grow_to_include(&mut self, p: &Point3) {
+        assert!(p.x >= 0 && p.y >= 0 && p.z >= 0);
+
+        let size = &mut self.blocks.size;
+        size.x = size.x.max(p.x + 1);
+        size.y = size.y.max(p.y + 1);
+        size.z = size.z.max(p.z + 1);
+    }
+
+    /// Returns true if the player may change the size of the machine. This is
+    /// always allowed in sandbox mode.
+    pub fn is_resizable(&self) -> bool {
+        self.level.as_ref().map_or(true, |level| level.resizable)
+    }
+
+    /// Moves all blocks by `shift` and changes the size of the machine.
+    ///
+    /// Returns the blocks that no longer fit into the machine, together with
+    /// their positions from before the shift.
+    pub fn resize(&mut self, shift: &Vector3, size: &Vector3) -> Vec<(Point3, PlacedBlock)> {
+        let old_blocks = mem::replace(&mut self.blocks, Blocks::new(*size));
+        let mut dropped = Vec::new();
+
+        for (_, (pos, placed_block)) in old_blocks.data.iter() {
+            let new_pos = pos + shift;
+
+            if self.is_valid_pos(&new_pos) {
+                self.set(&new_pos, Some(placed_block.clone()));
+            } else {
+                dropped.push((*pos, placed_block.clone()));
+            }
+        }
+
+        dropped
+    }
+
+    /// Returns the number of blocks that `resize` would drop.
+    pub fn num_blocks_outside(&self, shift: &Vector3, size: &Vector3) -> usize {
+        self.iter_blocks()
+            .filter(|(_, (pos, _))| !is_in_bounds(size, &(pos + shift)))
+            .count()
     }
 
     pub fn is_valid_layer(&self, layer: isize) -> bool {
@@ -561,31 +789,19 @@ impl Machine {
     }
 
     pub fn get(&self, p: &Point3) -> Option<&PlacedBlock> {
-        self.blocks
-            .indices
-            .get(p)
-            .and_then(|id| *id)
-            .map(|id| &self.blocks.data[id].1)
+        self.get_index(p).map(|id| &self.blocks.data[id].1)
     }
 
     pub fn get_mut(&mut self, p: &Point3) -> Option<&mut PlacedBlock> {
-        self.blocks
-            .indices
-            .get(p)
-            .and_then(|id| *id)
-            .map(move |id| &mut self.blocks.data[id].1)
+        self.get_index(p).map(move |id| &mut self.blocks.data[id].1)
     }
 
     pub fn get_index(&self, p: &Point3) -> Option<BlockIndex> {
-        self.blocks.indices.get(p).and_then(|id| *id)
+        self.blocks.indices.get(p)
     }
 
     pub fn get_with_index(&self, p: &Point3) -> Option<(BlockIndex, &PlacedBlock)> {
-        self.blocks
-            .indices
-            .get(p)
-            .and_then(|id| *id)
-            .map(|id| (id, &self.blocks.data[id].1))
+        self.get_index(p).map(|id| (id, &self.blocks.data[id].1))
     }
 
     pub fn block_at_index(&self, index: BlockIndex) -> &Block {
@@ -599,13 +815,12 @@ impl Machine {
 
         if let Some(block) = block {
             let id = self.blocks.data.add((*p, block));
-            self.blocks.indices[*p] = Some(id);
+            self.blocks.indices.set(p, Some(id));
         }
     }
 
     pub fn remove(&mut self, p: &Point3) -> Option<(BlockIndex, PlacedBlock)> {
-        if let Some(Some(id)) = self.blocks.indices.get(p).cloned() {
-            self.blocks.indices[*p] = None;
+        if let Some(id) = self.blocks.indices.set(p, None) {
             self.blocks.data.remove(id).map(|(data_pos, block)| {
                 assert!(data_pos == *p);
                 (id, block)
@@ -623,7 +838,7 @@ impl Machine {
         self.blocks.data.gc();
 
         for (index, (grid_pos, _)) in self.blocks.data.iter() {
-            self.blocks.indices[*grid_pos] = Some(index);
+            self.blocks.indices.set(grid_pos, Some(index));
         }
     }
 
@@ -634,6 +849,133 @@ impl Machine {
     pub fn num_blocks(&self) -> usize {
         self.blocks.data.len()
     }
+
+    /// Returns the number of chunks allocated for looking up blocks by their
+    /// position, and the approximate number of bytes they use.
+    pub fn index_memory(&self) -> (usize, usize) {
+        (
+            self.blocks.indices.num_chunks(),
+            self.blocks.indices.num_bytes(),
+        )
+    }
+
+    /// Returns the positions of all blocks that can be reached from the given
+    /// positions by following connections between neighboring blocks. Blocks
+    /// are neighbors if they are neighbors in the simulation, see
+    /// `neighbor_index`.
+    pub fn connected_blocks(&self, start: &[Point3]) -> Vec<Point3> {
+        let portal_partners = self.portal_partners();
+
+        let mut visited = HashSet::new();
+        let mut result = Vec::new();
+        let mut queue: Vec<Point3> = start
+            .iter()
+            .filter(|p| self.is_block_at(p))
+            .cloned()
+            .collect();
+
+        while let Some(pos) = queue.pop() {
+            if !visited.insert(pos) {
+                continue;
+            }
+            result.push(pos);
+
+            let block = &self.get(&pos).unwrap().block;
+
+            for &dir in &Dir3::ALL {
+                // Linked portals face in opposite directions, so `dir.invert()`
+                // is the direction back to us for them as well.
+                if let Some(neighbor_index) = self.neighbor_index(&pos, dir, &portal_partners) {
+                    let (neighbor_pos, neighbor) = &self.blocks.data[neighbor_index];
+
+                    if block.connects(dir) && neighbor.block.connects(dir.invert()) {
+                        queue.push(*neighbor_pos);
+                    }
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Returns the index of the block that the simulation considers to be the
+    /// neighbor of the block at `pos` in the given direction.
+    ///
+    /// This is the block next to it, except for portals: on their link side,
+    /// linked portals are neighbors of their partner, as given by
+    /// `portal_partners`, and never of the block directly behind them.
+    pub fn neighbor_index(
+        &self,
+        pos: &Point3,
+        dir: Dir3,
+        portal_partners: &BTreeMap<BlockIndex, BlockIndex>,
+    ) -> Option<BlockIndex> {
+        let (block_index, placed_block) = self.get_with_index(pos)?;
+
+        if placed_block.block.portal_link_dir() == Some(dir) {
+            return portal_partners.get(&block_index).cloned();
+        }
+
+        let (neighbor_index, neighbor) = self.get_with_index(&(pos + dir.to_vector()))?;
+
+        if neighbor.block.portal_link_dir() == Some(dir.invert()) {
+            None
+        } else {
+            Some(neighbor_index)
+        }
+    }
+
+    /// Returns the position and block that the simulation considers to be the
+    /// neighbor of the block at `pos` in the given direction, see
+    /// `neighbor_index`.
+    pub fn neighbor(
+        &self,
+        pos: &Point3,
+        dir: Dir3,
+        portal_partners: &BTreeMap<BlockIndex, BlockIndex>,
+    ) -> Option<&(Point3, PlacedBlock)> {
+        self.neighbor_index(pos, dir, portal_partners)
+            .map(|index| &self.blocks.data[index])
+    }
+
+    /// Returns the partner of each linked portal, see `portal_pairs`.
+    pub fn portal_partners(&self) -> BTreeMap<BlockIndex, BlockIndex> {
+        self.portal_pairs()
+            .into_iter()
+            .flat_map(|(a, b)| vec![(a, b), (b, a)])
+            .collect()
+    }
+
+    /// Returns the pairs of portals that are linked to each other.
+    ///
+    /// A channel links two portals only if there are exactly two portals on
+    /// it, and they face in opposite directions.
+    pub fn portal_pairs(&self) -> Vec<(BlockIndex, BlockIndex)> {
+        let mut channels: BTreeMap<usize, Vec<BlockIndex>> = BTreeMap::new();
+
+        for (block_index, (_, placed_block)) in self.iter_blocks() {
+            if let Block::Portal { channel, .. } = placed_block.block {
+                channels.entry(channel).or_default().push(block_index);
+            }
+        }
+
+        channels
+            .values()
+            .filter_map(|portals| match portals.as_slice() {
+                &[a, b] => {
+                    let link_a = self.block_at_index(a).portal_link_dir();
+                    let link_b = self.block_at_index(b).portal_link_dir();
+
+                    if link_a.map(Dir3::invert) == link_b {
+                        Some((a, b))
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            })
+            .collect()
+    }
 }
 
 /// Stores only the data necessary for restoring a machine.
@@ -642,6 +984,10 @@ pub struct SavedMachine {
     pub size: Vector3,
     pub block_data: Vec<(Point3, PlacedBlock)>,
     pub level: Option<Level>,
+
+    /// Names of the mods whose blocks are used in the machine.
+    #[serde(default)]
+    pub mods: Vec<String>,
 }
 
 impl SavedMachine {
@@ -651,17 +997,104 @@ impl SavedMachine {
             .data
             .iter()
             .map(|(_index, data)| data.clone())
-            .collect();
+            .collect::<Vec<_>>();
+
+        let mut mods = block_data
+            .iter()
+            .filter_map(|(_, placed_block)| match &placed_block.block {
+                Block::Custom(custom) => Some(custom.mod_name.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        mods.sort();
+        mods.dedup();
 
         Self {
             size: machine.size(),
             block_data,
             level: machine.level.clone(),
+            mods,
         }
     }
 
-    pub fn into_machine(self) -> Machine {
+    /// Restores a machine that does not use blocks from mods. Machines with
+    /// custom blocks need to be loaded with `Mods::resolve` instead, which
+    /// looks up the definitions of the blocks.
+    pub fn into_machine(self) -> Result<Machine, mods::Error> {
+        let unresolved =
+            self.block_data
+                .iter()
+                .find_map(|(_, placed_block)| match &placed_block.block {
+                    Block::Custom(custom) if custom.def.is_none() => Some(&custom.mod_name),
+                    _ => None,
+                });
+
+        if let Some(mod_name) = self.mods.first().or(unresolved) {
+            return Err(mods::Error::MissingMod(mod_name.clone()));
+        }
+
+        Ok(self.into_resolved_machine())
+    }
+
+    /// Restores the machine, assuming that all of its custom blocks have been
+    /// resolved.
+    pub(crate) fn into_resolved_machine(self) -> Machine {
         // TODO: Make use of moving
         Machine::new_from_block_data(&self.size, &self.block_data, &self.level)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::machine::string_util::machine_from_string;
+
+    /// Test that machines grow to include blocks, but never shrink by it.
+    #[test]
+    fn test_machine_grow_to_include() {
+        let mut machine = Machine::new_sandbox(grid::Vector3::new(2, 3, 1));
+
+        machine.grow_to_include(&Point3::new(1, 1, 0));
+        assert_eq!(machine.size(), grid::Vector3::new(2, 3, 1));
+
+        machine.grow_to_include(&Point3::new(20, 0, 2));
+        assert_eq!(machine.size(), grid::Vector3::new(21, 3, 3));
+        assert!(machine.is_valid_pos(&Point3::new(20, 2, 2)));
+
+        // Loading blocks outside of the given size grows the machine as well.
+        let blocks = vec![(
+            Point3::new(40, 1, 0),
+            PlacedBlock {
+                block: Block::Solid,
+            },
+        )];
+        let machine = Machine::new_from_block_data(&grid::Vector3::new(2, 2, 1), &blocks, &None);
+        assert_eq!(machine.size(), grid::Vector3::new(41, 2, 1));
+        assert_eq!(machine.get(&Point3::new(40, 1, 0)), Some(&blocks[0].1));
+    }
+
+    /// Test that connected blocks are found through linked portals, but not
+    /// through the back of portals, just like in the simulation.
+    #[test]
+    fn test_connected_blocks_portals() {
+        let m = "
+◉--⊐--⊏--
+";
+        let machine = machine_from_string(m);
+
+        let connected_xs = |x: isize| {
+            let mut xs: Vec<_> = machine
+                .connected_blocks(&[Point3::new(x, 0, 0)])
+                .into_iter()
+                .map(|p| p.x)
+                .collect();
+            xs.sort();
+            xs
+        };
+
+        assert_eq!(connected_xs(0), vec![0, 1, 2, 3, 6, 7, 8]);
+        assert_eq!(connected_xs(8), vec![0, 1, 2, 3, 6, 7, 8]);
+        assert_eq!(connected_xs(4), vec![4, 5]);
+    }
+}
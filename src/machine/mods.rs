@@ -0,0 +1,360 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::exec::BlipDieMode;
+use crate::machine::grid::{Dir3, DirMap3};
+use crate::machine::{BlipKind, Block, Machine, PlacedBlock, SavedMachine};
+
+/// How a custom block treats wind.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum CustomWind {
+    /// Passes on wind through its holes, like a pipe.
+    Conduct,
+
+    /// Produces wind out of its holes in every tick.
+    Source,
+
+    /// Produces wind out of its holes in ticks in which it is activated.
+    SourceWhenActivated,
+}
+
+impl Default for CustomWind {
+    fn default() -> Self {
+        CustomWind::Conduct
+    }
+}
+
+/// Definition of a kind of block that is loaded from a mod file.
+///
+/// Directions refer to the block in its default orientation, i.e. before it
+/// is rotated in the editor.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct CustomDef {
+    pub name: String,
+
+    #[serde(default)]
+    pub description: String,
+
+    /// Directions in which wind and blips can pass through the block.
+    #[serde(default)]
+    pub holes: Vec<Dir3>,
+
+    #[serde(default)]
+    pub wind: CustomWind,
+
+    /// Directions from which blips can enter to activate the block.
+    #[serde(default)]
+    pub buttons: Vec<Dir3>,
+
+    /// If set, only blips of this kind activate the block.
+    #[serde(default)]
+    pub activation_kind: Option<BlipKind>,
+
+    /// Whether blips that enter the block die, other than by pressing a
+    /// button.
+    #[serde(default)]
+    pub kills_blips: bool,
+
+    /// Directions in which a blip is produced when the block is activated.
+    /// The new blips have the kind of the activating blip.
+    #[serde(default)]
+    pub spawns: Vec<Dir3>,
+
+    /// RGB color of the block.
+    pub color: [f32; 3],
+}
+
+/// A block whose behavior is defined by a mod.
+///
+/// When saving, only the name of the mod and the block are stored. The
+/// definition needs to be looked up again when loading, see
+/// `Mods::resolve`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Custom {
+    pub mod_name: String,
+    pub block_name: String,
+
+    /// For each direction of the definition, the direction it is pointing to
+    /// in the machine.
+    pub orient: DirMap3<Dir3>,
+
+    #[serde(skip)]
+    pub def: Option<Arc<CustomDef>>,
+}
+
+impl PartialEq for Custom {
+    fn eq(&self, other: &Custom) -> bool {
+        self.mod_name == other.mod_name
+            && self.block_name == other.block_name
+            && self.orient == other.orient
+    }
+}
+
+impl Eq for Custom {}
+
+impl Hash for Custom {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.mod_name.hash(state);
+        self.block_name.hash(state);
+        self.orient.hash(state);
+    }
+}
+
+impl Custom {
+    pub fn new(mod_name: &str, def: Arc<CustomDef>) -> Self {
+        Self {
+            mod_name: mod_name.to_string(),
+            block_name: def.name.clone(),
+            orient: DirMap3::from_fn(|dir| dir),
+            def: Some(def),
+        }
+    }
+
+    fn has(&self, def_dirs: impl Fn(&CustomDef) -> &[Dir3], dir: Dir3) -> bool {
+        self.def.as_deref().map_or(false, |def| {
+            def_dirs(def)
+                .iter()
+                .any(|def_dir| self.orient[*def_dir] == dir)
+        })
+    }
+
+    fn dirs<'a>(&'a self, def_dirs: &'a [Dir3]) -> impl Iterator<Item = Dir3> + 'a {
+        def_dirs.iter().map(move |def_dir| self.orient[*def_dir])
+    }
+
+    pub fn wind(&self) -> CustomWind {
+        self.def
+            .as_ref()
+            .map_or(CustomWind::Conduct, |def| def.wind)
+    }
+
+    pub fn description(&self) -> String {
+        match &self.def {
+            Some(def) => format!("{}\n\nFrom mod: {}", def.description, self.mod_name),
+            None => format!("Missing mod: {}", self.mod_name),
+        }
+    }
+
+    pub fn mutate_dirs(&mut self, f: impl Fn(Dir3) -> Dir3) {
+        let orient = self.orient.clone();
+        self.orient = DirMap3::from_fn(|dir| f(orient[dir]));
+    }
+
+    pub fn has_hole(&self, dir: Dir3) -> bool {
+        self.has(|def| &def.holes[..], dir)
+    }
+
+    pub fn has_wind_hole_in(&self, dir: Dir3) -> bool {
+        self.wind() == CustomWind::Conduct && self.has_hole(dir)
+    }
+
+    pub fn has_move_hole(&self, dir: Dir3) -> bool {
+        self.has_hole(dir) || self.has_button(dir)
+    }
+
+    pub fn has_button(&self, dir: Dir3) -> bool {
+        self.has(|def| &def.buttons[..], dir)
+    }
+
+    pub fn has_blip_spawn(&self, dir: Dir3) -> bool {
+        self.has(|def| &def.spawns[..], dir)
+    }
+
+    pub fn has_wind_source(&self, dir: Dir3) -> bool {
+        self.wind() != CustomWind::Conduct && self.has_hole(dir)
+    }
+
+    pub fn is_blip_killer(&self, dir: Option<Dir3>) -> Option<BlipDieMode> {
+        if dir.map_or(false, |dir| self.has_button(dir)) {
+            Some(BlipDieMode::PressButton)
+        } else if self.def.as_ref().map_or(false, |def| def.kills_blips) {
+            Some(BlipDieMode::PopMiddle)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_activatable(&self, blip_kind: BlipKind, dir: Option<Dir3>) -> bool {
+        let kind_matches = self.def.as_ref().map_or(false, |def| {
+            def.activation_kind.map_or(true, |kind| kind == blip_kind)
+        });
+
+        kind_matches && dir.map_or(false, |dir| self.has_button(dir))
+    }
+
+    /// Directions of the holes in the machine.
+    pub fn hole_dirs(&self) -> Vec<Dir3> {
+        self.def
+            .as_ref()
+            .map_or(Vec::new(), |def| self.dirs(&def.holes).collect())
+    }
+
+    /// Directions of the buttons in the machine.
+    pub fn button_dirs(&self) -> Vec<Dir3> {
+        self.def
+            .as_ref()
+            .map_or(Vec::new(), |def| self.dirs(&def.buttons).collect())
+    }
+
+    /// Directions in which blips are produced when the block is activated.
+    pub fn spawn_dirs(&self) -> Vec<Dir3> {
+        self.def
+            .as_ref()
+            .map_or(Vec::new(), |def| self.dirs(&def.spawns).collect())
+    }
+}
+
+/// Contents of a mod file.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ModFile {
+    pub blocks: Vec<CustomDef>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, serde_json::Error),
+    MissingMod(String),
+    MissingBlock {
+        mod_name: String,
+        block_name: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(path, err) => write!(f, "Failed to read {:?}: {}", path, err),
+            Error::Parse(path, err) => write!(f, "Failed to parse mod file {:?}: {}", path, err),
+            Error::MissingMod(mod_name) => write!(
+                f,
+                "The machine uses blocks from mod `{}', which is not loaded",
+                mod_name
+            ),
+            Error::MissingBlock {
+                mod_name,
+                block_name,
+            } => write!(
+                f,
+                "The machine uses block `{}' from mod `{}', which does not define it",
+                block_name, mod_name,
+            ),
+        }
+    }
+}
+
+/// Blocks defined by mods. The name of a mod is the file stem of its file in
+/// the mods directory.
+#[derive(Clone, Debug, Default)]
+pub struct Mods {
+    mods: BTreeMap<String, Vec<Arc<CustomDef>>>,
+}
+
+impl Mods {
+    /// Loads all `.json` files in the given directory as mods. A missing
+    /// directory just means that there are no mods.
+    pub fn load_dir(dir: &Path) -> Result<Mods, Error> {
+        let mut mods = Mods::default();
+
+        if !dir.is_dir() {
+            return Ok(mods);
+        }
+
+        let entries = fs::read_dir(dir).map_err(|err| Error::Io(dir.to_path_buf(), err))?;
+
+        for entry in entries {
+            let path = entry
+                .map_err(|err| Error::Io(dir.to_path_buf(), err))?
+                .path();
+
+            if path
+                .extension()
+                .map_or(true, |extension| extension != "json")
+            {
+                continue;
+            }
+
+            let mod_name = match path.file_stem() {
+                Some(stem) => stem.to_string_lossy().to_string(),
+                None => continue,
+            };
+
+            let file = File::open(&path).map_err(|err| Error::Io(path.clone(), err))?;
+            let mod_file: ModFile = serde_json::from_reader(BufReader::new(file))
+                .map_err(|err| Error::Parse(path.clone(), err))?;
+
+            info!(
+                "Loaded mod `{}' with {} blocks",
+                mod_name,
+                mod_file.blocks.len()
+            );
+
+            mods.add(&mod_name, mod_file);
+        }
+
+        Ok(mods)
+    }
+
+    pub fn add(&mut self, mod_name: &str, mod_file: ModFile) {
+        self.mods.insert(
+            mod_name.to_string(),
+            mod_file.blocks.into_iter().map(Arc::new).collect(),
+        );
+    }
+
+    pub fn get(&self, mod_name: &str, block_name: &str) -> Result<Arc<CustomDef>, Error> {
+        let defs = self
+            .mods
+            .get(mod_name)
+            .ok_or_else(|| Error::MissingMod(mod_name.to_string()))?;
+
+        defs.iter()
+            .find(|def| def.name == block_name)
+            .cloned()
+            .ok_or_else(|| Error::MissingBlock {
+                mod_name: mod_name.to_string(),
+                block_name: block_name.to_string(),
+            })
+    }
+
+    /// Returns one block for each kind of block that is defined by mods, e.g.
+    /// for placing in the editor.
+    pub fn prototypes(&self) -> Vec<Block> {
+        self.mods
+            .iter()
+            .flat_map(|(mod_name, defs)| {
+                defs.iter()
+                    .map(move |def| Block::Custom(Custom::new(mod_name, def.clone())))
+            })
+            .collect()
+    }
+
+    /// Looks up the definitions of all custom blocks in a saved machine.
+    pub fn resolve(&self, saved_machine: SavedMachine) -> Result<Machine, Error> {
+        if let Some(mod_name) = saved_machine
+            .mods
+            .iter()
+            .find(|mod_name| !self.mods.contains_key(*mod_name))
+        {
+            return Err(Error::MissingMod(mod_name.clone()));
+        }
+
+        let mut saved_machine = saved_machine;
+
+        for (_, PlacedBlock { block }) in saved_machine.block_data.iter_mut() {
+            if let Block::Custom(custom) = block {
+                custom.def = Some(self.get(&custom.mod_name, &custom.block_name)?);
+            }
+        }
+
+        Ok(saved_machine.into_resolved_machine())
+    }
+}
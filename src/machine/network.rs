@@ -0,0 +1,222 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::exec::anim::WindDeadend;
+use crate::machine::grid::{Dir3, Point3};
+use crate::machine::{Block, Machine};
+
+/// A connected component of pipes in a machine.
+#[derive(Clone, Debug)]
+pub struct Network {
+    /// Positions of the pipes in the network.
+    pub blocks: Vec<Point3>,
+
+    /// Positions of the wind sources whose wind can reach the network,
+    /// possibly by passing through other blocks first.
+    pub sources: Vec<Point3>,
+
+    /// Openings through which wind leaves the network without entering
+    /// another block.
+    pub deadends: Vec<(Point3, Dir3, WindDeadend)>,
+}
+
+/// Can `block` be part of a pipe network?
+fn is_conduit(block: &Block) -> bool {
+    match block {
+        Block::FunnelXY { .. } | Block::Portal { .. } => true,
+        _ => block.is_pipe(),
+    }
+}
+
+fn any_activation(f: impl Fn(bool) -> bool) -> bool {
+    f(false) || f(true)
+}
+
+/// Returns the pipe network that the block at `pos` belongs to, if it is a
+/// pipe.
+///
+/// Pipes belong to the same network if they are linked through wind holes,
+/// regardless of the direction in which wind can flow between them. Linked
+/// portals connect the network to their partner.
+pub fn network_at(machine: &Machine, pos: &Point3) -> Option<Network> {
+    if !machine.get(pos).map_or(false, |b| is_conduit(&b.block)) {
+        return None;
+    }
+
+    let portal_partners = machine.portal_partners();
+
+    let mut visited = HashSet::new();
+    let mut blocks = Vec::new();
+    let mut deadends = Vec::new();
+    let mut queue = vec![*pos];
+
+    while let Some(pos) = queue.pop() {
+        if !visited.insert(pos) {
+            continue;
+        }
+        blocks.push(pos);
+
+        let block = &machine.get(&pos).unwrap().block;
+
+        for &dir in &Dir3::ALL {
+            if !any_activation(|activated| block.has_wind_hole(dir, activated)) {
+                continue;
+            }
+
+            let neighbor = machine
+                .neighbor(&pos, dir, &portal_partners)
+                .map(|(neighbor_pos, placed_block)| (*neighbor_pos, &placed_block.block));
+
+            match neighbor {
+                Some((neighbor_pos, neighbor))
+                    if is_conduit(neighbor)
+                        && any_activation(|activated| {
+                            neighbor.has_wind_hole(dir.invert(), activated)
+                        }) =>
+                {
+                    queue.push(neighbor_pos);
+                }
+                Some((_, neighbor)) => {
+                    let flows_out =
+                        any_activation(|activated| block.has_wind_hole_out(dir, activated));
+                    let flows_in = any_activation(|activated| {
+                        neighbor.has_wind_hole_in(dir.invert(), activated)
+                    });
+
+                    // Wind sources blow against the wind of the network
+                    // anyway, so they are not counted as dead ends.
+                    let is_source = neighbor.has_wind_source(dir.invert());
+
+                    if flows_out && !flows_in && !is_source {
+                        let deadend = if neighbor.is_pipe() {
+                            WindDeadend::Space
+                        } else {
+                            WindDeadend::Block
+                        };
+                        deadends.push((pos, dir, deadend));
+                    }
+                }
+                None => {
+                    if any_activation(|activated| block.has_wind_hole_out(dir, activated)) {
+                        deadends.push((pos, dir, WindDeadend::Space));
+                    }
+                }
+            }
+        }
+    }
+
+    let sources = upstream_sources(machine, &blocks);
+
+    Some(Network {
+        blocks,
+        sources,
+        deadends,
+    })
+}
+
+/// Returns the positions of the wind sources whose wind can reach any of the
+/// given blocks, following the direction in which wind flows.
+pub fn upstream_sources(machine: &Machine, targets: &[Point3]) -> Vec<Point3> {
+    let portal_partners = machine.portal_partners();
+
+    let mut visited: HashSet<Point3> = targets.iter().cloned().collect();
+    let mut queue: VecDeque<Point3> = targets.iter().cloned().collect();
+    let mut sources = Vec::new();
+
+    while let Some(pos) = queue.pop_front() {
+        let block = match machine.get(&pos) {
+            Some(placed_block) => &placed_block.block,
+            None => continue,
+        };
+
+        for &dir in &Dir3::ALL {
+            // Wind would flow from the neighbor into this block in the
+            // opposite direction.
+            if !any_activation(|activated| block.has_wind_hole_in(dir, activated)) {
+                continue;
+            }
+
+            let (neighbor_pos, neighbor) = match machine.neighbor(&pos, dir, &portal_partners) {
+                Some((neighbor_pos, placed_block)) => (*neighbor_pos, &placed_block.block),
+                None => continue,
+            };
+
+            if visited.contains(&neighbor_pos) {
+                continue;
+            }
+
+            if neighbor.has_wind_source(dir.invert()) {
+                visited.insert(neighbor_pos);
+                sources.push(neighbor_pos);
+            } else if any_activation(|activated| {
+                neighbor.has_wind_hole_out(dir.invert(), activated)
+            }) {
+                visited.insert(neighbor_pos);
+                queue.push_back(neighbor_pos);
+            }
+        }
+    }
+
+    sources
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::machine::string_util::machine_from_string;
+
+    /// Test that a pipe network spans funnels, and that its wind sources and
+    /// dead ends are found.
+    #[test]
+    fn test_pipe_network() {
+        let m = "
+◉--▷--
+";
+
+        let machine = machine_from_string(m);
+
+        assert!(network_at(&machine, &Point3::new(0, 0, 0)).is_none());
+
+        let network = network_at(&machine, &Point3::new(5, 0, 0)).unwrap();
+        let mut network_blocks = network.blocks.clone();
+        network_blocks.sort_by_key(|p| p.x);
+
+        assert_eq!(
+            network_blocks,
+            (1..=5).map(|x| Point3::new(x, 0, 0)).collect::<Vec<_>>()
+        );
+        assert_eq!(network.sources, vec![Point3::new(0, 0, 0)]);
+        assert_eq!(network.deadends.len(), 1);
+        assert_eq!(network.deadends[0].0, Point3::new(5, 0, 0));
+        assert_eq!(network.deadends[0].1, Dir3::X_POS);
+    }
+
+    /// Test that a pipe network follows portal links, and that pipes behind a
+    /// portal's link side form their own network without wind sources.
+    #[test]
+    fn test_pipe_network_portals() {
+        let m = "
+◉--⊐--⊏--
+";
+
+        let machine = machine_from_string(m);
+
+        let network = network_at(&machine, &Point3::new(8, 0, 0)).unwrap();
+        let mut network_xs: Vec<_> = network.blocks.iter().map(|p| p.x).collect();
+        network_xs.sort();
+
+        assert_eq!(network_xs, vec![1, 2, 3, 6, 7, 8]);
+        assert_eq!(network.sources, vec![Point3::new(0, 0, 0)]);
+        assert_eq!(network.deadends.len(), 1);
+        assert_eq!(network.deadends[0].0, Point3::new(8, 0, 0));
+        assert_eq!(network.deadends[0].1, Dir3::X_POS);
+
+        let behind = network_at(&machine, &Point3::new(4, 0, 0)).unwrap();
+        let mut behind_xs: Vec<_> = behind.blocks.iter().map(|p| p.x).collect();
+        behind_xs.sort();
+
+        assert_eq!(behind_xs, vec![4, 5]);
+        assert!(behind.sources.is_empty());
+        assert_eq!(behind.deadends.len(), 2);
+    }
+}
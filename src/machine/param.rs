@@ -0,0 +1,329 @@
+use std::fmt;
+
+use crate::machine::grid::{Axis3, Dir3, Sign};
+use crate::machine::{BlipKind, Block, NUM_CHANNELS};
+
+/// Value of a block parameter. Parameters allow inspecting and editing blocks
+/// generically, e.g. in the editor UI.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum ParamValue {
+    Bool(bool),
+    Dir(Dir3),
+    Axis(Axis3),
+    Kind(BlipKind),
+    OptionalKind(Option<BlipKind>),
+    Number(usize),
+    OptionalNumber(Option<usize>),
+}
+
+impl fmt::Display for ParamValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamValue::Bool(value) => write!(f, "{}", if *value { "yes" } else { "no" }),
+            ParamValue::Dir(dir) => f.write_str(dir_name(*dir)),
+            ParamValue::Axis(axis) => f.write_str(axis_name(*axis)),
+            ParamValue::Kind(kind) => write!(f, "{}", kind),
+            ParamValue::OptionalKind(kind) => match kind {
+                Some(kind) => write!(f, "{}", kind),
+                None => f.write_str("any"),
+            },
+            ParamValue::Number(number) => write!(f, "{}", number),
+            ParamValue::OptionalNumber(number) => match number {
+                Some(number) => write!(f, "{}", number),
+                None => f.write_str("unlimited"),
+            },
+        }
+    }
+}
+
+/// Mutable reference to a block parameter.
+enum ParamMut<'a> {
+    Bool(&'a mut bool),
+    Dir(&'a mut Dir3),
+    Axis(&'a mut Axis3),
+    Kind(&'a mut BlipKind),
+    OptionalKind(&'a mut Option<BlipKind>),
+    Number(&'a mut usize),
+    OptionalNumber(&'a mut Option<usize>),
+}
+
+impl<'a> ParamMut<'a> {
+    fn get(&self) -> ParamValue {
+        match self {
+            ParamMut::Bool(x) => ParamValue::Bool(**x),
+            ParamMut::Dir(x) => ParamValue::Dir(**x),
+            ParamMut::Axis(x) => ParamValue::Axis(**x),
+            ParamMut::Kind(x) => ParamValue::Kind(**x),
+            ParamMut::OptionalKind(x) => ParamValue::OptionalKind(**x),
+            ParamMut::Number(x) => ParamValue::Number(**x),
+            ParamMut::OptionalNumber(x) => ParamValue::OptionalNumber(**x),
+        }
+    }
+
+    fn set(&mut self, value: ParamValue) {
+        match (self, value) {
+            (ParamMut::Bool(x), ParamValue::Bool(y)) => **x = y,
+            (ParamMut::Dir(x), ParamValue::Dir(y)) => **x = y,
+            (ParamMut::Axis(x), ParamValue::Axis(y)) => **x = y,
+            (ParamMut::Kind(x), ParamValue::Kind(y)) => **x = y,
+            (ParamMut::OptionalKind(x), ParamValue::OptionalKind(y)) => **x = y,
+            (ParamMut::Number(x), ParamValue::Number(y)) => **x = y,
+            (ParamMut::OptionalNumber(x), ParamValue::OptionalNumber(y)) => **x = y,
+            _ => (),
+        }
+    }
+}
+
+pub fn dir_name(dir: Dir3) -> &'static str {
+    match (dir.0, dir.1) {
+        (Axis3::X, Sign::Pos) => "+x",
+        (Axis3::X, Sign::Neg) => "-x",
+        (Axis3::Y, Sign::Pos) => "+y",
+        (Axis3::Y, Sign::Neg) => "-y",
+        (Axis3::Z, Sign::Pos) => "+z",
+        (Axis3::Z, Sign::Neg) => "-z",
+    }
+}
+
+pub fn axis_name(axis: Axis3) -> &'static str {
+    match axis {
+        Axis3::X => "x",
+        Axis3::Y => "y",
+        Axis3::Z => "z",
+    }
+}
+
+fn param_muts(block: &mut Block) -> Vec<(&'static str, ParamMut)> {
+    match block {
+        Block::Pipe(dir_a, dir_b) => vec![
+            ("dir a", ParamMut::Dir(dir_a)),
+            ("dir b", ParamMut::Dir(dir_b)),
+        ],
+        Block::PipeMergeXY => vec![],
+        Block::GeneralPipe(dirs) => Dir3::ALL
+            .iter()
+            .zip(dirs.values_mut())
+            .map(|(dir, enabled)| (dir_name(*dir), ParamMut::Bool(enabled)))
+            .collect(),
+        Block::FunnelXY { flow_dir } => vec![("flow dir", ParamMut::Dir(flow_dir))],
+        Block::WindSource => vec![],
+        Block::BlipSpawn {
+            out_dir,
+            kind,
+            num_spawns,
+        } => vec![
+            ("out dir", ParamMut::Dir(out_dir)),
+            ("kind", ParamMut::Kind(kind)),
+            ("num spawns", ParamMut::OptionalNumber(num_spawns)),
+        ],
+        Block::BlipDuplicator { out_dirs, kind } => vec![
+            ("out dir a", ParamMut::Dir(&mut out_dirs.0)),
+            ("out dir b", ParamMut::Dir(&mut out_dirs.1)),
+            ("kind", ParamMut::OptionalKind(kind)),
+        ],
+        Block::BlipWindSource { button_dir } => vec![("button dir", ParamMut::Dir(button_dir))],
+        Block::Solid => vec![],
+        Block::Input { out_dir, index } => vec![
+            ("out dir", ParamMut::Dir(out_dir)),
+            ("index", ParamMut::Number(index)),
+        ],
+        Block::Output { in_dir, index } => vec![
+            ("in dir", ParamMut::Dir(in_dir)),
+            ("index", ParamMut::Number(index)),
+        ],
+        Block::Air => vec![],
+        Block::DetectorBlipDuplicator {
+            out_dir,
+            flow_axis,
+            kind,
+        } => vec![
+            ("out dir", ParamMut::Dir(out_dir)),
+            ("flow axis", ParamMut::Axis(flow_axis)),
+            ("kind", ParamMut::OptionalKind(kind)),
+        ],
+        Block::PipeButton { axis } => vec![("axis", ParamMut::Axis(axis))],
+        Block::DetectorWindSource { axis } => vec![("axis", ParamMut::Axis(axis))],
+        Block::BlipDeleter { out_dirs } => vec![
+            ("out dir a", ParamMut::Dir(&mut out_dirs.0)),
+            ("out dir b", ParamMut::Dir(&mut out_dirs.1)),
+        ],
+        Block::Delay { flow_dir } => vec![("flow dir", ParamMut::Dir(flow_dir))],
+        Block::Portal { open_dir, channel } => vec![
+            ("open dir", ParamMut::Dir(open_dir)),
+            ("channel", ParamMut::Number(channel)),
+        ],
+        Block::Transmitter { in_dir, channel } => vec![
+            ("in dir", ParamMut::Dir(in_dir)),
+            ("channel", ParamMut::Number(channel)),
+        ],
+        Block::Receiver { out_dir, channel } => vec![
+            ("out dir", ParamMut::Dir(out_dir)),
+            ("channel", ParamMut::Number(channel)),
+        ],
+        Block::Timer {
+            out_dir,
+            kind,
+            period,
+            phase,
+        } => vec![
+            ("out dir", ParamMut::Dir(out_dir)),
+            ("kind", ParamMut::Kind(kind)),
+            ("period", ParamMut::Number(period)),
+            ("phase", ParamMut::Number(phase)),
+        ],
+        Block::Custom(_) => vec![],
+    }
+}
+
+impl Block {
+    /// Returns the names and values of all parameters of this block.
+    pub fn params(&self) -> Vec<(&'static str, ParamValue)> {
+        let mut block = self.clone();
+        let params = param_muts(&mut block)
+            .into_iter()
+            .map(|(name, param)| (name, param.get()))
+            .collect();
+
+        params
+    }
+
+    /// Sets the parameter with the given name, if the block has a parameter
+    /// of this name and type. Returns true if anything was changed.
+    pub fn set_param(&mut self, name: &str, value: ParamValue) -> bool {
+        let previous = self.clone();
+
+        for (param_name, mut param) in param_muts(self) {
+            if param_name == name {
+                param.set(value);
+            }
+        }
+
+        self.make_params_valid();
+
+        // Blocks that only exist in the XY plane keep their previous value
+        // instead of being turned out of it.
+        if self.points_out_of_xy_plane() {
+            *self = previous;
+            return false;
+        }
+
+        *self != previous
+    }
+
+    fn points_out_of_xy_plane(&self) -> bool {
+        match self {
+            Block::FunnelXY { flow_dir } => flow_dir.0 == Axis3::Z,
+            Block::PipeButton { axis } | Block::DetectorWindSource { axis } => *axis == Axis3::Z,
+            _ => false,
+        }
+    }
+
+    /// Fixes up parameter combinations that the simulation does not support.
+    fn make_params_valid(&mut self) {
+        match self {
+            Block::BlipDuplicator { out_dirs, .. } | Block::BlipDeleter { out_dirs } => {
+                if out_dirs.0 == out_dirs.1 {
+                    out_dirs.1 = out_dirs.0.invert();
+                }
+            }
+            Block::DetectorBlipDuplicator {
+                out_dir, flow_axis, ..
+            } => {
+                if out_dir.0 == *flow_axis {
+                    *flow_axis = if out_dir.0 == Axis3::X {
+                        Axis3::Y
+                    } else {
+                        Axis3::X
+                    };
+                }
+            }
+            Block::BlipSpawn {
+                num_spawns: Some(num_spawns),
+                ..
+            } => {
+                *num_spawns = (*num_spawns).max(1);
+            }
+            Block::Portal { channel, .. }
+            | Block::Transmitter { channel, .. }
+            | Block::Receiver { channel, .. } => {
+                *channel = (*channel).min(NUM_CHANNELS - 1);
+            }
+            Block::Timer { period, phase, .. } => {
+                *period = (*period).max(1);
+                *phase %= *period;
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that parameters can be set by name, and that invalid values are
+    /// fixed up or rejected.
+    #[test]
+    fn test_set_param() {
+        let mut pipe = Block::Pipe(Dir3::X_NEG, Dir3::X_POS);
+
+        assert!(pipe.set_param("dir a", ParamValue::Dir(Dir3::Y_POS)));
+        assert_eq!(pipe, Block::Pipe(Dir3::Y_POS, Dir3::X_POS));
+
+        // Same value, unknown name and wrong type change nothing.
+        assert!(!pipe.set_param("dir a", ParamValue::Dir(Dir3::Y_POS)));
+        assert!(!pipe.set_param("flow dir", ParamValue::Dir(Dir3::Z_POS)));
+        assert!(!pipe.set_param("dir b", ParamValue::Number(3)));
+        assert_eq!(pipe, Block::Pipe(Dir3::Y_POS, Dir3::X_POS));
+
+        // Duplicators need two different out dirs.
+        let mut duplicator = Block::BlipDuplicator {
+            out_dirs: (Dir3::X_NEG, Dir3::X_POS),
+            kind: None,
+        };
+        assert!(duplicator.set_param("out dir b", ParamValue::Dir(Dir3::Y_POS)));
+        assert!(duplicator.set_param("out dir b", ParamValue::Dir(Dir3::X_NEG)));
+        assert_eq!(
+            duplicator,
+            Block::BlipDuplicator {
+                out_dirs: (Dir3::X_NEG, Dir3::X_POS),
+                kind: None,
+            }
+        );
+
+        // Channels are clamped, and the timer phase stays below the period.
+        let mut portal = Block::Portal {
+            open_dir: Dir3::X_POS,
+            channel: 0,
+        };
+        assert!(portal.set_param("channel", ParamValue::Number(100)));
+        assert_eq!(portal.params()[1].1, ParamValue::Number(NUM_CHANNELS - 1));
+
+        let mut timer = Block::Timer {
+            out_dir: Dir3::X_POS,
+            kind: BlipKind::A,
+            period: 4,
+            phase: 3,
+        };
+        assert!(timer.set_param("period", ParamValue::Number(0)));
+        assert_eq!(timer.timer_period(), Some(1));
+        assert_eq!(timer.params()[3].1, ParamValue::Number(0));
+
+        // Blocks that only exist in the XY plane can not point out of it.
+        let mut funnel = Block::FunnelXY {
+            flow_dir: Dir3::X_POS,
+        };
+        assert!(!funnel.set_param("flow dir", ParamValue::Dir(Dir3::Z_NEG)));
+        assert!(funnel.set_param("flow dir", ParamValue::Dir(Dir3::Y_NEG)));
+        assert_eq!(
+            funnel,
+            Block::FunnelXY {
+                flow_dir: Dir3::Y_NEG
+            }
+        );
+
+        let mut button = Block::PipeButton { axis: Axis3::X };
+        assert!(!button.set_param("axis", ParamValue::Axis(Axis3::Z)));
+        assert_eq!(button, Block::PipeButton { axis: Axis3::X });
+    }
+}
@@ -1,5 +1,5 @@
-use crate::machine::grid::{Dir3, Point3};
-use crate::machine::{BlipKind, Block};
+use crate::machine::grid::{Dir3, Point3, Vector3};
+use crate::machine::{BlipKind, Block, Machine, PlacedBlock};
 
 pub fn blocks_from_string(s: &str) -> Vec<(Point3, Block)> {
     s.lines()
@@ -13,6 +13,17 @@ pub fn blocks_from_string(s: &str) -> Vec<(Point3, Block)> {
         .collect()
 }
 
+/// Builds a machine from the blocks in the string, see `blocks_from_string`.
+/// The machine is just large enough to contain the blocks.
+pub fn machine_from_string(s: &str) -> Machine {
+    let blocks: Vec<_> = blocks_from_string(s)
+        .into_iter()
+        .map(|(pos, block)| (pos, PlacedBlock { block }))
+        .collect();
+
+    Machine::new_from_block_data(&Vector3::zeros(), &blocks, &None)
+}
+
 pub fn block_from_char(c: char) -> Option<Block> {
     if c == '.' || c == ' ' {
         return None;
@@ -109,6 +120,24 @@ pub fn block_from_char(c: char) -> Option<Block> {
 
         '☐' => Block::Solid,
 
+        '⊐' => Block::Portal {
+            open_dir: Dir3::X_NEG,
+            channel: 0,
+        },
+        '⊏' => Block::Portal {
+            open_dir: Dir3::X_POS,
+            channel: 0,
+        },
+
+        'T' => Block::Transmitter {
+            in_dir: Dir3::X_NEG,
+            channel: 0,
+        },
+        'R' => Block::Receiver {
+            out_dir: Dir3::X_POS,
+            channel: 0,
+        },
+
         _ => panic!("No block for {}", c),
     };
 
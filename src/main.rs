@@ -1,33 +1,237 @@
 // Needed for pareen stuff
 #![type_length_limit = "600000000"]
 
-//#![feature(type_alias_impl_trait)]
-
-#[macro_use]
-mod util;
-mod config;
-mod edit;
-mod edit_camera_view;
-mod exec;
-mod game;
-mod input_state;
-mod machine;
-mod render;
-
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+use std::process;
 use std::thread;
 use std::time::{Duration, Instant};
 
 use clap::{App, Arg};
 use coarse_prof::profile;
 use glium::glutin;
-use log::info;
+use log::{error, info};
+
+use ultimate_scale::{config, exec, game, input_state, machine};
 
+use exec::batch::{self, Batch};
+use exec::cycle::CycleDetector;
+use exec::{Exec, LevelProgress, LevelStatus};
 use game::Game;
 use input_state::InputState;
 use machine::level::{Level, Spec};
+use machine::mods::Mods;
 use machine::{grid, BlipKind, Machine, SavedMachine};
+
+/// Runs the machine without rendering and writes the signals of all its
+/// blocks to a VCD file.
+fn export_vcd(machine: Machine, path: &Path, max_ticks: usize) -> io::Result<()> {
+    let mut exec = Exec::new(machine, &mut rand::thread_rng());
+    let mut recorder = exec::vcd::Recorder::new_all_blocks(&exec);
+    recorder.record(&exec);
+
+    while exec.cur_tick() < max_ticks {
+        exec.update();
+        recorder.record(&exec);
+
+        let status = exec
+            .next_level_progress()
+            .map_or(LevelStatus::Running, LevelProgress::status);
+        if status != LevelStatus::Running {
+            info!("Level status after tick {}: {:?}", exec.cur_tick(), status);
+            break;
+        }
+    }
+
+    info!("Writing {} ticks to VCD file {:?}", exec.cur_tick(), path);
+    let file = File::create(path)?;
+    recorder.write(&mut BufWriter::new(file))
+}
+
+/// Runs the machine for the given number of ticks without rendering, and
+/// prints how long that took, including a breakdown of the update steps.
+fn bench(machine: Machine, num_ticks: usize) {
+    let mut exec = Exec::new(machine, &mut rand::thread_rng());
+    coarse_prof::reset();
+
+    let start = Instant::now();
+    for _ in 0..num_ticks {
+        exec.update();
+    }
+    let duration = start.elapsed();
+
+    println!(
+        "{} blocks, {} ticks in {:.3}s ({:.1}us per tick)",
+        exec.machine().num_blocks(),
+        num_ticks,
+        duration.as_secs_f64(),
+        duration.as_secs_f64() * 1e6 / num_ticks.max(1) as f64,
+    );
+    coarse_prof::write(&mut io::stdout()).unwrap();
+}
+
+/// Runs the machine on the level examples generated from many seeds in
+/// parallel, and prints a summary. Returns true if all examples were
+/// completed.
+fn run_batch(machine: Machine, config: &batch::Config) -> bool {
+    if machine.level.is_none() {
+        error!("Only machines with a level can be evaluated on examples");
+        return false;
+    }
+
+    info!(
+        "Evaluating {} examples on {} threads",
+        config.num_seeds, config.num_threads
+    );
+    let summary = Batch::spawn(&machine, config).wait();
+
+    println!(
+        "Completed {} of {} examples ({} failed, {} still running after {} ticks)",
+        summary.count(LevelStatus::Completed),
+        summary.results.len(),
+        summary.count(LevelStatus::Failed),
+        summary.count(LevelStatus::Running),
+        config.max_ticks,
+    );
+
+    if let Some((mean, max)) = summary.completed_ticks() {
+        println!("Ticks until completion: mean {:.1}, max {}", mean, max);
+    }
+
+    for result in summary.failures() {
+        match result.failure.as_ref() {
+            Some(failure) => println!("Seed {}: {}", result.seed, failure),
+            None => println!("Seed {}: still running", result.seed),
+        }
+    }
+
+    summary.is_success()
+}
+
+/// Number of blips that the outputs of the level have received so far.
+fn num_outputs_fed(exec: &Exec) -> usize {
+    exec.next_level_progress().map_or(0, |progress| {
+        progress.outputs.iter().map(|output| output.num_fed).sum()
+    })
+}
+
+/// Result of `verify`, which determines the exit status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Verdict {
+    /// The level was completed. For clock levels, the machine was also found
+    /// to keep producing the pattern forever.
+    Completed,
+
+    /// A clock level was completed, but the machine was not found to keep
+    /// producing the pattern forever.
+    CompletedOnce,
+
+    /// The level was not completed, or the machine has no level.
+    NotCompleted,
+}
+
+impl Verdict {
+    fn exit_code(self) -> i32 {
+        match self {
+            Verdict::Completed => 0,
+            Verdict::NotCompleted => 1,
+            Verdict::CompletedOnce => 2,
+        }
+    }
+}
+
+/// Runs the machine without rendering until the level is completed or failed,
+/// or until the machine is found to be looping without producing outputs.
+fn verify(machine: Machine, max_ticks: usize) -> Verdict {
+    let mut exec = Exec::new(machine, &mut rand::thread_rng());
+    let mut cycle_detector = CycleDetector::new(&exec);
+    cycle_detector.record(&exec);
+
+    // Number of outputs fed at each tick.
+    let mut num_outputs = vec![num_outputs_fed(&exec)];
+
+    let mut status = LevelStatus::Running;
+
+    while exec.cur_tick() < max_ticks {
+        exec.update();
+        num_outputs.push(num_outputs_fed(&exec));
+
+        status = exec
+            .next_level_progress()
+            .map_or(LevelStatus::Running, LevelProgress::status);
+        if status != LevelStatus::Running {
+            break;
+        }
+
+        if let Some(cycle) = cycle_detector.record(&exec) {
+            if num_outputs[cycle.start] == num_outputs[cycle.start + cycle.period] {
+                println!(
+                    "Looping: the machine repeats itself every {} ticks since tick {}, \
+                     without producing any outputs",
+                    cycle.period, cycle.start,
+                );
+                return Verdict::NotCompleted;
+            }
+        }
+    }
+
+    let progress = match exec.next_level_progress() {
+        Some(progress) => progress,
+        None => {
+            match cycle_detector.cycle() {
+                Some(cycle) => println!(
+                    "The machine repeats itself every {} ticks since tick {}",
+                    cycle.period, cycle.start,
+                ),
+                None => println!("No repetition found in {} ticks", exec.cur_tick()),
+            }
+            println!("The machine has no level to complete");
+            return Verdict::NotCompleted;
+        }
+    };
+
+    match status {
+        LevelStatus::Running => {
+            println!("Level still running after {} ticks", exec.cur_tick());
+            Verdict::NotCompleted
+        }
+        LevelStatus::Failed => {
+            if let Some(failure) = progress.failure.as_ref() {
+                println!("Failed: {}", failure);
+            }
+            Verdict::NotCompleted
+        }
+        LevelStatus::Completed => {
+            println!("Completed in {} ticks", exec.cur_tick());
+
+            // If a clock machine has started repeating itself before
+            // completing the level, and each repetition produces whole
+            // patterns, it will keep producing the correct outputs forever.
+            let pattern_len = match exec.machine().level.as_ref().map(|level| &level.spec) {
+                Some(Spec::Clock { pattern }) => pattern.len(),
+                _ => return Verdict::Completed,
+            };
+            if let Some(cycle) = cycle_detector.cycle() {
+                let num_per_period =
+                    num_outputs[cycle.start + cycle.period] - num_outputs[cycle.start];
+
+                if num_per_period > 0 && num_per_period % pattern_len == 0 {
+                    println!(
+                        "The machine repeats itself every {} ticks since tick {}, \
+                         producing the clock pattern forever",
+                        cycle.period, cycle.start,
+                    );
+                    return Verdict::Completed;
+                }
+            }
+
+            println!("The machine was not found to produce the clock pattern forever");
+            Verdict::CompletedOnce
+        }
+    }
+}
+
 fn main() {
     simple_logger::init_with_level(log::Level::Info).unwrap();
 
@@ -50,10 +254,195 @@ fn main() {
                 .help("Play a specific level")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("mods")
+                .long("mods")
+                .value_name("DIR")
+                .help("Load custom blocks from the mod files in the given directory")
+                .default_value("mods")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("lint")
+                .long("lint")
+                .help("Print warnings about likely mistakes in the machine and exit"),
+        )
+        .arg(
+            Arg::with_name("vcd")
+                .long("vcd")
+                .value_name("FILE")
+                .help("Run the machine without a window and write its signals to a VCD file")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("verify")
+                .long("verify")
+                .help("Run the machine without a window and check if it completes the level"),
+        )
+        .arg(
+            Arg::with_name("batch")
+                .long("batch")
+                .value_name("N")
+                .help("Run the machine without a window on N level examples and print a summary")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("threads")
+                .long("threads")
+                .value_name("N")
+                .help("Number of threads to use for --batch")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("bench")
+                .long("bench")
+                .help("Run the machine without a window for --ticks ticks and print timings"),
+        )
+        .arg(
+            Arg::with_name("ticks")
+                .long("ticks")
+                .value_name("N")
+                .help("Maximal number of ticks to run for when running without a window")
+                .default_value("1000")
+                .takes_value(true),
+        )
         .get_matches();
 
+    let mods_dir = args.value_of("mods").unwrap();
+    let mods = Mods::load_dir(Path::new(mods_dir)).unwrap_or_else(|err| {
+        error!("Failed to load mods: {}", err);
+        process::exit(1);
+    });
+
+    // TODO: Better level choosing
+    let level = if let Some(level) = args.value_of("level") {
+        if level == "id_3" {
+            Some(Level {
+                size: grid::Vector3::new(27, 27, 4),
+                spec: Spec::Id { dim: 3 },
+                resizable: false,
+            })
+        } else if level == "clock" {
+            Some(Level {
+                size: grid::Vector3::new(9, 9, 1),
+                spec: Spec::Clock {
+                    pattern: vec![BlipKind::A, BlipKind::B],
+                },
+                resizable: false,
+            })
+        } else if level == "o_beats_g" {
+            Some(Level {
+                size: grid::Vector3::new(19, 19, 2),
+                spec: Spec::BitwiseMax,
+                resizable: false,
+            })
+        } else if level == "make_it_3" {
+            Some(Level {
+                size: grid::Vector3::new(19, 19, 2),
+                spec: Spec::MakeItN { n: 3, max: 30 },
+                resizable: false,
+            })
+        } else if level == "make_it_10" {
+            Some(Level {
+                size: grid::Vector3::new(60, 60, 15),
+                spec: Spec::MakeItN { n: 10, max: 30 },
+                resizable: false,
+            })
+        } else if level == "mul_by_3" {
+            Some(Level {
+                size: grid::Vector3::new(30, 30, 30),
+                spec: Spec::MultiplyByN { n: 3, max: 15 },
+                resizable: false,
+            })
+        } else {
+            None
+        }
+    } else {
+        None
+    };
+
+    let initial_machine = if let Some(file) = args.value_of("file") {
+        info!("Loading machine from file `{}'", file);
+        let file = File::open(file).unwrap();
+        let reader = BufReader::new(file);
+        let saved_machine: SavedMachine = serde_json::from_reader(reader).unwrap();
+        mods.resolve(saved_machine).unwrap_or_else(|err| {
+            error!("Failed to load machine: {}", err);
+            process::exit(1);
+        })
+    } else if let Some(level) = level {
+        info!("Running level \"{}\"", level.spec.description());
+        Machine::new_from_level(level)
+    } else {
+        info!("Starting in sandbox mode");
+        let grid_size = grid::Vector3::new(60, 60, 40);
+        Machine::new_sandbox(grid_size)
+    };
+
+    if args.is_present("lint") {
+        let warnings = machine::lint::lint(&initial_machine);
+        for warning in warnings.iter() {
+            println!("{}", warning);
+        }
+
+        process::exit(if warnings.is_empty() { 0 } else { 1 });
+    }
+
+    let max_ticks = args
+        .value_of("ticks")
+        .unwrap()
+        .parse()
+        .unwrap_or_else(|err| {
+            error!("Invalid number of ticks: {}", err);
+            process::exit(1);
+        });
+
+    if args.is_present("verify") {
+        process::exit(verify(initial_machine, max_ticks).exit_code());
+    }
+
+    if let Some(num_seeds) = args.value_of("batch") {
+        let mut config = batch::Config {
+            max_ticks,
+            ..batch::Config::default()
+        };
+
+        config.num_seeds = num_seeds.parse().unwrap_or_else(|err| {
+            error!("Invalid number of examples: {}", err);
+            process::exit(1);
+        });
+
+        if let Some(num_threads) = args.value_of("threads") {
+            config.num_threads = num_threads.parse().unwrap_or_else(|err| {
+                error!("Invalid number of threads: {}", err);
+                process::exit(1);
+            });
+        }
+
+        process::exit(if run_batch(initial_machine, &config) {
+            0
+        } else {
+            1
+        });
+    }
+
+    if args.is_present("bench") {
+        bench(initial_machine, max_ticks);
+        process::exit(0);
+    }
+
+    if let Some(vcd_path) = args.value_of("vcd") {
+        if let Err(err) = export_vcd(initial_machine, Path::new(vcd_path), max_ticks) {
+            error!("Failed to export VCD file: {}", err);
+            process::exit(1);
+        }
+
+        process::exit(0);
+    }
+
     let mut config: config::Config = Default::default();
     config.render_pipeline.hdr = Some(1.0);
+    config.editor.mod_blocks = mods.prototypes();
     /*config.render_pipeline.shadow_mapping = None;
     config.render_pipeline.deferred_shading = None;
     config.render_pipeline.glow = None;*/
@@ -128,62 +517,6 @@ fn main() {
     let mut imgui_renderer = imgui_glium_renderer::Renderer::init(&mut imgui, &display)
         .expect("Failed to initialize imgui_glium_renderer");
 
-    // TODO: Better level choosing
-    let level = if let Some(level) = args.value_of("level") {
-        if level == "id_3" {
-            Some(Level {
-                size: grid::Vector3::new(27, 27, 4),
-                spec: Spec::Id { dim: 3 },
-            })
-        } else if level == "clock" {
-            Some(Level {
-                size: grid::Vector3::new(9, 9, 1),
-                spec: Spec::Clock {
-                    pattern: vec![BlipKind::A, BlipKind::B],
-                },
-            })
-        } else if level == "o_beats_g" {
-            Some(Level {
-                size: grid::Vector3::new(19, 19, 2),
-                spec: Spec::BitwiseMax,
-            })
-        } else if level == "make_it_3" {
-            Some(Level {
-                size: grid::Vector3::new(19, 19, 2),
-                spec: Spec::MakeItN { n: 3, max: 30 },
-            })
-        } else if level == "make_it_10" {
-            Some(Level {
-                size: grid::Vector3::new(60, 60, 15),
-                spec: Spec::MakeItN { n: 10, max: 30 },
-            })
-        } else if level == "mul_by_3" {
-            Some(Level {
-                size: grid::Vector3::new(30, 30, 30),
-                spec: Spec::MultiplyByN { n: 3, max: 15 },
-            })
-        } else {
-            None
-        }
-    } else {
-        None
-    };
-
-    let initial_machine = if let Some(file) = args.value_of("file") {
-        info!("Loading machine from file `{}'", file);
-        let file = File::open(file).unwrap();
-        let reader = BufReader::new(file);
-        let saved_machine: SavedMachine = serde_json::from_reader(reader).unwrap();
-        saved_machine.into_machine()
-    } else if let Some(level) = level {
-        info!("Running level \"{}\"", level.spec.description());
-        Machine::new_from_level(level)
-    } else {
-        info!("Starting in sandbox mode");
-        let grid_size = grid::Vector3::new(60, 60, 40);
-        Machine::new_sandbox(grid_size)
-    };
-
     let mut input_state = InputState::new(window);
 
     let mut game = Game::create(&display, &config, initial_machine).unwrap();
@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+
+/// How the blocks in one layer of the machine are shown.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum LayerVisibility {
+    Show,
+
+    /// Render the layer with dithering, so that it can be seen through.
+    Ghost,
+
+    Hide,
+}
+
+impl Default for LayerVisibility {
+    fn default() -> Self {
+        LayerVisibility::Show
+    }
+}
+
+/// How the layers other than the current one are shown.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum LayerFocus {
+    /// All layers are shown according to their visibility.
+    All,
+
+    /// Layers above the current layer are hidden.
+    Cutaway,
+
+    /// Only the current layer is shown normally, all other layers are
+    /// ghosted.
+    Isolate,
+}
+
+impl Default for LayerFocus {
+    fn default() -> Self {
+        LayerFocus::All
+    }
+}
+
+/// Settings for showing the layers of a machine, both while editing and
+/// during execution.
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct Layers {
+    /// Visibility of layers that have been changed by the user. Missing
+    /// layers are shown.
+    pub visibility: BTreeMap<isize, LayerVisibility>,
+
+    pub focus: LayerFocus,
+}
+
+impl Layers {
+    pub fn get(&self, layer: isize) -> LayerVisibility {
+        self.visibility.get(&layer).copied().unwrap_or_default()
+    }
+
+    pub fn set(&mut self, layer: isize, visibility: LayerVisibility) {
+        if visibility == LayerVisibility::Show {
+            self.visibility.remove(&layer);
+        } else {
+            self.visibility.insert(layer, visibility);
+        }
+    }
+
+    /// Returns how `layer` is to be rendered, taking the focus on the current
+    /// layer into account.
+    pub fn effective(&self, layer: isize, current_layer: isize) -> LayerVisibility {
+        let visibility = self.get(layer);
+
+        if layer == current_layer {
+            return visibility;
+        }
+
+        match self.focus {
+            LayerFocus::All => visibility,
+            LayerFocus::Cutaway if layer > current_layer => LayerVisibility::Hide,
+            LayerFocus::Cutaway => visibility,
+            LayerFocus::Isolate if visibility == LayerVisibility::Hide => visibility,
+            LayerFocus::Isolate => LayerVisibility::Ghost,
+        }
+    }
+
+    pub fn is_hidden(&self, layer: isize, current_layer: isize) -> bool {
+        self.effective(layer, current_layer) == LayerVisibility::Hide
+    }
+
+    pub fn is_ghost(&self, layer: isize, current_layer: isize) -> bool {
+        self.effective(layer, current_layer) == LayerVisibility::Ghost
+    }
+}
@@ -5,7 +5,7 @@ use nalgebra as na;
 use rendology::{basic_obj, line, BasicObj, Light};
 
 use crate::machine::grid::{self, Axis3, Dir3, Sign};
-use crate::machine::{BlipKind, Block, Machine, PlacedBlock};
+use crate::machine::{BlipKind, Block, Machine, PlacedBlock, NUM_CHANNELS};
 
 use crate::exec::anim::{AnimState, WindLife};
 use crate::exec::{Activation, Exec, LevelProgress, TickTime};
@@ -101,6 +101,19 @@ pub fn output_status_color(failed: bool, completed: bool) -> na::Vector3<f32> {
     })
 }
 
+pub fn channel_color(channel: usize) -> na::Vector3<f32> {
+    gamma_correct(&match channel % NUM_CHANNELS {
+        0 => na::Vector3::new(0.894, 0.102, 0.110),
+        1 => na::Vector3::new(0.216, 0.494, 0.722),
+        2 => na::Vector3::new(0.302, 0.686, 0.290),
+        3 => na::Vector3::new(0.596, 0.306, 0.639),
+        4 => na::Vector3::new(1.0, 0.498, 0.0),
+        5 => na::Vector3::new(1.0, 1.0, 0.2),
+        6 => na::Vector3::new(0.651, 0.337, 0.157),
+        _ => na::Vector3::new(0.969, 0.506, 0.749),
+    })
+}
+
 pub fn floor_color() -> na::Vector3<f32> {
     //gamma_correct(&na::Vector3::new(0.1608, 0.4235, 0.5725))
     //gamma_correct(&na::Vector3::new(0.3, 0.3, 0.3))
@@ -1344,6 +1357,272 @@ pub fn render_block(
                 out,
             );
         }
+        Block::Portal { open_dir, channel } => {
+            let is_wind_active = anim_state
+                .as_ref()
+                .map_or(false, |anim| anim.num_alive_out() > 0);
+
+            let color = block_color(&channel_color(channel), alpha);
+            let render_list = if is_wind_active {
+                &mut out.solid_glow
+            } else {
+                out.solid()
+            };
+
+            // The portal's gate is placed at the side that links to its
+            // partner.
+            let gate_transform = translation
+                * transform
+                * open_dir.invert().to_rotation_mat_x()
+                * na::Matrix4::new_translation(&na::Vector3::new(0.3, 0.0, 0.0));
+            let gate_scaling = na::Vector3::new(0.15, 0.8, 0.8);
+            render_list[BasicObj::Cube].add(basic_obj::Instance {
+                transform: gate_transform * na::Matrix4::new_nonuniform_scaling(&gate_scaling),
+                color,
+                ..Default::default()
+            });
+            render_outline(&gate_transform, &gate_scaling, alpha, out);
+
+            let inner_transform = translation * transform * open_dir.to_rotation_mat_x();
+            let inner_scaling = na::Vector3::new(0.45, 0.45, 0.45);
+            out.solid_dither[BasicObj::Cube].add(basic_obj::Instance {
+                transform: inner_transform * na::Matrix4::new_nonuniform_scaling(&inner_scaling),
+                color: block_color(&channel_color(channel), alpha * 0.7),
+                ..Default::default()
+            });
+            render_outline(&inner_transform, &inner_scaling, alpha, out);
+
+            render_half_pipe(
+                center,
+                transform,
+                open_dir,
+                &block_color(&pipe_color(), alpha),
+                out.solid(),
+            );
+        }
+        Block::Transmitter { in_dir, channel } => {
+            let activation = anim_state.and_then(|s| s.activation);
+            let next_activation = anim_state.and_then(|s| s.next_activation);
+
+            let render_list = if activation.is_some() {
+                &mut out.solid_glow
+            } else {
+                out.solid()
+            };
+
+            let cube_transform = translation * transform * in_dir.to_rotation_mat_x();
+            let scaling = na::Vector3::new(0.5, 0.6, 0.6);
+            render_list[BasicObj::Cube].add(basic_obj::Instance {
+                transform: cube_transform * na::Matrix4::new_nonuniform_scaling(&scaling),
+                color: block_color(&channel_color(channel), alpha),
+                ..Default::default()
+            });
+            render_outline(&cube_transform, &scaling, alpha, out);
+
+            let antenna_transform = translation
+                * transform
+                * na::Matrix4::new_translation(&na::Vector3::new(0.0, 0.0, 0.4));
+            let antenna_scaling = na::Vector3::new(0.08, 0.08, 0.3);
+            out.solid()[BasicObj::Cube].add(basic_obj::Instance {
+                transform: antenna_transform
+                    * na::Matrix4::new_nonuniform_scaling(&antenna_scaling),
+                color: block_color(&channel_color(channel), alpha),
+                ..Default::default()
+            });
+            render_outline(&antenna_transform, &antenna_scaling, alpha, out);
+
+            let button_length = button_length_anim(&activation, &next_activation, scaling.x)
+                .eval(tick_time.tick_progress());
+
+            render_bridge(
+                &Bridge {
+                    center: *center,
+                    dir: in_dir,
+                    offset: scaling.x / 2.0,
+                    length: button_length,
+                    size: 0.4,
+                    color: block_color(&button_color(), alpha),
+                },
+                transform,
+                out,
+            );
+        }
+        Block::Receiver { out_dir, channel } => {
+            let activation = anim_state.and_then(|s| s.activation);
+
+            let render_list = if activation.is_some() {
+                &mut out.solid_glow
+            } else {
+                out.solid()
+            };
+
+            let cube_transform = translation * transform * out_dir.to_rotation_mat_x();
+            let scaling = na::Vector3::new(0.5, 0.6, 0.6);
+            render_list[BasicObj::Cube].add(basic_obj::Instance {
+                transform: cube_transform * na::Matrix4::new_nonuniform_scaling(&scaling),
+                color: block_color(&channel_color(channel), alpha),
+                ..Default::default()
+            });
+            render_outline(&cube_transform, &scaling, alpha, out);
+
+            let dish_transform = translation
+                * transform
+                * na::Matrix4::new_translation(&na::Vector3::new(0.0, 0.0, 0.35));
+            let dish_scaling = na::Vector3::new(0.3, 0.3, 0.05);
+            out.solid()[BasicObj::Cube].add(basic_obj::Instance {
+                transform: dish_transform * na::Matrix4::new_nonuniform_scaling(&dish_scaling),
+                color: block_color(&channel_color(channel), alpha),
+                ..Default::default()
+            });
+            render_outline(&dish_transform, &dish_scaling, alpha, out);
+
+            let bridge_length = bridge_length_anim(0.05, 0.25, activation.is_some())
+                .eval(tick_time.tick_progress());
+
+            render_bridge(
+                &Bridge {
+                    center: *center,
+                    dir: out_dir,
+                    offset: scaling.x / 2.0,
+                    length: bridge_length,
+                    size: 0.25,
+                    color: block_color(&impatient_bridge_color(), alpha),
+                },
+                transform,
+                out,
+            );
+        }
+        Block::Timer {
+            out_dir,
+            kind,
+            period,
+            phase,
+        } => {
+            let activation = anim_state.and_then(|s| s.activation);
+            let scaling_anim = blip_spawn_scaling_anim(activation);
+
+            let cube_transform = translation * transform * out_dir.to_rotation_mat_x();
+            let size_anim =
+                scaling_anim.as_ref() * pareen::constant(na::Vector3::new(0.6, 0.6, 0.6));
+            let size = size_anim.eval(tick_time.tick_progress());
+
+            out.solid()[BasicObj::Cube].add(basic_obj::Instance {
+                transform: cube_transform * na::Matrix4::new_nonuniform_scaling(&size),
+                color: block_color(&blip_color(kind), alpha),
+                ..Default::default()
+            });
+            render_outline(&cube_transform, &size, alpha, out);
+
+            // The hand on top completes one revolution per period, pointing
+            // along the output direction whenever the timer fires.
+            let period = period.max(1) as f32;
+            let turns = (tick_time.to_f32() - phase as f32) / period;
+            let hand_transform = translation
+                * transform
+                * out_dir.to_rotation_mat_x()
+                * na::Matrix4::new_rotation(
+                    na::Vector3::z() * (-turns * 2.0 * std::f32::consts::PI),
+                )
+                * na::Matrix4::new_translation(&na::Vector3::new(0.12, 0.0, size.z / 2.0));
+            let hand_scaling = na::Vector3::new(0.25, 0.06, 0.03);
+            out.solid()[BasicObj::Cube].add(basic_obj::Instance {
+                transform: hand_transform * na::Matrix4::new_nonuniform_scaling(&hand_scaling),
+                color: block_color(&button_color(), alpha),
+                ..Default::default()
+            });
+            render_outline(&hand_transform, &hand_scaling, alpha, out);
+
+            let bridge_size_anim = scaling_anim.as_ref() * 0.25;
+            let bridge_length_anim = bridge_length_anim(0.05, 0.4, activation.is_some());
+
+            let bridge_anim =
+                bridge_size_anim
+                    .zip(bridge_length_anim)
+                    .map(|(bridge_size, bridge_length)| Bridge {
+                        center: *center,
+                        dir: out_dir,
+                        offset: size.x / 2.0,
+                        length: bridge_length,
+                        size: bridge_size,
+                        color: block_color(&patient_bridge_color(), alpha),
+                    });
+
+            render_bridge(&bridge_anim.eval(tick_time.tick_progress()), transform, out);
+        }
+        Block::Custom(ref custom) => {
+            let activation = anim_state.and_then(|s| s.activation);
+            let next_activation = anim_state.and_then(|s| s.next_activation);
+
+            // Blocks with missing definitions are shown in plain gray, so that
+            // they can at least be found and removed.
+            let color = custom
+                .def
+                .as_ref()
+                .map_or(na::Vector3::new(0.5, 0.5, 0.5), |def| {
+                    gamma_correct(&na::Vector3::new(def.color[0], def.color[1], def.color[2]))
+                });
+
+            let render_list = if activation.is_some() {
+                &mut out.solid_glow
+            } else {
+                out.solid()
+            };
+
+            let cube_transform = translation * transform;
+            let scaling = na::Vector3::new(0.6, 0.6, 0.6);
+            render_list[BasicObj::Cube].add(basic_obj::Instance {
+                transform: cube_transform * na::Matrix4::new_nonuniform_scaling(&scaling),
+                color: block_color(&color, alpha),
+                ..Default::default()
+            });
+            render_outline(&cube_transform, &scaling, alpha, out);
+
+            for dir in custom.hole_dirs() {
+                render_half_pipe(
+                    center,
+                    transform,
+                    dir,
+                    &block_color(&pipe_color(), alpha),
+                    out.solid(),
+                );
+            }
+
+            let button_length = button_length_anim(&activation, &next_activation, scaling.x)
+                .eval(tick_time.tick_progress());
+
+            for dir in custom.button_dirs() {
+                render_bridge(
+                    &Bridge {
+                        center: *center,
+                        dir,
+                        offset: scaling.x / 2.0,
+                        length: button_length,
+                        size: 0.4,
+                        color: block_color(&button_color(), alpha),
+                    },
+                    transform,
+                    out,
+                );
+            }
+
+            let bridge_length =
+                bridge_length_anim(0.05, 0.3, activation.is_some()).eval(tick_time.tick_progress());
+
+            for dir in custom.spawn_dirs() {
+                render_bridge(
+                    &Bridge {
+                        center: *center,
+                        dir,
+                        offset: scaling.x / 2.0,
+                        length: bridge_length,
+                        size: 0.25,
+                        color: block_color(&patient_bridge_color(), alpha),
+                    },
+                    transform,
+                    out,
+                );
+            }
+        }
     }
 }
 
@@ -1,4 +1,5 @@
 pub mod floor;
+pub mod layers;
 pub mod machine;
 pub mod wind;
 